@@ -0,0 +1,212 @@
+//! Derive macros for magneto, use them through the re-exports in `magneto::graphics`.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Fields, Ident, Index, Member};
+
+/// Implements `HasLayout` for a struct, with one vertex attribute per field in declaration order.
+///
+/// Each field type must implement `magneto::graphics::AttributeFormat`, which covers `f32`,
+/// `u32`, `i32`, arrays of them with up to four elements, and nalgebra vectors, points and
+/// square matrices. Matrices span one shader location per column. Offsets are taken from the
+/// struct itself, so padding between fields is respected.
+///
+/// Field attributes:
+///
+/// * `#[layout(skip)]` - The field is not passed to the shader and takes no location.
+/// * `#[layout(format = Float32x3)]` - Use the given `wgpu::VertexFormat` for a single
+///   location, instead of the format of the field type.
+#[proc_macro_derive(HasLayout, attributes(layout))]
+pub fn derive_has_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    has_layout(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+/// How a field is laid out, from its `#[layout(...)]` attributes.
+enum FieldLayout {
+    /// Use the `AttributeFormat` of the field type.
+    Type,
+    Skip,
+    Format(Ident),
+}
+
+fn field_layout(field: &syn::Field) -> syn::Result<FieldLayout> {
+    let mut layout = FieldLayout::Type;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("layout")) {
+        attr.parse_nested_meta(|meta| {
+            if !matches!(layout, FieldLayout::Type) {
+                return Err(meta.error("only one of `skip` or `format` may be given"));
+            }
+            if meta.path.is_ident("skip") {
+                layout = FieldLayout::Skip;
+                Ok(())
+            } else if meta.path.is_ident("format") {
+                layout = FieldLayout::Format(meta.value()?.parse()?);
+                Ok(())
+            } else {
+                Err(meta.error("expected `skip` or `format = ...`"))
+            }
+        })?;
+    }
+    Ok(layout)
+}
+
+/// The fields of a struct with the members used to access them.
+fn struct_fields<'a>(
+    input: &'a DeriveInput,
+    derive: &str,
+) -> syn::Result<Vec<(&'a syn::Field, Member)>> {
+    let fields = match &input.data {
+        Data::Struct(data) => &data.fields,
+        _ => {
+            return Err(syn::Error::new(
+                input.ident.span(),
+                format!("{} can only be derived for structs", derive),
+            ))
+        }
+    };
+
+    let members: Vec<Member> = match fields {
+        Fields::Named(fields) => fields
+            .named
+            .iter()
+            .map(|f| Member::Named(f.ident.clone().unwrap()))
+            .collect(),
+        Fields::Unnamed(fields) => (0..fields.unnamed.len())
+            .map(|i| Member::Unnamed(Index::from(i)))
+            .collect(),
+        Fields::Unit => Vec::new(),
+    };
+    Ok(fields.iter().zip(members).collect())
+}
+
+fn has_layout(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let mut attributes = Vec::new();
+    for (field, member) in struct_fields(&input, "HasLayout")? {
+        let ty = &field.ty;
+        let offset = quote!(::core::mem::offset_of!(Self, #member) as u64);
+        match field_layout(field)? {
+            FieldLayout::Skip => {}
+            FieldLayout::Type => attributes.push(quote! {
+                attributes.extend(
+                    <#ty as ::magneto::graphics::AttributeFormat>::attributes(#offset, location),
+                );
+                location += <#ty as ::magneto::graphics::AttributeFormat>::LOCATIONS;
+            }),
+            FieldLayout::Format(format) => attributes.push(quote! {
+                attributes.push(::magneto::__private::wgpu::VertexAttribute {
+                    format: ::magneto::__private::wgpu::VertexFormat::#format,
+                    offset: #offset,
+                    shader_location: location,
+                });
+                location += 1;
+            }),
+        }
+    }
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    Ok(quote! {
+        impl #impl_generics ::magneto::graphics::HasLayout for #name #ty_generics #where_clause {
+            #[allow(unused_mut)]
+            fn layout(
+                shader_offset: u32,
+            ) -> ::std::vec::Vec<::magneto::__private::wgpu::VertexAttribute> {
+                let mut attributes = ::std::vec::Vec::new();
+                let mut location = shader_offset;
+                #(#attributes)*
+                let _ = location;
+                attributes
+            }
+        }
+    })
+}
+
+/// Implements `UniformLayout` for a `#[repr(C)]` struct, checking at compile time that it matches
+/// the layout of the same struct in a WGSL uniform buffer.
+///
+/// Each field type must implement `magneto::graphics::UniformLayout`. Every field must be at the
+/// offset WGSL gives it and the struct must be padded to a multiple of 16 bytes, otherwise the
+/// crate fails to compile with an error naming the field. Generic structs are checked when their
+/// `SIZE` is first used, for example by `UniformBuffer::new`.
+///
+/// Field attributes:
+///
+/// * `#[uniform(skip)]` - The field is padding that is not declared in the shader.
+#[proc_macro_derive(UniformLayout, attributes(uniform))]
+pub fn derive_uniform_layout(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    uniform_layout(input)
+        .unwrap_or_else(syn::Error::into_compile_error)
+        .into()
+}
+
+fn is_skipped(field: &syn::Field) -> syn::Result<bool> {
+    let mut skip = false;
+    for attr in field.attrs.iter().filter(|a| a.path().is_ident("uniform")) {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("skip") {
+                skip = true;
+                Ok(())
+            } else {
+                Err(meta.error("expected `skip`"))
+            }
+        })?;
+    }
+    Ok(skip)
+}
+
+fn uniform_layout(input: DeriveInput) -> syn::Result<TokenStream2> {
+    let name = &input.ident;
+    let trait_path = quote!(::magneto::graphics::UniformLayout);
+
+    let mut checks = Vec::new();
+    for (field, member) in struct_fields(&input, "UniformLayout")? {
+        if is_skipped(field)? {
+            continue;
+        }
+        let ty = &field.ty;
+        let message = format!(
+            "`{}::{}` is not at its WGSL uniform offset, check the padding before it",
+            name,
+            quote!(#member)
+        );
+        checks.push(quote! {
+            let offset = end.next_multiple_of(<#ty as #trait_path>::ALIGN);
+            assert!(::core::mem::offset_of!(Self, #member) == offset, #message);
+            end = offset + <#ty as #trait_path>::SIZE;
+        });
+    }
+
+    let message = format!(
+        "`{}` is not padded to its WGSL uniform size, a multiple of 16 bytes",
+        name
+    );
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut output = quote! {
+        impl #impl_generics #trait_path for #name #ty_generics #where_clause {
+            const ALIGN: usize = 16;
+            #[allow(unused_mut)]
+            const SIZE: usize = {
+                let mut end = 0usize;
+                #(#checks)*
+                let size = end.next_multiple_of(<Self as #trait_path>::ALIGN);
+                assert!(::core::mem::size_of::<Self>() == size, #message);
+                size
+            };
+        }
+
+        impl #impl_generics ::magneto::graphics::UniformStruct for #name #ty_generics #where_clause {}
+    };
+
+    // Report layout errors at the definition rather than the first use, where possible
+    if input.generics.params.is_empty() {
+        output.extend(quote! {
+            const _: usize = <#name as #trait_path>::SIZE;
+        });
+    }
+    Ok(output)
+}
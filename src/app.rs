@@ -0,0 +1,345 @@
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+use anyhow::{bail, Result};
+use winit::dpi::PhysicalSize;
+use winit::event::{Event, WindowEvent};
+use winit::event_loop::{ControlFlow, EventLoop};
+use winit::window::{Window, WindowBuilder};
+
+use crate::camera::Projection;
+use crate::graphics::{Display, DisplayBuilder};
+use crate::input::Input;
+
+/// Settings for the window and update loop created by `run`.
+#[derive(Clone, Debug)]
+pub struct AppConfig {
+    pub title: String,
+    pub size: PhysicalSize<u32>,
+    /// Adapter, device and present mode settings for the display.
+    pub display: DisplayBuilder,
+    /// Interval between calls to `App::fixed_update`, must be above zero.
+    pub fixed_timestep: Duration,
+    /// Longest frame time fed into the fixed update accumulator. Keeps a long stall, such as
+    /// dragging the window, from being followed by a burst of fixed updates.
+    pub max_frame_time: Duration,
+}
+
+impl Default for AppConfig {
+    fn default() -> Self {
+        AppConfig {
+            title: "magneto".to_string(),
+            size: PhysicalSize::new(1280, 720),
+            display: DisplayBuilder::new(),
+            fixed_timestep: Duration::from_secs_f64(1.0 / 60.0),
+            max_frame_time: Duration::from_millis(250),
+        }
+    }
+}
+
+/// Frame time statistics over a window of recent frames.
+#[derive(Clone, Debug)]
+pub struct FrameStats {
+    /// Number of frames since the runner started.
+    pub frame_count: u64,
+    /// Duration of the most recent frame.
+    pub frame_time: Duration,
+    recent: VecDeque<Duration>,
+}
+
+impl FrameStats {
+    /// Number of frames the averages are taken over.
+    const WINDOW: usize = 120;
+
+    pub fn new() -> FrameStats {
+        FrameStats {
+            frame_count: 0,
+            frame_time: Duration::ZERO,
+            recent: VecDeque::with_capacity(Self::WINDOW),
+        }
+    }
+
+    /// Records a frame that took `frame_time`.
+    pub fn record(&mut self, frame_time: Duration) {
+        self.frame_count += 1;
+        self.frame_time = frame_time;
+        if self.recent.len() == Self::WINDOW {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(frame_time);
+    }
+
+    pub fn average_frame_time(&self) -> Duration {
+        if self.recent.is_empty() {
+            return Duration::ZERO;
+        }
+        self.recent.iter().sum::<Duration>() / self.recent.len() as u32
+    }
+
+    pub fn min_frame_time(&self) -> Duration {
+        self.recent.iter().copied().min().unwrap_or_default()
+    }
+
+    pub fn max_frame_time(&self) -> Duration {
+        self.recent.iter().copied().max().unwrap_or_default()
+    }
+
+    /// Frames per second from the average frame time.
+    pub fn fps(&self) -> f32 {
+        let average = self.average_frame_time().as_secs_f32();
+        if average > 0.0 {
+            1.0 / average
+        } else {
+            0.0
+        }
+    }
+}
+
+impl Default for FrameStats {
+    fn default() -> Self {
+        FrameStats::new()
+    }
+}
+
+/// Accumulates frame time and counts how many fixed updates are due.
+#[derive(Clone, Debug)]
+pub struct FixedTimestep {
+    step: Duration,
+    max_frame_time: Duration,
+    accumulator: Duration,
+}
+
+impl FixedTimestep {
+    /// Panics if `step` is zero.
+    pub fn new(step: Duration, max_frame_time: Duration) -> FixedTimestep {
+        assert!(!step.is_zero(), "Fixed timestep must be above zero");
+        FixedTimestep {
+            step,
+            max_frame_time,
+            accumulator: Duration::ZERO,
+        }
+    }
+
+    pub fn step(&self) -> Duration {
+        self.step
+    }
+
+    /// Adds a frame of `dt`, capped at the max frame time, and returns the number of fixed
+    /// updates now due.
+    pub fn advance(&mut self, dt: Duration) -> u32 {
+        self.accumulator += dt.min(self.max_frame_time);
+        let mut steps = 0;
+        while self.accumulator >= self.step {
+            self.accumulator -= self.step;
+            steps += 1;
+        }
+        steps
+    }
+
+    /// Fraction of a step accumulated towards the next fixed update, from 0 up to 1.
+    pub fn alpha(&self) -> f32 {
+        self.accumulator.as_secs_f32() / self.step.as_secs_f32()
+    }
+}
+
+/// State owned by the runner and shared with every `App` hook.
+pub struct AppContext {
+    pub window: Window,
+    pub display: Display,
+    pub input: Input,
+    pub stats: FrameStats,
+    /// Projection resized along with the display, before `App::resize` is called. Set it in
+    /// `App::init` to have its aspect ratio follow the window.
+    pub projection: Option<Box<dyn Projection>>,
+    exit: bool,
+}
+
+impl AppContext {
+    /// Asks the runner to shut down after the current event.
+    pub fn exit(&mut self) {
+        self.exit = true;
+    }
+}
+
+/// The target of a single rendered frame.
+pub struct Frame<'a> {
+    /// Colour attachment to render into, multisampled if the display uses MSAA.
+    pub view: &'a wgpu::TextureView,
+    /// Frame image `view` must be resolved into when it is multisampled.
+    pub resolve_target: Option<&'a wgpu::TextureView>,
+    pub depth: &'a wgpu::TextureView,
+    /// Fraction of a fixed timestep elapsed since the last `fixed_update`, for interpolating
+    /// between the previous and current fixed update state.
+    pub alpha: f32,
+}
+
+/// An application driven by `run`.
+pub trait App: Sized + 'static {
+    /// Creates the application once the window and display exist.
+    fn init(ctx: &mut AppContext) -> Result<Self>;
+
+    /// Called once per frame with the time since the previous frame.
+    fn update(&mut self, _ctx: &mut AppContext, _dt: Duration) {}
+
+    /// Called zero or more times per frame, at the fixed timestep set in `AppConfig`.
+    fn fixed_update(&mut self, _ctx: &mut AppContext, _step: Duration) {}
+
+    fn render(&mut self, ctx: &AppContext, frame: &Frame);
+
+    /// Called after the display is resized to a new, non-zero window size.
+    fn resize(&mut self, _ctx: &mut AppContext, _size: PhysicalSize<u32>) {}
+
+    /// Called for every window event, after `ctx.input` has been updated.
+    fn window_event(&mut self, _ctx: &mut AppContext, _event: &WindowEvent) {}
+
+    /// Called once when the event loop shuts down, before the app and display are dropped.
+    fn exit(&mut self, _ctx: &mut AppContext) {}
+}
+
+/// Runner state that lives inside the event loop.
+struct Runner<A> {
+    app: A,
+    ctx: AppContext,
+    last_frame: Instant,
+    timestep: FixedTimestep,
+}
+
+impl<A> Runner<A>
+where
+    A: App,
+{
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        let size = self.ctx.display.reload_swapchain(size);
+        if !self.ctx.display.is_minimised() {
+            if let Some(ref mut projection) = self.ctx.projection {
+                projection.resize(size);
+            }
+            self.app.resize(&mut self.ctx, size);
+        }
+    }
+
+    fn update(&mut self) {
+        let now = Instant::now();
+        let dt = now - self.last_frame;
+        self.last_frame = now;
+        self.ctx.stats.record(dt);
+
+        self.app.update(&mut self.ctx, dt);
+
+        let step = self.timestep.step();
+        for _ in 0..self.timestep.advance(dt) {
+            self.app.fixed_update(&mut self.ctx, step);
+        }
+
+        self.ctx.window.request_redraw();
+    }
+
+    fn render(&mut self) {
+        match self.ctx.display.begin_frame() {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                log::error!("Unable to acquire frame: {}", e);
+                self.ctx.exit();
+                return;
+            }
+        }
+
+        let display = &self.ctx.display;
+        let frame = Frame {
+            view: display
+                .msaa
+                .as_ref()
+                .map_or(display.frame_view(), |msaa| &msaa.view),
+            resolve_target: display.msaa.as_ref().map(|_| display.frame_view()),
+            depth: &display.depth.view,
+            alpha: self.timestep.alpha(),
+        };
+        self.app.render(&self.ctx, &frame);
+        self.ctx.display.end_frame();
+    }
+
+    fn handle_event(&mut self, event: &Event<()>) {
+        self.ctx.input.handle_event(event);
+
+        match event {
+            Event::WindowEvent { event, window_id } if *window_id == self.ctx.window.id() => {
+                match event {
+                    WindowEvent::CloseRequested => self.ctx.exit(),
+                    WindowEvent::Resized(size) => self.resize(*size),
+                    WindowEvent::ScaleFactorChanged { new_inner_size, .. } => {
+                        self.resize(**new_inner_size)
+                    }
+                    _ => {}
+                }
+                self.app.window_event(&mut self.ctx, event);
+            }
+            Event::MainEventsCleared => self.update(),
+            Event::RedrawRequested(window_id) if *window_id == self.ctx.window.id() => {
+                self.render()
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Creates a window and display, then runs `A` until the window is closed or
+/// `AppContext::exit` is called.
+///
+/// Only returns if the config is invalid or creating the window, display or app fails. On
+/// shutdown `App::exit` is called and the app is dropped before the display, then the process
+/// exits.
+pub fn run<A>(config: AppConfig) -> Result<()>
+where
+    A: App,
+{
+    if config.fixed_timestep.is_zero() {
+        bail!("AppConfig::fixed_timestep must be above zero.");
+    }
+
+    let event_loop = EventLoop::new();
+    let window = WindowBuilder::new()
+        .with_title(&config.title)
+        .with_inner_size(config.size)
+        .build(&event_loop)?;
+    let display = pollster::block_on(config.display.build(&window))?;
+
+    let mut ctx = AppContext {
+        window,
+        display,
+        input: Input::new(),
+        stats: FrameStats::new(),
+        projection: None,
+        exit: false,
+    };
+    let app = A::init(&mut ctx)?;
+
+    let mut runner = Some(Runner {
+        app,
+        ctx,
+        last_frame: Instant::now(),
+        timestep: FixedTimestep::new(config.fixed_timestep, config.max_frame_time),
+    });
+
+    event_loop.run(move |event, _, control_flow| {
+        *control_flow = ControlFlow::Poll;
+
+        if let Event::LoopDestroyed = event {
+            if let Some(Runner {
+                mut app, mut ctx, ..
+            }) = runner.take()
+            {
+                app.exit(&mut ctx);
+                drop(app);
+            }
+            return;
+        }
+
+        if let Some(ref mut runner) = runner {
+            runner.handle_event(&event);
+            if runner.ctx.exit {
+                *control_flow = ControlFlow::Exit;
+            }
+        }
+    })
+}
@@ -1,10 +1,24 @@
-use nalgebra::{vector, Matrix4, Point3, Vector3};
-use winit::event::VirtualKeyCode as Keycode;
+use nalgebra::{vector, Matrix4, Point3, Rotation3, Unit, Vector3};
+use winit::event::{MouseButton, MouseScrollDelta};
 
-type KeySet = std::collections::HashSet<Keycode>;
+use crate::graphics::Bounds;
+use crate::input::{Actions, PIXELS_PER_LINE};
+
+pub mod projection;
+pub use projection::*;
+
+// Names of the actions and axes read by the camera controllers, defined in `input`.
+pub use crate::input::{LOOK_X, LOOK_Y, MOVE_FORWARD, MOVE_RIGHT, MOVE_UP, ROLL, SPRINT, ZOOM};
+
+type ButtonSet = std::collections::HashSet<MouseButton>;
 
 pub trait Camera {
     fn view_matrix(&self) -> Matrix4<f32>;
+
+    /// Combined matrix transforming world space to clip space through `projection`.
+    fn view_projection(&self, projection: &dyn Projection) -> Matrix4<f32> {
+        projection.projection_matrix() * self.view_matrix()
+    }
 }
 
 pub struct FPSCamera {
@@ -13,8 +27,13 @@ pub struct FPSCamera {
     up: Vector3<f32>,
     yaw: f32,
     pitch: f32,
+    roll: f32,
     sensitivity: f32,
     speed: f32,
+    /// Radians per second when looking or rolling with keys or a gamepad.
+    pub turn_speed: f32,
+    /// Speed multiplier while sprinting.
+    pub sprint_multiplier: f32,
 }
 
 impl FPSCamera {
@@ -25,51 +44,58 @@ impl FPSCamera {
             up: vector![0.0, 1.0, 0.0],
             yaw: 0.0,
             pitch: 0.0,
+            roll: 0.0,
             sensitivity,
             speed,
+            turn_speed: 1.5,
+            sprint_multiplier: 2.5,
         }
     }
 
-    pub fn update(&mut self, keys: &KeySet, dt: std::time::Duration) {
+    pub fn update(&mut self, actions: &Actions, dt: std::time::Duration) {
         // Delta time seconds, for ease of use in calculations
         let dt = dt.as_secs_f32();
 
+        let turn = dt * self.turn_speed;
+        self.yaw += actions.axis(LOOK_X) * turn;
+        self.pitch += actions.axis(LOOK_Y) * turn;
+        self.roll += actions.axis(ROLL) * turn;
+        self.clamp_pitch();
+
         // Calculate normalized front from yaw and pitch
-        self.front.x = self.yaw.sin();
+        self.front.x = self.yaw.sin() * self.pitch.cos();
         self.front.y = self.pitch.sin();
-        self.front.z = self.yaw.cos();
+        self.front.z = self.yaw.cos() * self.pitch.cos();
         self.front.normalize_mut();
 
+        // Roll the world up vector around the view direction
+        let roll = Rotation3::from_axis_angle(&Unit::new_normalize(self.front), -self.roll);
+        self.up = roll * vector![0.0, 1.0, 0.0];
+
         // Normalized camera right vector
         let right = self.up.cross(&self.front).normalize();
 
-        // Move forward/back
-        if keys.contains(&Keycode::W) {
-            self.pos -= self.front * dt * self.speed;
-        }
-        if keys.contains(&Keycode::S) {
-            self.pos += self.front * dt * self.speed;
+        let mut speed = self.speed;
+        if actions.pressed(SPRINT) {
+            speed *= self.sprint_multiplier;
         }
 
-        // Strafe
-        if keys.contains(&Keycode::D) {
-            self.pos += right * dt * self.speed;
-        }
-        if keys.contains(&Keycode::A) {
-            self.pos -= right * dt * self.speed;
-        }
+        let movement = self.front * actions.axis(MOVE_FORWARD)
+            + right * actions.axis(MOVE_RIGHT)
+            + self.up * actions.axis(MOVE_UP);
+        self.pos += movement * dt * speed;
     }
 
     pub fn mouse_moved(&mut self, delta: (f64, f64)) {
-        self.yaw -= delta.0 as f32 * self.sensitivity;
-        self.pitch += delta.1 as f32 * self.sensitivity;
+        self.yaw += delta.0 as f32 * self.sensitivity;
+        self.pitch -= delta.1 as f32 * self.sensitivity;
+        self.clamp_pitch();
+    }
 
-        if self.pitch > std::f32::consts::FRAC_PI_2 {
-            self.pitch = std::f32::consts::FRAC_PI_2;
-        }
-        if self.pitch < -std::f32::consts::FRAC_PI_2 {
-            self.pitch = -std::f32::consts::FRAC_PI_2;
-        }
+    fn clamp_pitch(&mut self) {
+        // Stay short of straight up or down, where `front` is parallel to `up`
+        let max_pitch = std::f32::consts::FRAC_PI_2 - 0.01;
+        self.pitch = self.pitch.clamp(-max_pitch, max_pitch);
     }
 }
 
@@ -78,3 +104,117 @@ impl Camera for FPSCamera {
         Matrix4::look_at_lh(&self.pos, &(self.pos + self.front), &self.up)
     }
 }
+
+/// Camera rotating around a target point, for model viewers and editors.
+///
+/// Left-drag orbits, middle-drag pans the target and the scroll wheel zooms. With a yaw and pitch
+/// of zero the camera sits behind the target on -Z, looking down +Z.
+pub struct OrbitCamera {
+    pub target: Point3<f32>,
+    pub distance: f32,
+    pub yaw: f32,
+    pub pitch: f32,
+    pub min_distance: f32,
+    pub max_distance: f32,
+    /// Largest pitch angle away from the horizon, kept below a right angle so `up` stays valid.
+    pub max_pitch: f32,
+    /// Radians per pixel of mouse motion.
+    pub sensitivity: f32,
+    /// Radians per second when orbiting with keys or a gamepad.
+    pub key_speed: f32,
+    /// Fraction of the distance zoomed per scroll line.
+    pub zoom_speed: f32,
+    up: Vector3<f32>,
+}
+
+impl OrbitCamera {
+    pub fn new(target: Point3<f32>, distance: f32, sensitivity: f32) -> OrbitCamera {
+        OrbitCamera {
+            target,
+            distance,
+            yaw: 0.0,
+            pitch: 0.0,
+            min_distance: 0.01,
+            max_distance: f32::INFINITY,
+            max_pitch: std::f32::consts::FRAC_PI_2 - 0.01,
+            sensitivity,
+            key_speed: 1.5,
+            zoom_speed: 0.1,
+            up: vector![0.0, 1.0, 0.0],
+        }
+    }
+
+    /// World space position of the camera.
+    pub fn position(&self) -> Point3<f32> {
+        let offset = vector![
+            -self.pitch.cos() * self.yaw.sin(),
+            self.pitch.sin(),
+            -self.pitch.cos() * self.yaw.cos()
+        ];
+        self.target + offset * self.distance
+    }
+
+    /// Orbits with the look axes and zooms with the zoom axis.
+    pub fn update(&mut self, actions: &Actions, dt: std::time::Duration) {
+        let step = dt.as_secs_f32() * self.key_speed;
+
+        self.yaw -= actions.axis(LOOK_X) * step;
+        self.pitch += actions.axis(LOOK_Y) * step;
+        self.distance *= 1.0 - actions.axis(ZOOM) * step;
+
+        self.clamp();
+    }
+
+    /// Orbits while the left button is held and pans while the middle button is held.
+    pub fn mouse_moved(&mut self, delta: (f64, f64), buttons: &ButtonSet) {
+        let (dx, dy) = (delta.0 as f32, delta.1 as f32);
+
+        if buttons.contains(&MouseButton::Left) {
+            self.yaw -= dx * self.sensitivity;
+            self.pitch += dy * self.sensitivity;
+        }
+
+        if buttons.contains(&MouseButton::Middle) {
+            // Move the target in the view plane, scaled so the scene roughly follows the cursor
+            let front = (self.target - self.position()).normalize();
+            let right = self.up.cross(&front).normalize();
+            let up = front.cross(&right);
+            let scale = self.distance * self.sensitivity;
+            self.target += (up * dy - right * dx) * scale;
+        }
+
+        self.clamp();
+    }
+
+    /// Zooms towards or away from the target.
+    pub fn scrolled(&mut self, delta: MouseScrollDelta) {
+        let lines = match delta {
+            MouseScrollDelta::LineDelta(_, y) => y,
+            MouseScrollDelta::PixelDelta(pos) => pos.y as f32 / PIXELS_PER_LINE,
+        };
+        self.distance *= (1.0 - self.zoom_speed).powf(lines);
+        self.clamp();
+    }
+
+    /// Centres the camera on `bounds` and moves back until the bounding sphere fits in view.
+    ///
+    /// `fovy` is the vertical field of view of the projection, pass the smaller of the horizontal
+    /// and vertical fields of view for narrow targets. `max_distance` is raised if needed.
+    pub fn frame(&mut self, bounds: &Bounds, fovy: f32) {
+        self.target = bounds.sphere.center;
+        self.distance = bounds.sphere.radius / (fovy / 2.0).sin();
+        self.max_distance = self.max_distance.max(self.distance);
+        self.clamp();
+    }
+
+    fn clamp(&mut self) {
+        self.pitch = self.pitch.clamp(-self.max_pitch, self.max_pitch);
+        self.distance = self.distance.clamp(self.min_distance, self.max_distance);
+    }
+}
+
+impl Camera for OrbitCamera {
+    fn view_matrix(&self) -> Matrix4<f32> {
+        Matrix4::look_at_lh(&self.position(), &self.target, &self.up)
+    }
+}
@@ -0,0 +1,172 @@
+use nalgebra::Matrix4;
+use winit::dpi::PhysicalSize;
+
+use crate::graphics::Display;
+
+/// A projection from left-handed view space, looking down +Z, to wgpu clip space.
+///
+/// Clip space depth is in the range 0..1, matching wgpu.
+pub trait Projection {
+    fn projection_matrix(&self) -> Matrix4<f32>;
+
+    /// Updates the aspect ratio for a new render target size.
+    ///
+    /// `app::run` calls this for the projection in `AppContext::projection` whenever the display
+    /// is resized. Other projections are passed the size returned by `Display::reload_swapchain`.
+    /// Zero sized targets, such as a minimised window, are ignored.
+    fn resize(&mut self, size: PhysicalSize<u32>);
+}
+
+/// Aspect ratio of `size`, or `None` if it has no area.
+fn aspect_ratio(size: PhysicalSize<u32>) -> Option<f32> {
+    if size.width == 0 || size.height == 0 {
+        None
+    } else {
+        Some(size.width as f32 / size.height as f32)
+    }
+}
+
+/// Aspect ratio of the display's render target, falling back to 1 if it has no area.
+fn display_aspect(dpy: &Display) -> f32 {
+    aspect_ratio(PhysicalSize::new(dpy.sc_desc.width, dpy.sc_desc.height)).unwrap_or(1.0)
+}
+
+/// Perspective projection mapping `znear` to depth 0 and `zfar` to depth 1.
+#[derive(Copy, Clone, Debug)]
+pub struct Perspective {
+    /// Vertical field of view in radians.
+    pub fovy: f32,
+    pub aspect: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl Perspective {
+    pub fn new(fovy: f32, aspect: f32, znear: f32, zfar: f32) -> Perspective {
+        Perspective {
+            fovy,
+            aspect,
+            znear,
+            zfar,
+        }
+    }
+
+    /// Creates a projection matching the aspect ratio of the display.
+    pub fn for_display(dpy: &Display, fovy: f32, znear: f32, zfar: f32) -> Perspective {
+        Perspective::new(fovy, display_aspect(dpy), znear, zfar)
+    }
+}
+
+impl Projection for Perspective {
+    #[rustfmt::skip]
+    fn projection_matrix(&self) -> Matrix4<f32> {
+        let f = 1.0 / (self.fovy / 2.0).tan();
+        let depth = self.zfar / (self.zfar - self.znear);
+        Matrix4::new(
+            f / self.aspect, 0.0, 0.0, 0.0,
+            0.0, f, 0.0, 0.0,
+            0.0, 0.0, depth, -self.znear * depth,
+            0.0, 0.0, 1.0, 0.0,
+        )
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        if let Some(aspect) = aspect_ratio(size) {
+            self.aspect = aspect;
+        }
+    }
+}
+
+/// Perspective projection with reversed depth and no far plane.
+///
+/// `znear` maps to depth 1 and infinity to depth 0, which spreads floating point depth precision
+/// evenly over the view distance. Pipelines must use `CompareFunction::Greater` (or
+/// `GreaterEqual`) and depth must be cleared to 0.
+#[derive(Copy, Clone, Debug)]
+pub struct InfinitePerspective {
+    /// Vertical field of view in radians.
+    pub fovy: f32,
+    pub aspect: f32,
+    pub znear: f32,
+}
+
+impl InfinitePerspective {
+    pub fn new(fovy: f32, aspect: f32, znear: f32) -> InfinitePerspective {
+        InfinitePerspective {
+            fovy,
+            aspect,
+            znear,
+        }
+    }
+
+    /// Creates a projection matching the aspect ratio of the display.
+    pub fn for_display(dpy: &Display, fovy: f32, znear: f32) -> InfinitePerspective {
+        InfinitePerspective::new(fovy, display_aspect(dpy), znear)
+    }
+}
+
+impl Projection for InfinitePerspective {
+    #[rustfmt::skip]
+    fn projection_matrix(&self) -> Matrix4<f32> {
+        let f = 1.0 / (self.fovy / 2.0).tan();
+        Matrix4::new(
+            f / self.aspect, 0.0, 0.0, 0.0,
+            0.0, f, 0.0, 0.0,
+            0.0, 0.0, 0.0, self.znear,
+            0.0, 0.0, 1.0, 0.0,
+        )
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        if let Some(aspect) = aspect_ratio(size) {
+            self.aspect = aspect;
+        }
+    }
+}
+
+/// Orthographic projection of a box centred on the view axis.
+///
+/// The visible height stays fixed, the width follows the aspect ratio.
+#[derive(Copy, Clone, Debug)]
+pub struct Orthographic {
+    /// Height of the visible area in view space units.
+    pub height: f32,
+    pub aspect: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl Orthographic {
+    pub fn new(height: f32, aspect: f32, znear: f32, zfar: f32) -> Orthographic {
+        Orthographic {
+            height,
+            aspect,
+            znear,
+            zfar,
+        }
+    }
+
+    /// Creates a projection matching the aspect ratio of the display.
+    pub fn for_display(dpy: &Display, height: f32, znear: f32, zfar: f32) -> Orthographic {
+        Orthographic::new(height, display_aspect(dpy), znear, zfar)
+    }
+}
+
+impl Projection for Orthographic {
+    #[rustfmt::skip]
+    fn projection_matrix(&self) -> Matrix4<f32> {
+        let depth = 1.0 / (self.zfar - self.znear);
+        Matrix4::new(
+            2.0 / (self.height * self.aspect), 0.0, 0.0, 0.0,
+            0.0, 2.0 / self.height, 0.0, 0.0,
+            0.0, 0.0, depth, -self.znear * depth,
+            0.0, 0.0, 0.0, 1.0,
+        )
+    }
+
+    fn resize(&mut self, size: PhysicalSize<u32>) {
+        if let Some(aspect) = aspect_ratio(size) {
+            self.aspect = aspect;
+        }
+    }
+}
@@ -1,5 +1,12 @@
-use super::{Display, Texture};
+use std::ops::Deref;
 
+use anyhow::{anyhow, bail, Result};
+
+use super::{Display, ShaderReflection, Texture};
+
+/// Builds a bind group, binding resources in the order they are added.
+///
+/// Prefer `BindGroupLayout::bind_group`, which binds by name and checks against the layout.
 pub struct BindGroupBuilder<'a> {
     layout: &'a wgpu::BindGroupLayout,
     resources: Vec<wgpu::BindingResource<'a>>,
@@ -28,6 +35,19 @@ impl<'a> BindGroupBuilder<'a> {
         self
     }
 
+    // Pushes a storage buffer onto the resources
+    pub fn with_storage_buffer(mut self, buffer: &'a wgpu::Buffer) -> Self {
+        self.resources.push(buffer.as_entire_binding());
+        self
+    }
+
+    // Pushes a texture view onto the resources, without a sampler (e.g. a storage texture)
+    pub fn with_texture_view(mut self, view: &'a wgpu::TextureView) -> Self {
+        self.resources
+            .push(wgpu::BindingResource::TextureView(view));
+        self
+    }
+
     // Builds the BindGroup
     pub fn build(self, dpy: &Display) -> wgpu::BindGroup {
         let entries: Vec<wgpu::BindGroupEntry> = self
@@ -48,10 +68,19 @@ impl<'a> BindGroupBuilder<'a> {
     }
 }
 
+/// Builds a bind group layout with bindings numbered in the order they are added.
+///
+/// See `BindGroupLayoutBuilder` for explicit bindings and other binding types.
 pub struct BglBuilder {
     entries: Vec<wgpu::BindGroupLayoutEntry>,
 }
 
+impl Default for BglBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl BglBuilder {
     pub fn new() -> BglBuilder {
         BglBuilder {
@@ -94,7 +123,7 @@ impl BglBuilder {
 
     pub fn with_sampler(mut self) -> BglBuilder {
         self.entries.push(wgpu::BindGroupLayoutEntry {
-            binding: 1,
+            binding: self.next_index(),
             visibility: wgpu::ShaderStage::FRAGMENT,
             ty: wgpu::BindingType::Sampler {
                 filtering: true,
@@ -105,6 +134,59 @@ impl BglBuilder {
         self
     }
 
+    pub fn with_compute_uniforms(mut self) -> BglBuilder {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.next_index(),
+            visibility: wgpu::ShaderStage::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Uniform,
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+        self
+    }
+
+    /// Storage buffers bound to vertex or fragment stages need `read_only`, unless the adapter
+    /// supports `Features::VERTEX_WRITABLE_STORAGE`.
+    pub fn with_storage_buffer(
+        mut self,
+        visibility: wgpu::ShaderStage,
+        read_only: bool,
+    ) -> BglBuilder {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.next_index(),
+            visibility,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        });
+        self
+    }
+
+    pub fn with_storage_texture(
+        mut self,
+        visibility: wgpu::ShaderStage,
+        format: wgpu::TextureFormat,
+        access: wgpu::StorageTextureAccess,
+    ) -> BglBuilder {
+        self.entries.push(wgpu::BindGroupLayoutEntry {
+            binding: self.next_index(),
+            visibility,
+            ty: wgpu::BindingType::StorageTexture {
+                access,
+                format,
+                view_dimension: wgpu::TextureViewDimension::D2,
+            },
+            count: None,
+        });
+        self
+    }
+
     pub fn build(self, dpy: &Display) -> wgpu::BindGroupLayout {
         dpy.device
             .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
@@ -113,3 +195,509 @@ impl BglBuilder {
             })
     }
 }
+
+/// A named entry of a `BindGroupLayout`.
+#[derive(Clone, Debug)]
+pub struct NamedLayoutEntry {
+    pub name: String,
+    pub entry: wgpu::BindGroupLayoutEntry,
+}
+
+/// Builds a `BindGroupLayout` from named entries with explicit binding indices.
+///
+/// Entries are checked for duplicate bindings and names when building.
+#[derive(Clone, Debug, Default)]
+pub struct BindGroupLayoutBuilder {
+    label: Option<&'static str>,
+    entries: Vec<NamedLayoutEntry>,
+}
+
+impl BindGroupLayoutBuilder {
+    pub fn new() -> BindGroupLayoutBuilder {
+        BindGroupLayoutBuilder::default()
+    }
+
+    /// Starts from the bindings `reflection` declares in `group`, named after their variables.
+    pub fn from_reflection(reflection: &ShaderReflection, group: u32) -> BindGroupLayoutBuilder {
+        reflection
+            .bindings
+            .iter()
+            .filter(|b| b.group == group)
+            .fold(BindGroupLayoutBuilder::new(), |builder, b| {
+                let name = match &b.name {
+                    Some(name) => name.clone(),
+                    None => format!("binding_{}", b.binding),
+                };
+                builder.with_entry(&name, b.binding, b.visibility, b.ty)
+            })
+    }
+
+    pub fn with_label(mut self, label: &'static str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Adds an entry of any type.
+    pub fn with_entry(
+        mut self,
+        name: &str,
+        binding: u32,
+        visibility: wgpu::ShaderStage,
+        ty: wgpu::BindingType,
+    ) -> Self {
+        self.entries.push(NamedLayoutEntry {
+            name: name.to_string(),
+            entry: wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility,
+                ty,
+                count: None,
+            },
+        });
+        self
+    }
+
+    pub fn with_uniform(self, name: &str, binding: u32, visibility: wgpu::ShaderStage) -> Self {
+        let ty = buffer_type(wgpu::BufferBindingType::Uniform, false);
+        self.with_entry(name, binding, visibility, ty)
+    }
+
+    /// A uniform buffer whose offset is given when the bind group is set.
+    pub fn with_dynamic_uniform(
+        self,
+        name: &str,
+        binding: u32,
+        visibility: wgpu::ShaderStage,
+    ) -> Self {
+        let ty = buffer_type(wgpu::BufferBindingType::Uniform, true);
+        self.with_entry(name, binding, visibility, ty)
+    }
+
+    /// Storage buffers bound to vertex or fragment stages need `read_only`, unless the adapter
+    /// supports `Features::VERTEX_WRITABLE_STORAGE`.
+    pub fn with_storage(
+        self,
+        name: &str,
+        binding: u32,
+        visibility: wgpu::ShaderStage,
+        read_only: bool,
+    ) -> Self {
+        let ty = buffer_type(wgpu::BufferBindingType::Storage { read_only }, false);
+        self.with_entry(name, binding, visibility, ty)
+    }
+
+    /// A storage buffer whose offset is given when the bind group is set.
+    pub fn with_dynamic_storage(
+        self,
+        name: &str,
+        binding: u32,
+        visibility: wgpu::ShaderStage,
+        read_only: bool,
+    ) -> Self {
+        let ty = buffer_type(wgpu::BufferBindingType::Storage { read_only }, true);
+        self.with_entry(name, binding, visibility, ty)
+    }
+
+    /// A sampled texture. `sample_type` must match the texture format, for example
+    /// `Float { filterable: true }` for colour textures.
+    pub fn with_texture(
+        self,
+        name: &str,
+        binding: u32,
+        visibility: wgpu::ShaderStage,
+        view_dimension: wgpu::TextureViewDimension,
+        sample_type: wgpu::TextureSampleType,
+    ) -> Self {
+        let ty = wgpu::BindingType::Texture {
+            sample_type,
+            view_dimension,
+            multisampled: false,
+        };
+        self.with_entry(name, binding, visibility, ty)
+    }
+
+    /// A multisampled texture, read with `textureLoad`.
+    pub fn with_multisampled_texture(
+        self,
+        name: &str,
+        binding: u32,
+        visibility: wgpu::ShaderStage,
+        sample_type: wgpu::TextureSampleType,
+    ) -> Self {
+        let ty = wgpu::BindingType::Texture {
+            sample_type,
+            view_dimension: wgpu::TextureViewDimension::D2,
+            multisampled: true,
+        };
+        self.with_entry(name, binding, visibility, ty)
+    }
+
+    /// A depth texture, for use with a comparison sampler.
+    pub fn with_depth_texture(
+        self,
+        name: &str,
+        binding: u32,
+        visibility: wgpu::ShaderStage,
+        view_dimension: wgpu::TextureViewDimension,
+    ) -> Self {
+        self.with_texture(
+            name,
+            binding,
+            visibility,
+            view_dimension,
+            wgpu::TextureSampleType::Depth,
+        )
+    }
+
+    pub fn with_storage_texture(
+        self,
+        name: &str,
+        binding: u32,
+        visibility: wgpu::ShaderStage,
+        view_dimension: wgpu::TextureViewDimension,
+        format: wgpu::TextureFormat,
+        access: wgpu::StorageTextureAccess,
+    ) -> Self {
+        let ty = wgpu::BindingType::StorageTexture {
+            access,
+            format,
+            view_dimension,
+        };
+        self.with_entry(name, binding, visibility, ty)
+    }
+
+    /// A sampler, `filtering` must be false to sample non-filterable textures.
+    pub fn with_sampler(
+        self,
+        name: &str,
+        binding: u32,
+        visibility: wgpu::ShaderStage,
+        filtering: bool,
+    ) -> Self {
+        let ty = wgpu::BindingType::Sampler {
+            filtering,
+            comparison: false,
+        };
+        self.with_entry(name, binding, visibility, ty)
+    }
+
+    /// A sampler that compares against a depth texture, for shadow maps.
+    pub fn with_comparison_sampler(
+        self,
+        name: &str,
+        binding: u32,
+        visibility: wgpu::ShaderStage,
+    ) -> Self {
+        let ty = wgpu::BindingType::Sampler {
+            filtering: true,
+            comparison: true,
+        };
+        self.with_entry(name, binding, visibility, ty)
+    }
+
+    pub fn entries(&self) -> &[NamedLayoutEntry] {
+        &self.entries
+    }
+
+    /// Checks for duplicate bindings and names.
+    pub fn validate(&self) -> Result<()> {
+        for (i, a) in self.entries.iter().enumerate() {
+            for b in &self.entries[..i] {
+                if a.entry.binding == b.entry.binding {
+                    bail!(
+                        "Bind group layout entries `{}` and `{}` both use binding {}",
+                        b.name,
+                        a.name,
+                        a.entry.binding
+                    );
+                }
+                if a.name == b.name {
+                    bail!("Bind group layout has two entries named `{}`", a.name);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn build(self, dpy: &Display) -> Result<BindGroupLayout> {
+        self.validate()?;
+        let entries: Vec<_> = self.entries.iter().map(|e| e.entry).collect();
+        let layout = dpy
+            .device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: self.label,
+                entries: &entries,
+            });
+
+        Ok(BindGroupLayout {
+            layout,
+            entries: self.entries,
+        })
+    }
+}
+
+fn buffer_type(ty: wgpu::BufferBindingType, has_dynamic_offset: bool) -> wgpu::BindingType {
+    wgpu::BindingType::Buffer {
+        ty,
+        has_dynamic_offset,
+        min_binding_size: None,
+    }
+}
+
+/// A `wgpu::BindGroupLayout` that remembers its named entries, so bind groups can be built and
+/// checked by name.
+///
+/// Dereferences to the `wgpu::BindGroupLayout` for use in pipeline layouts.
+#[derive(Debug)]
+pub struct BindGroupLayout {
+    layout: wgpu::BindGroupLayout,
+    entries: Vec<NamedLayoutEntry>,
+}
+
+impl BindGroupLayout {
+    pub fn entries(&self) -> &[NamedLayoutEntry] {
+        &self.entries
+    }
+
+    pub fn entry(&self, name: &str) -> Option<&wgpu::BindGroupLayoutEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.name == name)
+            .map(|e| &e.entry)
+    }
+
+    /// Number of dynamic offsets to pass when setting a bind group with this layout.
+    pub fn dynamic_offset_count(&self) -> usize {
+        self.entries
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e.entry.ty,
+                    wgpu::BindingType::Buffer {
+                        has_dynamic_offset: true,
+                        ..
+                    }
+                )
+            })
+            .count()
+    }
+
+    /// Starts a bind group with this layout.
+    pub fn bind_group(&self) -> NamedBindGroupBuilder<'_> {
+        NamedBindGroupBuilder::new(self)
+    }
+}
+
+impl Deref for BindGroupLayout {
+    type Target = wgpu::BindGroupLayout;
+
+    fn deref(&self) -> &wgpu::BindGroupLayout {
+        &self.layout
+    }
+}
+
+/// Builds a bind group for a `BindGroupLayout`, setting resources by entry name.
+///
+/// `build` checks that every entry is set exactly once with a resource of the right kind.
+pub struct NamedBindGroupBuilder<'a> {
+    layout: &'a BindGroupLayout,
+    label: Option<&'static str>,
+    resources: Vec<(&'a str, wgpu::BindingResource<'a>)>,
+}
+
+impl<'a> NamedBindGroupBuilder<'a> {
+    pub fn new(layout: &'a BindGroupLayout) -> NamedBindGroupBuilder<'a> {
+        NamedBindGroupBuilder {
+            layout,
+            label: None,
+            resources: Vec::new(),
+        }
+    }
+
+    pub fn with_label(mut self, label: &'static str) -> Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub fn with_resource(mut self, name: &'a str, resource: wgpu::BindingResource<'a>) -> Self {
+        self.resources.push((name, resource));
+        self
+    }
+
+    /// Binds all of `buffer`, for uniform and storage entries without a dynamic offset.
+    pub fn with_buffer(self, name: &'a str, buffer: &'a wgpu::Buffer) -> Self {
+        self.with_resource(name, buffer.as_entire_binding())
+    }
+
+    /// Binds `size` bytes of `buffer` from `offset`, or the rest of it if `size` is `None`. For
+    /// dynamic entries this is the range the dynamic offset moves.
+    pub fn with_buffer_range(
+        self,
+        name: &'a str,
+        buffer: &'a wgpu::Buffer,
+        offset: wgpu::BufferAddress,
+        size: Option<wgpu::BufferSize>,
+    ) -> Self {
+        let binding = wgpu::BufferBinding {
+            buffer,
+            offset,
+            size,
+        };
+        self.with_resource(name, wgpu::BindingResource::Buffer(binding))
+    }
+
+    /// Binds a view for texture and storage texture entries.
+    pub fn with_texture_view(self, name: &'a str, view: &'a wgpu::TextureView) -> Self {
+        self.with_resource(name, wgpu::BindingResource::TextureView(view))
+    }
+
+    pub fn with_sampler(self, name: &'a str, sampler: &'a wgpu::Sampler) -> Self {
+        self.with_resource(name, wgpu::BindingResource::Sampler(sampler))
+    }
+
+    /// Binds the view and sampler of `texture` to two entries.
+    pub fn with_texture(self, view: &'a str, sampler: &'a str, texture: &'a Texture) -> Self {
+        self.with_texture_view(view, &texture.view)
+            .with_sampler(sampler, &texture.sampler)
+    }
+
+    /// Checks the resources against the layout with `validate_bind_group`.
+    pub fn validate(&self) -> Result<()> {
+        let resources: Vec<_> = self
+            .resources
+            .iter()
+            .map(|(name, resource)| (*name, ResourceKind::of(resource)))
+            .collect();
+        validate_bind_group(&self.layout.entries, &resources)
+    }
+
+    pub fn build(self, dpy: &Display) -> Result<wgpu::BindGroup> {
+        self.validate()?;
+        let layout = self.layout;
+        let entries: Vec<wgpu::BindGroupEntry> = self
+            .resources
+            .into_iter()
+            .map(|(name, resource)| wgpu::BindGroupEntry {
+                binding: layout.entry(name).unwrap().binding,
+                resource,
+            })
+            .collect();
+
+        Ok(dpy.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: self.label,
+            layout,
+            entries: &entries,
+        }))
+    }
+}
+
+/// The kind of resource set for a bind group entry, as checked by `validate_bind_group`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ResourceKind {
+    /// A buffer range, where a `size` of `None` binds the rest of the buffer.
+    Buffer {
+        size: Option<wgpu::BufferSize>,
+    },
+    BufferArray,
+    Sampler,
+    TextureView,
+    TextureViewArray,
+    Unsupported,
+}
+
+impl ResourceKind {
+    pub fn of(resource: &wgpu::BindingResource) -> ResourceKind {
+        match resource {
+            wgpu::BindingResource::Buffer(binding) => ResourceKind::Buffer { size: binding.size },
+            wgpu::BindingResource::BufferArray(_) => ResourceKind::BufferArray,
+            wgpu::BindingResource::Sampler(_) => ResourceKind::Sampler,
+            wgpu::BindingResource::TextureView(_) => ResourceKind::TextureView,
+            wgpu::BindingResource::TextureViewArray(_) => ResourceKind::TextureViewArray,
+            _ => ResourceKind::Unsupported,
+        }
+    }
+
+    fn name(self) -> &'static str {
+        match self {
+            ResourceKind::Buffer { .. } => "a buffer",
+            ResourceKind::BufferArray => "a buffer array",
+            ResourceKind::Sampler => "a sampler",
+            ResourceKind::TextureView => "a texture view",
+            ResourceKind::TextureViewArray => "a texture view array",
+            ResourceKind::Unsupported => "an unsupported resource",
+        }
+    }
+}
+
+/// Checks bind group `resources`, set by entry name, against the layout `entries`.
+///
+/// Every entry must be set exactly once with a resource of the right kind. Buffers for dynamic
+/// entries need an explicit size, as the dynamic offset moves that range through the buffer.
+pub fn validate_bind_group(
+    entries: &[NamedLayoutEntry],
+    resources: &[(&str, ResourceKind)],
+) -> Result<()> {
+    for (i, (name, resource)) in resources.iter().enumerate() {
+        let entry = entries
+            .iter()
+            .find(|e| e.name == *name)
+            .map(|e| &e.entry)
+            .ok_or_else(|| anyhow!("Bind group layout has no entry named `{}`", name))?;
+        if resources[..i].iter().any(|(n, _)| n == name) {
+            bail!("Bind group entry `{}` is set more than once", name);
+        }
+
+        let matches = matches!(
+            (&entry.ty, resource),
+            (
+                wgpu::BindingType::Buffer { .. },
+                ResourceKind::Buffer { .. }
+            ) | (wgpu::BindingType::Sampler { .. }, ResourceKind::Sampler)
+                | (
+                    wgpu::BindingType::Texture { .. } | wgpu::BindingType::StorageTexture { .. },
+                    ResourceKind::TextureView
+                )
+        );
+        if !matches {
+            bail!(
+                "Bind group entry `{}` expects {}, but was given {}",
+                name,
+                binding_type_name(&entry.ty),
+                resource.name()
+            );
+        }
+
+        if let (
+            wgpu::BindingType::Buffer {
+                has_dynamic_offset: true,
+                ..
+            },
+            ResourceKind::Buffer { size: None },
+        ) = (&entry.ty, resource)
+        {
+            bail!(
+                "Bind group entry `{}` has a dynamic offset, so its buffer needs an explicit size",
+                name
+            );
+        }
+    }
+
+    let missing: Vec<&str> = entries
+        .iter()
+        .filter(|e| !resources.iter().any(|(n, _)| *n == e.name))
+        .map(|e| e.name.as_str())
+        .collect();
+    if !missing.is_empty() {
+        bail!("Bind group is missing entries: {}", missing.join(", "));
+    }
+    Ok(())
+}
+
+fn binding_type_name(ty: &wgpu::BindingType) -> &'static str {
+    match ty {
+        wgpu::BindingType::Buffer { .. } => "a buffer",
+        wgpu::BindingType::Sampler { .. } => "a sampler",
+        wgpu::BindingType::Texture { .. } => "a texture view",
+        wgpu::BindingType::StorageTexture { .. } => "a storage texture view",
+    }
+}
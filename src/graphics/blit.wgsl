@@ -0,0 +1,17 @@
+// Copies a texture into a render target of the same size, see `FrameBlit`.
+
+[[stage(vertex)]]
+fn vs_main([[builtin(vertex_index)]] index: u32) -> [[builtin(position)]] vec4<f32> {
+    // A single triangle covering the whole target
+    let x = f32(i32(index & 1u) * 4 - 1);
+    let y = f32(i32(index >> 1u) * 4 - 1);
+    return vec4<f32>(x, y, 0.0, 1.0);
+}
+
+[[group(0), binding(0)]]
+var source: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn fs_main([[builtin(position)]] position: vec4<f32>) -> [[location(0)]] vec4<f32> {
+    return textureLoad(source, vec2<i32>(i32(position.x), i32(position.y)), 0);
+}
@@ -0,0 +1,172 @@
+use nalgebra::{Matrix4, Point3, Vector3};
+
+use crate::graphics::Vertex;
+
+/// Axis-aligned bounding box.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    pub fn new(min: Point3<f32>, max: Point3<f32>) -> Aabb {
+        Aabb { min, max }
+    }
+
+    /// Smallest box containing all of `points`, or `None` if there are no points.
+    pub fn from_points<I>(points: I) -> Option<Aabb>
+    where
+        I: IntoIterator<Item = Point3<f32>>,
+    {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(Aabb::new(first, first), |aabb, p| Aabb {
+            min: aabb.min.inf(&p),
+            max: aabb.max.sup(&p),
+        }))
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        nalgebra::center(&self.min, &self.max)
+    }
+
+    /// Half of the size of the box along each axis.
+    pub fn half_extents(&self) -> Vector3<f32> {
+        (self.max - self.min) / 2.0
+    }
+
+    /// Smallest box containing both `self` and `other`.
+    pub fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.inf(&other.min),
+            max: self.max.sup(&other.max),
+        }
+    }
+
+    /// Returns the box containing `self` after being transformed by the affine `matrix`.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Aabb {
+        // Arvo's method: accumulate the smaller and larger contribution of each matrix element
+        let translation = Point3::new(matrix[(0, 3)], matrix[(1, 3)], matrix[(2, 3)]);
+        let mut min = translation;
+        let mut max = translation;
+        for i in 0..3 {
+            for j in 0..3 {
+                let a = matrix[(i, j)] * self.min[j];
+                let b = matrix[(i, j)] * self.max[j];
+                min[i] += a.min(b);
+                max[i] += a.max(b);
+            }
+        }
+        Aabb { min, max }
+    }
+}
+
+/// Bounding sphere.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    pub fn new(center: Point3<f32>, radius: f32) -> BoundingSphere {
+        BoundingSphere { center, radius }
+    }
+
+    /// Smallest sphere centred on `center` that contains all of `points`.
+    pub fn from_points<I>(center: Point3<f32>, points: I) -> BoundingSphere
+    where
+        I: IntoIterator<Item = Point3<f32>>,
+    {
+        let radius = points
+            .into_iter()
+            .map(|p| nalgebra::distance_squared(&center, &p))
+            .fold(0.0, f32::max)
+            .sqrt();
+        BoundingSphere { center, radius }
+    }
+
+    /// Smallest sphere containing both `self` and `other`.
+    pub fn union(&self, other: &BoundingSphere) -> BoundingSphere {
+        let offset = other.center - self.center;
+        let distance = offset.norm();
+        if distance + other.radius <= self.radius {
+            return *self;
+        }
+        if distance + self.radius <= other.radius {
+            return *other;
+        }
+
+        let radius = (distance + self.radius + other.radius) / 2.0;
+        let center = self.center + offset * ((radius - self.radius) / distance);
+        BoundingSphere { center, radius }
+    }
+
+    /// Returns the sphere containing `self` after being transformed by the affine `matrix`.
+    ///
+    /// Non-uniform scales grow the sphere by the largest scale factor.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> BoundingSphere {
+        let scale = (0..3)
+            .map(|i| matrix.fixed_slice::<3, 1>(0, i).norm())
+            .fold(0.0, f32::max);
+        BoundingSphere {
+            center: matrix.transform_point(&self.center),
+            radius: self.radius * scale,
+        }
+    }
+}
+
+/// Bounding box and sphere of a mesh.
+///
+/// An empty mesh has a zero sized box and sphere at the origin.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub struct Bounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+impl Default for Bounds {
+    fn default() -> Self {
+        Bounds {
+            aabb: Aabb::new(Point3::origin(), Point3::origin()),
+            sphere: BoundingSphere::new(Point3::origin(), 0.0),
+        }
+    }
+}
+
+impl Bounds {
+    /// Computes bounds from the positions of `vertices`.
+    ///
+    /// The sphere is centred on the centre of the box.
+    pub fn from_vertices<V>(vertices: &[V]) -> Bounds
+    where
+        V: Vertex,
+    {
+        let points = || vertices.iter().map(|v| Point3::from(v.position()));
+        let aabb = match Aabb::from_points(points()) {
+            Some(aabb) => aabb,
+            None => return Bounds::default(),
+        };
+        Bounds {
+            aabb,
+            sphere: BoundingSphere::from_points(aabb.center(), points()),
+        }
+    }
+
+    /// Bounds containing both `self` and `other`.
+    pub fn union(&self, other: &Bounds) -> Bounds {
+        Bounds {
+            aabb: self.aabb.union(&other.aabb),
+            sphere: self.sphere.union(&other.sphere),
+        }
+    }
+
+    /// Returns the bounds containing `self` after being transformed by the affine `matrix`.
+    pub fn transform(&self, matrix: &Matrix4<f32>) -> Bounds {
+        Bounds {
+            aabb: self.aabb.transform(matrix),
+            sphere: self.sphere.transform(matrix),
+        }
+    }
+}
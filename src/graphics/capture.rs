@@ -0,0 +1,228 @@
+use std::path::Path;
+
+use anyhow::{anyhow, bail, Result};
+
+use super::{Display, Texture};
+
+/// Returns the number of bytes per row of a buffer holding a copy of a texture `width` texels wide,
+/// padded up to wgpu's required row alignment.
+pub fn padded_bytes_per_row(width: u32) -> u32 {
+    let unpadded = width * 4;
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    unpadded.div_ceil(align) * align
+}
+
+/// Strips the padding from rows of `padded_row` bytes, leaving tightly packed rows of `width` texels.
+pub fn unpad_rows(data: &[u8], width: u32, padded_row: u32) -> Vec<u8> {
+    let row = (width * 4) as usize;
+    let mut pixels = Vec::with_capacity(data.len() / padded_row as usize * row);
+    for padded in data.chunks(padded_row as usize) {
+        pixels.extend_from_slice(&padded[..row]);
+    }
+    pixels
+}
+
+/// Swaps the red and blue channels of 8-bit BGRA `pixels` in place, turning them into RGBA.
+pub fn bgra_to_rgba(pixels: &mut [u8]) {
+    for px in pixels.chunks_exact_mut(4) {
+        px.swap(0, 2);
+    }
+}
+
+/// Draws a texture into a render target of the same size and format.
+///
+/// Used by displays built with `DisplayBuilder::with_capture` to copy their offscreen target into
+/// the swapchain frame, which cannot be the destination of a texture copy.
+pub struct FrameBlit {
+    layout: wgpu::BindGroupLayout,
+    pipeline: wgpu::RenderPipeline,
+    bind_group: wgpu::BindGroup,
+}
+
+impl FrameBlit {
+    /// Creates a blit from `source` into targets of `format`.
+    pub fn new(device: &wgpu::Device, source: &Texture, format: wgpu::TextureFormat) -> FrameBlit {
+        let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label: Some("blit"),
+            flags: wgpu::ShaderFlags::all(),
+            source: wgpu::ShaderSource::Wgsl(include_str!("blit.wgsl").into()),
+        });
+        let layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("blit"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStage::FRAGMENT,
+                ty: wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+                count: None,
+            }],
+        });
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("blit"),
+            bind_group_layouts: &[&layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("blit"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &module,
+                entry_point: "vs_main",
+                buffers: &[],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &module,
+                entry_point: "fs_main",
+                targets: &[format.into()],
+            }),
+            primitive: wgpu::PrimitiveState::default(),
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+        });
+        let bind_group = FrameBlit::bind_group(device, &layout, source);
+
+        FrameBlit {
+            layout,
+            pipeline,
+            bind_group,
+        }
+    }
+
+    fn bind_group(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        source: &Texture,
+    ) -> wgpu::BindGroup {
+        device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("blit"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&source.view),
+            }],
+        })
+    }
+
+    /// Replaces the texture drawn, for example after it was recreated at a new size.
+    pub fn set_source(&mut self, device: &wgpu::Device, source: &Texture) {
+        self.bind_group = FrameBlit::bind_group(device, &self.layout, source);
+    }
+
+    /// Records drawing the source texture over all of `target`.
+    pub fn blit(&self, encoder: &mut wgpu::CommandEncoder, target: &wgpu::TextureView) {
+        let mut rp = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("blit"),
+            color_attachments: &[wgpu::RenderPassColorAttachment {
+                view: target,
+                resolve_target: None,
+                ops: wgpu::Operations {
+                    load: wgpu::LoadOp::Load,
+                    store: true,
+                },
+            }],
+            depth_stencil_attachment: None,
+        });
+        rp.set_pipeline(&self.pipeline);
+        rp.set_bind_group(0, &self.bind_group, &[]);
+        rp.draw(0..3, 0..1);
+    }
+}
+
+impl Texture {
+    /// Copies the first mip level of this texture back from the GPU into an `RgbaImage`.
+    ///
+    /// The texture must have been created with `COPY_SRC` usage, such as one from `new_render_target`.
+    /// Only 8-bit RGBA and BGRA formats are supported, BGRA data is swizzled into RGBA.
+    pub async fn to_image(&self, dpy: &Display) -> Result<image::RgbaImage> {
+        let swizzle = match self.format {
+            wgpu::TextureFormat::Rgba8Unorm | wgpu::TextureFormat::Rgba8UnormSrgb => false,
+            wgpu::TextureFormat::Bgra8Unorm | wgpu::TextureFormat::Bgra8UnormSrgb => true,
+            f => bail!("Cannot read back texture with format {:?}.", f),
+        };
+
+        let width = self.size.width;
+        let height = self.size.height;
+        let padded_row = padded_bytes_per_row(width);
+
+        let buffer = dpy.device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("readback"),
+            size: (padded_row * height) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = dpy
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("readback"),
+            });
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &self.texture,
+                mip_level: 0,
+                origin: wgpu::Origin3d::ZERO,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: std::num::NonZeroU32::new(padded_row),
+                    rows_per_image: std::num::NonZeroU32::new(height),
+                },
+            },
+            wgpu::Extent3d {
+                width,
+                height,
+                depth_or_array_layers: 1,
+            },
+        );
+        dpy.queue.submit(std::iter::once(encoder.finish()));
+
+        let slice = buffer.slice(..);
+        let mapping = slice.map_async(wgpu::MapMode::Read);
+        dpy.device.poll(wgpu::Maintain::Wait);
+        mapping.await?;
+
+        let mut pixels = unpad_rows(&slice.get_mapped_range(), width, padded_row);
+        buffer.unmap();
+
+        if swizzle {
+            bgra_to_rgba(&mut pixels);
+        }
+
+        image::RgbaImage::from_raw(width, height, pixels)
+            .ok_or_else(|| anyhow!("Readback buffer does not match texture size."))
+    }
+
+    /// Reads back this texture using `to_image` and writes it to `path` as a PNG.
+    pub async fn save_png<P: AsRef<Path>>(&self, dpy: &Display, path: P) -> Result<()> {
+        let image = self.to_image(dpy).await?;
+        image.save_with_format(path, image::ImageFormat::Png)?;
+        Ok(())
+    }
+}
+
+impl Display {
+    /// Reads back the current contents of the display's render target.
+    ///
+    /// Swapchain images cannot be copied from, so windowed displays can only be captured if built
+    /// with `DisplayBuilder::with_capture`. Headless displays can always be captured.
+    pub async fn capture_frame(&self) -> Result<image::RgbaImage> {
+        match self.offscreen {
+            Some(ref target) => target.to_image(self).await,
+            None => bail!(
+                "Cannot capture a swapchain frame, build the display with DisplayBuilder::with_capture."
+            ),
+        }
+    }
+
+    /// Captures the current frame using `capture_frame` and writes it to `path` as a PNG.
+    pub async fn save_screenshot<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let image = self.capture_frame().await?;
+        image.save_with_format(path, image::ImageFormat::Png)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,98 @@
+use super::Shader;
+
+/// Builds a compute pipeline, the compute counterpart of `RenderPipelineBuilder`.
+pub struct ComputePipelineBuilder<'a> {
+    label: Option<&'static str>,
+    module: Option<&'a wgpu::ShaderModule>,
+    module_entry_point: &'static str,
+    layout: Option<&'a wgpu::PipelineLayout>,
+}
+
+impl<'a> Default for ComputePipelineBuilder<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> ComputePipelineBuilder<'a> {
+    pub fn new() -> ComputePipelineBuilder<'a> {
+        ComputePipelineBuilder {
+            label: None,
+            module: None,
+            module_entry_point: "main",
+            layout: None,
+        }
+    }
+
+    pub fn with_label(&mut self, label: &'static str) -> &mut Self {
+        self.label = Some(label);
+        self
+    }
+
+    pub fn with_module(&mut self, module: &'a wgpu::ShaderModule) -> &mut Self {
+        self.module = Some(module);
+        self
+    }
+
+    /// Uses `shader` along with its pipeline layout.
+    pub fn with_shader(&mut self, shader: &'a Shader) -> &mut Self {
+        self.module = Some(&shader.module);
+        self.layout = Some(&shader.pipeline_layout);
+        self
+    }
+
+    pub fn with_module_entry_point(&mut self, ep: &'static str) -> &mut Self {
+        self.module_entry_point = ep;
+        self
+    }
+
+    pub fn with_layout(&mut self, layout: &'a wgpu::PipelineLayout) -> &mut Self {
+        self.layout = Some(layout);
+        self
+    }
+
+    pub fn build(&mut self, device: &wgpu::Device) -> wgpu::ComputePipeline {
+        let module = self
+            .module
+            .expect("Cannot construct compute pipeline without shader module.");
+
+        device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: self.label,
+            layout: self.layout,
+            module,
+            entry_point: self.module_entry_point,
+        })
+    }
+}
+
+/// Number of workgroups of `workgroup_size` needed to cover `size` invocations along each axis.
+///
+/// The last workgroup along an axis may be partly outside `size`, shaders should bounds check.
+///
+/// Panics if any dimension of `workgroup_size` is zero.
+pub fn workgroup_count(size: [u32; 3], workgroup_size: [u32; 3]) -> [u32; 3] {
+    assert!(
+        !workgroup_size.contains(&0),
+        "Workgroup size {:?} has a zero dimension, use 1 for unused axes",
+        workgroup_size
+    );
+    [
+        size[0].div_ceil(workgroup_size[0]),
+        size[1].div_ceil(workgroup_size[1]),
+        size[2].div_ceil(workgroup_size[2]),
+    ]
+}
+
+pub trait ComputePassExt {
+    /// Dispatches enough workgroups of `workgroup_size` to cover `size` invocations.
+    ///
+    /// `workgroup_size` must match the `workgroup_size` attribute of the shader's entry point.
+    fn dispatch_size(&mut self, size: [u32; 3], workgroup_size: [u32; 3]);
+}
+
+impl<'a> ComputePassExt for wgpu::ComputePass<'a> {
+    fn dispatch_size(&mut self, size: [u32; 3], workgroup_size: [u32; 3]) {
+        let [x, y, z] = workgroup_count(size, workgroup_size);
+        self.dispatch(x, y, z);
+    }
+}
@@ -1,62 +1,526 @@
-use anyhow::Result;
+use anyhow::{anyhow, bail, Context, Result};
 
+use super::{FrameBlit, Texture};
+
+/// Format of the offscreen target used by headless displays.
+pub const HEADLESS_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rgba8UnormSrgb;
+
+/// Owns the wgpu device and queue, along with the target being rendered to.
+///
+/// A windowed display presents through `surface` and `swapchain`.
+/// A headless display has neither, and instead renders into `offscreen`. A windowed display built
+/// with `DisplayBuilder::with_capture` also renders into `offscreen`, which `end_frame` copies into
+/// the swapchain frame, so that `capture_frame` can read it back.
+/// In both cases `sc_desc` describes the size and format of the target, and `depth` is a depth
+/// attachment of the same size.
+///
+/// With a `sample_count` above 1, rendering goes to the multisampled `msaa` target and `depth`
+/// is multisampled to match. `color_attachment` resolves `msaa` into the frame.
+///
+/// Frames are rendered between `begin_frame` and `end_frame`, which handle swapchain recovery.
 pub struct Display {
-    pub surface: wgpu::Surface,
+    pub surface: Option<wgpu::Surface>,
     pub device: wgpu::Device,
     pub queue: wgpu::Queue,
     pub sc_desc: wgpu::SwapChainDescriptor,
-    pub swapchain: wgpu::SwapChain,
+    pub swapchain: Option<wgpu::SwapChain>,
+    pub offscreen: Option<Texture>,
+    pub depth: Texture,
+    pub sample_count: u32,
+    pub msaa: Option<Texture>,
+    frame: Option<wgpu::SwapChainFrame>,
+    blit: Option<FrameBlit>,
 }
 
-impl Display {
-    pub async fn new(window: &winit::window::Window) -> Result<Display> {
+/// Creates the multisampled colour target for a display, if it uses multisampling.
+fn new_msaa_target(
+    device: &wgpu::Device,
+    sc_desc: &wgpu::SwapChainDescriptor,
+    sample_count: u32,
+) -> Option<Texture> {
+    if sample_count > 1 {
+        Some(Texture::new_multisampled(
+            device,
+            sc_desc.width,
+            sc_desc.height,
+            sc_desc.format,
+            sample_count,
+            Some("msaa"),
+        ))
+    } else {
+        None
+    }
+}
+
+/// Configures how a `Display` chooses its adapter and creates its device.
+///
+/// Defaults match `Display::new`: primary backends, default power preference, vsync (`Fifo`),
+/// no features, default limits and no multisampling.
+#[derive(Clone, Debug)]
+pub struct DisplayBuilder {
+    backends: wgpu::BackendBit,
+    power_preference: wgpu::PowerPreference,
+    present_mode: wgpu::PresentMode,
+    required_features: wgpu::Features,
+    optional_features: wgpu::Features,
+    limits: wgpu::Limits,
+    sample_count: u32,
+    capture: bool,
+    label: Option<&'static str>,
+}
+
+impl Default for DisplayBuilder {
+    fn default() -> Self {
+        DisplayBuilder::new()
+    }
+}
+
+impl DisplayBuilder {
+    pub fn new() -> DisplayBuilder {
+        DisplayBuilder {
+            backends: wgpu::BackendBit::PRIMARY,
+            power_preference: wgpu::PowerPreference::default(),
+            present_mode: wgpu::PresentMode::Fifo,
+            required_features: wgpu::Features::empty(),
+            optional_features: wgpu::Features::empty(),
+            limits: wgpu::Limits::default(),
+            sample_count: 1,
+            capture: false,
+            label: None,
+        }
+    }
+
+    pub fn with_label(&mut self, label: &'static str) -> &mut Self {
+        self.label = Some(label);
+        self
+    }
+
+    /// Backends adapters may be chosen from.
+    pub fn with_backends(&mut self, backends: wgpu::BackendBit) -> &mut Self {
+        self.backends = backends;
+        self
+    }
+
+    pub fn with_power_preference(&mut self, power_preference: wgpu::PowerPreference) -> &mut Self {
+        self.power_preference = power_preference;
+        self
+    }
+
+    /// `Fifo` waits for vsync, `Mailbox` replaces queued frames without tearing, and `Immediate`
+    /// presents straight away and may tear.
+    pub fn with_present_mode(&mut self, present_mode: wgpu::PresentMode) -> &mut Self {
+        self.present_mode = present_mode;
+        self
+    }
+
+    /// Features the device must support, creating the display fails without them.
+    pub fn with_required_features(&mut self, features: wgpu::Features) -> &mut Self {
+        self.required_features |= features;
+        self
+    }
+
+    /// Features enabled only if the adapter supports them. Check `device.features()` to see
+    /// which were enabled.
+    pub fn with_optional_features(&mut self, features: wgpu::Features) -> &mut Self {
+        self.optional_features |= features;
+        self
+    }
+
+    pub fn with_limits(&mut self, limits: wgpu::Limits) -> &mut Self {
+        self.limits = limits;
+        self
+    }
+
+    /// Number of samples per pixel for multisample anti-aliasing, either 1 to disable
+    /// multisampling or 4.
+    ///
+    /// wgpu cannot report which sample counts an adapter supports, so only 4, which WebGPU
+    /// guarantees, is accepted. The GL backend cannot multisample at all, and its adapters are
+    /// not chosen when the sample count is above 1.
+    ///
+    /// To multisample when rendering into a texture instead, see `MultisampledTarget`.
+    pub fn with_sample_count(&mut self, sample_count: u32) -> &mut Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// Renders windowed frames into an offscreen texture, copied to the swapchain by `end_frame`,
+    /// so that `Display::capture_frame` can read them back. This costs a full screen copy per
+    /// frame. Headless displays can always be captured.
+    pub fn with_capture(&mut self, capture: bool) -> &mut Self {
+        self.capture = capture;
+        self
+    }
+
+    /// Creates a display presenting to `window`.
+    pub async fn build(&self, window: &winit::window::Window) -> Result<Display> {
+        self.validate()?;
         let size = window.inner_size();
 
-        let inst = wgpu::Instance::new(wgpu::BackendBit::PRIMARY);
+        let inst = wgpu::Instance::new(self.backends);
         let surface = unsafe { inst.create_surface(window) };
-        let adapter = inst
-            .request_adapter(&wgpu::RequestAdapterOptions {
-                power_preference: wgpu::PowerPreference::default(),
-                compatible_surface: Some(&surface),
-            })
-            .await
-            .expect("Unable to find adapter.");
-
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::default(),
-                    label: None,
-                },
-                None,
-            )
-            .await?;
+        let adapter = self.request_adapter(&inst, Some(&surface)).await?;
+        let (device, queue) = self.request_device(&adapter).await?;
 
+        let format = adapter
+            .get_swap_chain_preferred_format(&surface)
+            .ok_or_else(|| {
+                anyhow!(
+                    "Adapter {:?} cannot present to this window.",
+                    adapter.get_info().name
+                )
+            })?;
         let sc_desc = wgpu::SwapChainDescriptor {
             usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
-            format: adapter.get_swap_chain_preferred_format(&surface).unwrap(),
+            format,
             width: size.width,
             height: size.height,
-            present_mode: wgpu::PresentMode::Fifo,
+            present_mode: self.present_mode,
         };
 
         let swapchain = device.create_swap_chain(&surface, &sc_desc);
+        let depth = Texture::new_depth(
+            &device,
+            size.width,
+            size.height,
+            self.sample_count,
+            Some("depth"),
+        );
+        let msaa = new_msaa_target(&device, &sc_desc, self.sample_count);
+        let (offscreen, blit) = if self.capture {
+            let offscreen = Texture::new_render_target(
+                &device,
+                size.width,
+                size.height,
+                format,
+                Some("offscreen"),
+            );
+            let blit = FrameBlit::new(&device, &offscreen, format);
+            (Some(offscreen), Some(blit))
+        } else {
+            (None, None)
+        };
 
-        let d = Display {
-            surface,
+        Ok(Display {
+            surface: Some(surface),
             device,
             queue,
             sc_desc,
-            swapchain,
+            swapchain: Some(swapchain),
+            offscreen,
+            depth,
+            sample_count: self.sample_count,
+            msaa,
+            frame: None,
+            blit,
+        })
+    }
+
+    /// Creates a display without a window, rendering into an owned offscreen texture.
+    ///
+    /// If no adapter matches the power preference, any adapter with the required features is
+    /// used, including software fallbacks.
+    pub async fn build_headless(&self, width: u32, height: u32) -> Result<Display> {
+        self.validate()?;
+        let inst = wgpu::Instance::new(self.backends);
+        let adapter = self.request_adapter(&inst, None).await?;
+        let (device, queue) = self.request_device(&adapter).await?;
+
+        let sc_desc = wgpu::SwapChainDescriptor {
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+            format: HEADLESS_FORMAT,
+            width,
+            height,
+            present_mode: self.present_mode,
         };
 
-        Ok(d)
+        let offscreen =
+            Texture::new_render_target(&device, width, height, HEADLESS_FORMAT, Some("offscreen"));
+        let depth = Texture::new_depth(&device, width, height, self.sample_count, Some("depth"));
+        let msaa = new_msaa_target(&device, &sc_desc, self.sample_count);
+
+        Ok(Display {
+            surface: None,
+            device,
+            queue,
+            sc_desc,
+            swapchain: None,
+            offscreen: Some(offscreen),
+            depth,
+            sample_count: self.sample_count,
+            msaa,
+            frame: None,
+            blit: None,
+        })
+    }
+
+    /// Checks the settings that can be checked without an adapter, `build` and `build_headless`
+    /// call this first.
+    pub fn validate(&self) -> Result<()> {
+        if self.sample_count != 1 && self.sample_count != 4 {
+            bail!(
+                "Invalid sample count {}, must be 1 or 4.",
+                self.sample_count
+            );
+        }
+        Ok(())
+    }
+
+    async fn request_adapter(
+        &self,
+        inst: &wgpu::Instance,
+        surface: Option<&wgpu::Surface>,
+    ) -> Result<wgpu::Adapter> {
+        let adapter = inst
+            .request_adapter(&wgpu::RequestAdapterOptions {
+                power_preference: self.power_preference,
+                compatible_surface: surface,
+            })
+            .await;
+
+        match adapter {
+            Some(adapter) if self.is_suitable(&adapter) => Ok(adapter),
+            // Any adapter will do without a surface to be compatible with
+            _ if surface.is_none() => inst
+                .enumerate_adapters(self.backends)
+                .find(|a| self.is_suitable(a))
+                .ok_or_else(|| self.no_adapter_error(inst)),
+            _ => Err(self.no_adapter_error(inst)),
+        }
+    }
+
+    /// Returns true if `adapter` has the required features and can multisample if needed.
+    fn is_suitable(&self, adapter: &wgpu::Adapter) -> bool {
+        adapter.features().contains(self.required_features)
+            && (self.sample_count == 1 || adapter.get_info().backend != wgpu::Backend::Gl)
     }
 
-    pub fn reload_swapchain(&mut self, size: winit::dpi::PhysicalSize<u32>) {
+    fn no_adapter_error(&self, inst: &wgpu::Instance) -> anyhow::Error {
+        let adapters: Vec<String> = inst
+            .enumerate_adapters(self.backends)
+            .map(|adapter| {
+                let info = adapter.get_info();
+                let missing = self.required_features - adapter.features();
+                let msaa = if self.sample_count > 1 && info.backend == wgpu::Backend::Gl {
+                    ", no multisampling"
+                } else {
+                    ""
+                };
+                format!(
+                    "\n  {} ({:?}, {:?}), missing features: {:?}{}",
+                    info.name, info.backend, info.device_type, missing, msaa
+                )
+            })
+            .collect();
+
+        if adapters.is_empty() {
+            anyhow!(
+                "Unable to find adapter, none are available for backends {:?}.",
+                self.backends
+            )
+        } else {
+            anyhow!(
+                "Unable to find a suitable adapter with features {:?} and sample count {}. Available adapters:{}",
+                self.required_features,
+                self.sample_count,
+                adapters.concat()
+            )
+        }
+    }
+
+    async fn request_device(&self, adapter: &wgpu::Adapter) -> Result<(wgpu::Device, wgpu::Queue)> {
+        let features = self.required_features | (self.optional_features & adapter.features());
+        let info = adapter.get_info();
+        adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    features,
+                    limits: self.limits.clone(),
+                    label: self.label,
+                },
+                None,
+            )
+            .await
+            .with_context(|| {
+                format!(
+                    "Unable to create device on adapter {} ({:?}).\nRequested limits: {:?}\nAdapter limits: {:?}",
+                    info.name,
+                    info.backend,
+                    self.limits,
+                    adapter.limits()
+                )
+            })
+    }
+}
+
+impl Display {
+    /// Creates a display presenting to `window` with the default `DisplayBuilder` settings.
+    pub async fn new(window: &winit::window::Window) -> Result<Display> {
+        DisplayBuilder::new().build(window).await
+    }
+
+    /// Creates a display without a window, rendering into an owned offscreen texture.
+    ///
+    /// Any adapter will be used, including software fallbacks.
+    pub async fn new_headless(width: u32, height: u32) -> Result<Display> {
+        DisplayBuilder::new()
+            .with_backends(wgpu::BackendBit::all())
+            .build_headless(width, height)
+            .await
+    }
+
+    /// Returns true if this display renders into an offscreen texture rather than a window.
+    pub fn is_headless(&self) -> bool {
+        self.surface.is_none()
+    }
+
+    /// Resizes the render target and depth attachment, recreating either the swapchain or the
+    /// offscreen texture.
+    ///
+    /// A zero sized target, such as a minimised window, is recorded but nothing is recreated until
+    /// it has a size again.
+    ///
+    /// Returns the new size, to pass on to `camera::Projection::resize`. `app::run` does this for
+    /// the projection in `AppContext::projection`.
+    pub fn reload_swapchain(
+        &mut self,
+        size: winit::dpi::PhysicalSize<u32>,
+    ) -> winit::dpi::PhysicalSize<u32> {
         self.sc_desc.width = size.width;
         self.sc_desc.height = size.height;
-        self.swapchain = self.device.create_swap_chain(&self.surface, &self.sc_desc);
+        if self.is_minimised() {
+            return size;
+        }
+
+        self.frame = None;
+        if let Some(ref surface) = self.surface {
+            self.swapchain = Some(self.device.create_swap_chain(surface, &self.sc_desc));
+        }
+        if self.offscreen.is_some() {
+            let offscreen = Texture::new_render_target(
+                &self.device,
+                size.width,
+                size.height,
+                self.sc_desc.format,
+                Some("offscreen"),
+            );
+            if let Some(ref mut blit) = self.blit {
+                blit.set_source(&self.device, &offscreen);
+            }
+            self.offscreen = Some(offscreen);
+        }
+        self.depth = Texture::new_depth(
+            &self.device,
+            size.width,
+            size.height,
+            self.sample_count,
+            Some("depth"),
+        );
+        self.msaa = new_msaa_target(&self.device, &self.sc_desc, self.sample_count);
+        size
+    }
+
+    /// Returns true if the render target has no area, in which case frames are skipped.
+    pub fn is_minimised(&self) -> bool {
+        self.sc_desc.width == 0 || self.sc_desc.height == 0
+    }
+
+    /// Acquires the next frame to render into, available from `frame_view` until `end_frame`.
+    ///
+    /// A lost or outdated swapchain is recreated and acquisition retried once. Returns `Ok(false)`
+    /// if this frame should be skipped, because the display is minimised or the swapchain timed
+    /// out, and an error if the GPU is out of memory.
+    pub fn begin_frame(&mut self) -> Result<bool> {
+        self.frame = None;
+        if self.is_minimised() {
+            return Ok(false);
+        }
+
+        let swapchain = match self.swapchain {
+            Some(ref swapchain) => swapchain,
+            None => return Ok(true),
+        };
+        let frame = match swapchain.get_current_frame() {
+            Ok(frame) => frame,
+            Err(wgpu::SwapChainError::Lost) | Err(wgpu::SwapChainError::Outdated) => {
+                let size = winit::dpi::PhysicalSize::new(self.sc_desc.width, self.sc_desc.height);
+                self.reload_swapchain(size);
+                match self.swapchain.as_ref().unwrap().get_current_frame() {
+                    Ok(frame) => frame,
+                    Err(wgpu::SwapChainError::OutOfMemory) => bail!("Out of memory."),
+                    Err(e) => {
+                        log::warn!("Skipping frame: {}", e);
+                        return Ok(false);
+                    }
+                }
+            }
+            Err(wgpu::SwapChainError::Timeout) => return Ok(false),
+            Err(wgpu::SwapChainError::OutOfMemory) => bail!("Out of memory."),
+        };
+
+        self.frame = Some(frame);
+        Ok(true)
+    }
+
+    /// View of the colour target of the current frame.
+    ///
+    /// Displays with an offscreen texture, headless or built with `DisplayBuilder::with_capture`,
+    /// always return it. Panics if any other display is not between a successful `begin_frame`
+    /// and `end_frame`.
+    pub fn frame_view(&self) -> &wgpu::TextureView {
+        match (&self.offscreen, &self.frame) {
+            (Some(offscreen), _) => &offscreen.view,
+            (None, Some(frame)) => &frame.output.view,
+            (None, None) => panic!("No frame in progress, call begin_frame first."),
+        }
+    }
+
+    /// Colour attachment rendering into the current frame, through the multisampled target if
+    /// there is one.
+    pub fn color_attachment(
+        &self,
+        ops: wgpu::Operations<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'_> {
+        match self.msaa {
+            Some(ref msaa) => wgpu::RenderPassColorAttachment {
+                view: &msaa.view,
+                resolve_target: Some(self.frame_view()),
+                ops,
+            },
+            None => wgpu::RenderPassColorAttachment {
+                view: self.frame_view(),
+                resolve_target: None,
+                ops,
+            },
+        }
+    }
+
+    /// Depth attachment using the display's depth texture.
+    pub fn depth_attachment(
+        &self,
+        depth_ops: wgpu::Operations<f32>,
+    ) -> wgpu::RenderPassDepthStencilAttachment<'_> {
+        wgpu::RenderPassDepthStencilAttachment {
+            view: &self.depth.view,
+            depth_ops: Some(depth_ops),
+            stencil_ops: None,
+        }
+    }
+
+    /// Presents the current frame. Work using it must be submitted before this is called.
+    ///
+    /// Displays built with `DisplayBuilder::with_capture` first copy the offscreen texture into the
+    /// swapchain frame.
+    pub fn end_frame(&mut self) {
+        if let (Some(blit), Some(frame)) = (&self.blit, &self.frame) {
+            let mut encoder = self
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                    label: Some("blit"),
+                });
+            blit.blit(&mut encoder, &frame.output.view);
+            self.queue.submit(std::iter::once(encoder.finish()));
+        }
+        self.frame = None;
     }
 }
@@ -0,0 +1,71 @@
+use nalgebra::{Matrix2, Matrix3, Matrix4, Point2, Point3, Vector2, Vector3, Vector4};
+
+pub use magneto_derive::HasLayout;
+
+/// A type that can be a field of a struct deriving `HasLayout`.
+///
+/// The type is passed to the shader as `LOCATIONS` consecutive attributes of `FORMAT`, each
+/// `FORMAT.size()` bytes after the last.
+pub trait AttributeFormat {
+    const FORMAT: wgpu::VertexFormat;
+
+    /// Number of shader locations the type spans, one per column for matrices.
+    const LOCATIONS: u32 = 1;
+
+    /// Attributes for a field at `offset` bytes into the vertex, starting at `shader_location`.
+    fn attributes(offset: u64, shader_location: u32) -> Vec<wgpu::VertexAttribute> {
+        (0..Self::LOCATIONS)
+            .map(|i| wgpu::VertexAttribute {
+                format: Self::FORMAT,
+                offset: offset + i as u64 * Self::FORMAT.size(),
+                shader_location: shader_location + i,
+            })
+            .collect()
+    }
+}
+
+macro_rules! impl_attribute_format {
+    ($($ty:ty => $format:ident $(* $locations:literal)?,)*) => {
+        $(
+            impl AttributeFormat for $ty {
+                const FORMAT: wgpu::VertexFormat = wgpu::VertexFormat::$format;
+                $(const LOCATIONS: u32 = $locations;)?
+            }
+        )*
+    };
+}
+
+impl_attribute_format! {
+    f32 => Float32,
+    [f32; 1] => Float32,
+    [f32; 2] => Float32x2,
+    [f32; 3] => Float32x3,
+    [f32; 4] => Float32x4,
+    u32 => Uint32,
+    [u32; 1] => Uint32,
+    [u32; 2] => Uint32x2,
+    [u32; 3] => Uint32x3,
+    [u32; 4] => Uint32x4,
+    i32 => Sint32,
+    [i32; 1] => Sint32,
+    [i32; 2] => Sint32x2,
+    [i32; 3] => Sint32x3,
+    [i32; 4] => Sint32x4,
+    [[f32; 2]; 2] => Float32x2 * 2,
+    [[f32; 3]; 3] => Float32x3 * 3,
+    [[f32; 4]; 4] => Float32x4 * 4,
+    Vector2<f32> => Float32x2,
+    Vector3<f32> => Float32x3,
+    Vector4<f32> => Float32x4,
+    Vector2<u32> => Uint32x2,
+    Vector3<u32> => Uint32x3,
+    Vector4<u32> => Uint32x4,
+    Vector2<i32> => Sint32x2,
+    Vector3<i32> => Sint32x3,
+    Vector4<i32> => Sint32x4,
+    Point2<f32> => Float32x2,
+    Point3<f32> => Float32x3,
+    Matrix2<f32> => Float32x2 * 2,
+    Matrix3<f32> => Float32x3 * 3,
+    Matrix4<f32> => Float32x4 * 4,
+}
@@ -3,7 +3,7 @@ use std::ops::Range;
 use genmesh::generators::{Cube, Plane};
 use genmesh::{Triangulate, Vertices};
 
-use crate::graphics::Vertex;
+use crate::graphics::{generate_tangents, Bounds, MeshData, Vertex};
 
 use super::DeviceUtilExt;
 
@@ -12,11 +12,14 @@ use super::DeviceUtilExt;
 /// If `index_buffer` is `Some(..)`, then `count` represents the number of indicies.
 ///
 /// Otherwise, `count` represents the number of vertices.
+///
+/// `bounds` holds the bounding box and sphere of the vertices the mesh was built from.
 pub struct Mesh {
     pub vertex_buffer: wgpu::Buffer,
     pub index_buffer: Option<wgpu::Buffer>,
 
     pub count: u32,
+    pub bounds: Bounds,
 }
 
 impl Mesh {
@@ -29,11 +32,12 @@ impl Mesh {
             vertex_buffer: device.init_vertex_buffer(bytemuck::cast_slice(vertices)),
             index_buffer: None,
             count: vertices.len() as u32,
+            bounds: Bounds::from_vertices(vertices),
         }
     }
 
     /// Builds a mesh from the given vertices and corresponding indices.
-    pub fn from_indexed_vertices<V>(device: &wgpu::Device, vertices: &[V], indices: &[u32]) -> Mesh 
+    pub fn from_indexed_vertices<V>(device: &wgpu::Device, vertices: &[V], indices: &[u32]) -> Mesh
     where
         V: Vertex,
     {
@@ -41,9 +45,33 @@ impl Mesh {
             vertex_buffer: device.init_vertex_buffer(bytemuck::cast_slice(vertices)),
             index_buffer: Some(device.init_index_buffer(indices)),
             count: indices.len() as u32,
+            bounds: Bounds::from_vertices(vertices),
         }
     }
 
+    /// Uploads the vertices and indices of `data` into a new mesh.
+    pub fn from_mesh_data<V>(device: &wgpu::Device, data: &MeshData<V>) -> Mesh
+    where
+        V: Vertex,
+    {
+        Mesh::from_indexed_vertices(device, &data.vertices, &data.indices)
+    }
+
+    /// Uploads the vertices of a generated shape, generating tangents first if `V` stores them.
+    fn from_generated_vertices<V>(device: &wgpu::Device, mut vertices: Vec<V>) -> Mesh
+    where
+        V: Vertex,
+    {
+        if V::HAS_TANGENT {
+            let mut indices: Vec<u32> = (0..vertices.len() as u32).collect();
+            if generate_tangents(&mut vertices, &mut indices) {
+                return Mesh::from_indexed_vertices(device, &vertices, &indices);
+            }
+        }
+
+        Mesh::from_vertices(device, &vertices)
+    }
+
     /// Convenience function to create a cube mesh
     pub fn cube<V>(device: &wgpu::Device) -> Mesh
     where
@@ -62,11 +90,7 @@ impl Mesh {
             .vertices()
             .collect();
 
-        Mesh {
-            vertex_buffer: device.init_vertex_buffer(bytemuck::cast_slice(&vertices)),
-            index_buffer: None,
-            count: vertices.len() as u32,
-        }
+        Mesh::from_generated_vertices(device, vertices)
     }
 
     /// Convenience function to create a plane mesh
@@ -87,11 +111,7 @@ impl Mesh {
             .vertices()
             .collect();
 
-        Mesh {
-            vertex_buffer: device.init_vertex_buffer(bytemuck::cast_slice(&vertices)),
-            index_buffer: None,
-            count: vertices.len() as u32,
-        }
+        Mesh::from_generated_vertices(device, vertices)
     }
 
     /// Draws range `instances` of the mesh using `renderpass`.
@@ -0,0 +1,432 @@
+use std::collections::HashMap;
+use std::f32::consts::{PI, TAU};
+
+use nalgebra::{Matrix3, Matrix4, Point3, Vector3};
+
+use crate::graphics::{generate_tangents, Bounds, Vertex};
+
+/// CPU-side vertices and indices, which can be inspected and modified before being uploaded as a `Mesh`.
+///
+/// Triangles are wound counter-clockwise when viewed from the front, matching `Mesh::cube` and `Mesh::plane`.
+/// Generated shapes are centred on the origin with Y up.
+#[derive(Clone, Debug)]
+pub struct MeshData<V>
+where
+    V: Vertex,
+{
+    pub vertices: Vec<V>,
+    pub indices: Vec<u32>,
+}
+
+/// A point on the profile of a surface of revolution.
+struct ProfilePoint {
+    /// Distance from the Y axis.
+    radius: f32,
+    y: f32,
+    /// Normal in the (radius, y) plane.
+    normal: [f32; 2],
+    /// Texture V coordinate.
+    v: f32,
+}
+
+impl<V> MeshData<V>
+where
+    V: Vertex,
+{
+    pub fn new(vertices: Vec<V>, indices: Vec<u32>) -> MeshData<V> {
+        MeshData { vertices, indices }
+    }
+
+    /// Transforms positions and tangents by `matrix`, and normals by its inverse transpose.
+    ///
+    /// If `matrix` mirrors the mesh, triangles are rewound so that they still face outwards.
+    /// Vertices are updated in place with `Vertex::set_position`, `set_normal` and `set_tangent`,
+    /// whose defaults reset attributes `Vertex::with_tangent` cannot set.
+    pub fn transform(&mut self, matrix: &Matrix4<f32>) {
+        let linear: Matrix3<f32> = matrix.fixed_slice::<3, 3>(0, 0).into();
+        let normal_matrix = linear
+            .try_inverse()
+            .map(|m| m.transpose())
+            .unwrap_or_else(Matrix3::identity);
+
+        let mirrored = linear.determinant() < 0.0;
+        if mirrored {
+            for tri in self.indices.chunks_exact_mut(3) {
+                tri.swap(1, 2);
+            }
+        }
+
+        for v in self.vertices.iter_mut() {
+            let position = matrix.transform_point(&Point3::from(v.position()));
+            let normal = normal_matrix * Vector3::from(v.normal());
+            let normal = normal.try_normalize(f32::EPSILON).unwrap_or(normal);
+            let [tx, ty, tz, tw] = v.tangent();
+            let tangent = linear * Vector3::new(tx, ty, tz);
+            let tangent = tangent.try_normalize(f32::EPSILON).unwrap_or(tangent);
+            let tw = if mirrored { -tw } else { tw };
+            v.set_position(position.into());
+            v.set_normal(normal.into());
+            v.set_tangent([tangent.x, tangent.y, tangent.z, tw]);
+        }
+    }
+
+    /// Transforms this mesh by `matrix` using `transform`, consuming and returning it so calls can be chained.
+    ///
+    /// See `transform` for how custom vertex attributes are kept.
+    pub fn transformed(mut self, matrix: &Matrix4<f32>) -> MeshData<V> {
+        self.transform(matrix);
+        self
+    }
+
+    /// Computes the bounding box and sphere of the vertices.
+    pub fn bounds(&self) -> Bounds {
+        Bounds::from_vertices(&self.vertices)
+    }
+
+    /// Generates MikkTSpace tangents for this mesh using `generate_tangents`.
+    ///
+    /// Returns `false` if tangents could not be generated.
+    pub fn generate_tangents(&mut self) -> bool {
+        generate_tangents(&mut self.vertices, &mut self.indices)
+    }
+
+    /// Generates tangents if `V` stores them. Called at the end of every shape generator.
+    fn with_generated_tangents(mut self) -> MeshData<V> {
+        if V::HAS_TANGENT {
+            self.generate_tangents();
+        }
+        self
+    }
+
+    /// Appends the vertices and triangles of `other` onto this mesh.
+    pub fn merge(&mut self, other: &MeshData<V>) {
+        let offset = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&other.vertices);
+        self.indices
+            .extend(other.indices.iter().map(|i| i + offset));
+    }
+
+    /// Builds a surface of revolution around the Y axis by sweeping `profile` through `sectors` steps.
+    ///
+    /// The profile should run from top to bottom. Points with zero radius produce a pole, and the
+    /// degenerate triangles touching it are skipped.
+    fn lathe(profile: &[ProfilePoint], sectors: u32) -> MeshData<V> {
+        let mut vertices = Vec::with_capacity(profile.len() * (sectors as usize + 1));
+        for p in profile {
+            for j in 0..=sectors {
+                let u = j as f32 / sectors as f32;
+                let (sin, cos) = (u * TAU).sin_cos();
+                vertices.push(V::with_features(
+                    [p.radius * cos, p.y, p.radius * sin],
+                    [p.normal[0] * cos, p.normal[1], p.normal[0] * sin],
+                    [u, p.v],
+                ));
+            }
+        }
+
+        let ring = sectors + 1;
+        let mut indices = Vec::new();
+        for (i, pair) in profile.windows(2).enumerate() {
+            for j in 0..sectors {
+                let a = i as u32 * ring + j;
+                let b = a + 1;
+                let c = a + ring;
+                let d = c + 1;
+                if pair[0].radius != 0.0 {
+                    indices.extend_from_slice(&[a, b, c]);
+                }
+                if pair[1].radius != 0.0 {
+                    indices.extend_from_slice(&[b, d, c]);
+                }
+            }
+        }
+
+        MeshData { vertices, indices }
+    }
+
+    /// Builds a flat disc at height `y`, facing up or down.
+    fn cap(radius: f32, y: f32, sectors: u32, up: bool) -> MeshData<V> {
+        let normal = if up {
+            [0.0, 1.0, 0.0]
+        } else {
+            [0.0, -1.0, 0.0]
+        };
+        let mut vertices = vec![V::with_features([0.0, y, 0.0], normal, [0.5, 0.5])];
+        for j in 0..=sectors {
+            let (sin, cos) = (j as f32 / sectors as f32 * TAU).sin_cos();
+            vertices.push(V::with_features(
+                [radius * cos, y, radius * sin],
+                normal,
+                [0.5 + 0.5 * cos, 0.5 + 0.5 * sin],
+            ));
+        }
+
+        let mut indices = Vec::with_capacity(sectors as usize * 3);
+        for j in 1..=sectors {
+            if up {
+                indices.extend_from_slice(&[0, j + 1, j]);
+            } else {
+                indices.extend_from_slice(&[0, j, j + 1]);
+            }
+        }
+
+        MeshData { vertices, indices }
+    }
+
+    /// Generates a UV sphere with `sectors` divisions around and `stacks` divisions from pole to pole.
+    pub fn uv_sphere(radius: f32, sectors: u32, stacks: u32) -> MeshData<V> {
+        let sectors = sectors.max(3);
+        let stacks = stacks.max(2);
+        let profile: Vec<ProfilePoint> = (0..=stacks)
+            .map(|i| {
+                let v = i as f32 / stacks as f32;
+                let (sin, cos) = (v * PI).sin_cos();
+                ProfilePoint {
+                    radius: if i == 0 || i == stacks {
+                        0.0
+                    } else {
+                        radius * sin
+                    },
+                    y: radius * cos,
+                    normal: [sin, cos],
+                    v,
+                }
+            })
+            .collect();
+
+        MeshData::lathe(&profile, sectors).with_generated_tangents()
+    }
+
+    /// Generates a sphere by subdividing an icosahedron `subdivisions` times.
+    ///
+    /// Texture coordinates use the same spherical mapping as `uv_sphere`, with vertices duplicated
+    /// along the seam and at the poles so that no triangle wraps around the texture.
+    pub fn icosphere(radius: f32, subdivisions: u32) -> MeshData<V> {
+        let t = (1.0 + 5.0f32.sqrt()) / 2.0;
+        let mut positions: Vec<Vector3<f32>> = [
+            [-1.0, t, 0.0],
+            [1.0, t, 0.0],
+            [-1.0, -t, 0.0],
+            [1.0, -t, 0.0],
+            [0.0, -1.0, t],
+            [0.0, 1.0, t],
+            [0.0, -1.0, -t],
+            [0.0, 1.0, -t],
+            [t, 0.0, -1.0],
+            [t, 0.0, 1.0],
+            [-t, 0.0, -1.0],
+            [-t, 0.0, 1.0],
+        ]
+        .iter()
+        .map(|p| Vector3::from(*p).normalize())
+        .collect();
+
+        let mut triangles: Vec<[u32; 3]> = vec![
+            [0, 11, 5],
+            [0, 5, 1],
+            [0, 1, 7],
+            [0, 7, 10],
+            [0, 10, 11],
+            [1, 5, 9],
+            [5, 11, 4],
+            [11, 10, 2],
+            [10, 7, 6],
+            [7, 1, 8],
+            [3, 9, 4],
+            [3, 4, 2],
+            [3, 2, 6],
+            [3, 6, 8],
+            [3, 8, 9],
+            [4, 9, 5],
+            [2, 4, 11],
+            [6, 2, 10],
+            [8, 6, 7],
+            [9, 8, 1],
+        ];
+
+        for _ in 0..subdivisions {
+            let mut midpoints: HashMap<(u32, u32), u32> = HashMap::new();
+            let mut midpoint = |a: u32, b: u32| {
+                let key = (a.min(b), a.max(b));
+                *midpoints.entry(key).or_insert_with(|| {
+                    let p = (positions[a as usize] + positions[b as usize]).normalize();
+                    positions.push(p);
+                    positions.len() as u32 - 1
+                })
+            };
+
+            triangles = triangles
+                .iter()
+                .flat_map(|&[a, b, c]| {
+                    let ab = midpoint(a, b);
+                    let bc = midpoint(b, c);
+                    let ca = midpoint(c, a);
+                    vec![[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+                })
+                .collect();
+        }
+
+        // Matches the orientation of `lathe`, which places U = 0 on +X and increases towards +Z
+        let uv = |p: &Vector3<f32>| {
+            let u = p.z.atan2(p.x) / TAU;
+            [
+                if u < 0.0 { u + 1.0 } else { u },
+                p.y.clamp(-1.0, 1.0).acos() / PI,
+            ]
+        };
+
+        let mut vertices = Vec::new();
+        let mut remap: HashMap<(u32, u32), u32> = HashMap::new();
+        let mut indices = Vec::with_capacity(triangles.len() * 3);
+        for tri in &triangles {
+            let mut uvs: Vec<[f32; 2]> = tri.iter().map(|&i| uv(&positions[i as usize])).collect();
+            let is_pole = |i: usize| positions[tri[i] as usize].y.abs() > 1.0 - 1e-6;
+
+            // Triangles crossing the seam have some corners near U = 1 and others near U = 0
+            let max_u = uvs.iter().map(|uv| uv[0]).fold(0.0, f32::max);
+            let min_u = uvs.iter().map(|uv| uv[0]).fold(1.0, f32::min);
+            if max_u - min_u > 0.5 {
+                for (i, uv) in uvs.iter_mut().enumerate() {
+                    if uv[0] < 0.5 && !is_pole(i) {
+                        uv[0] += 1.0;
+                    }
+                }
+            }
+
+            // Poles have no meaningful U, so use the average of the other two corners
+            for i in 0..3 {
+                if is_pole(i) {
+                    uvs[i][0] = (uvs[(i + 1) % 3][0] + uvs[(i + 2) % 3][0]) / 2.0;
+                }
+            }
+
+            for (&i, tc) in tri.iter().zip(&uvs) {
+                let p = positions[i as usize];
+                let index = *remap.entry((i, tc[0].to_bits())).or_insert_with(|| {
+                    vertices.push(V::with_features((p * radius).into(), p.into(), *tc));
+                    vertices.len() as u32 - 1
+                });
+                indices.push(index);
+            }
+        }
+
+        MeshData { vertices, indices }.with_generated_tangents()
+    }
+
+    /// Generates a capped cylinder of the given `height`, with `sectors` divisions around.
+    pub fn cylinder(radius: f32, height: f32, sectors: u32) -> MeshData<V> {
+        let sectors = sectors.max(3);
+        let half = height / 2.0;
+        let profile = [
+            ProfilePoint {
+                radius,
+                y: half,
+                normal: [1.0, 0.0],
+                v: 0.0,
+            },
+            ProfilePoint {
+                radius,
+                y: -half,
+                normal: [1.0, 0.0],
+                v: 1.0,
+            },
+        ];
+
+        let mut mesh = MeshData::lathe(&profile, sectors);
+        mesh.merge(&MeshData::cap(radius, half, sectors, true));
+        mesh.merge(&MeshData::cap(radius, -half, sectors, false));
+        mesh.with_generated_tangents()
+    }
+
+    /// Generates a cone of the given `height` with its apex pointing up, with `sectors` divisions around.
+    pub fn cone(radius: f32, height: f32, sectors: u32) -> MeshData<V> {
+        let sectors = sectors.max(3);
+        let half = height / 2.0;
+        let slant = Vector3::new(height, radius, 0.0).normalize();
+        let profile = [
+            ProfilePoint {
+                radius: 0.0,
+                y: half,
+                normal: [slant.x, slant.y],
+                v: 0.0,
+            },
+            ProfilePoint {
+                radius,
+                y: -half,
+                normal: [slant.x, slant.y],
+                v: 1.0,
+            },
+        ];
+
+        let mut mesh = MeshData::lathe(&profile, sectors);
+        mesh.merge(&MeshData::cap(radius, -half, sectors, false));
+        mesh.with_generated_tangents()
+    }
+
+    /// Generates a torus lying in the XZ plane.
+    ///
+    /// `major_radius` is the distance from the centre to the middle of the tube, and `minor_radius`
+    /// is the radius of the tube.
+    pub fn torus(
+        major_radius: f32,
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    ) -> MeshData<V> {
+        let major_segments = major_segments.max(3);
+        let minor_segments = minor_segments.max(3);
+
+        // Start at the top of the tube and sweep outwards, so the profile runs top to bottom on the outside
+        let profile: Vec<ProfilePoint> = (0..=minor_segments)
+            .map(|j| {
+                let v = j as f32 / minor_segments as f32;
+                let (sin, cos) = (v * TAU).sin_cos();
+                ProfilePoint {
+                    radius: major_radius + minor_radius * sin,
+                    y: minor_radius * cos,
+                    normal: [sin, cos],
+                    v,
+                }
+            })
+            .collect();
+
+        MeshData::lathe(&profile, major_segments).with_generated_tangents()
+    }
+
+    /// Generates a capsule: a cylinder of the given `height` with hemispherical ends.
+    ///
+    /// The total height is `height + 2.0 * radius`. Each hemisphere has `stacks` divisions from pole to equator.
+    pub fn capsule(radius: f32, height: f32, sectors: u32, stacks: u32) -> MeshData<V> {
+        let sectors = sectors.max(3);
+        let stacks = stacks.max(1);
+        let half = height / 2.0;
+
+        // V runs along the length of the profile, so the texture is not stretched over the cylinder
+        let length = PI * radius + height;
+        let quarter = PI * radius / 2.0;
+
+        let mut profile = Vec::with_capacity(2 * stacks as usize + 2);
+        for i in 0..=stacks {
+            let t = i as f32 / stacks as f32;
+            let (sin, cos) = (t * PI / 2.0).sin_cos();
+            profile.push(ProfilePoint {
+                radius: if i == 0 { 0.0 } else { radius * sin },
+                y: half + radius * cos,
+                normal: [sin, cos],
+                v: t * quarter / length,
+            });
+        }
+        for i in 0..=stacks {
+            let t = i as f32 / stacks as f32;
+            let (sin, cos) = (PI / 2.0 + t * PI / 2.0).sin_cos();
+            profile.push(ProfilePoint {
+                radius: if i == stacks { 0.0 } else { radius * sin },
+                y: -half + radius * cos,
+                normal: [sin, cos],
+                v: (quarter + height + t * quarter) / length,
+            });
+        }
+
+        MeshData::lathe(&profile, sectors).with_generated_tangents()
+    }
+}
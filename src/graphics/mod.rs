@@ -7,18 +7,50 @@ pub use display::*;
 pub mod texture;
 pub use texture::*;
 
+pub mod capture;
+pub use capture::*;
+
 pub mod vertex;
 pub use vertex::*;
 
 pub mod bindgroup;
 pub use bindgroup::*;
 
+pub mod uniform;
+pub use uniform::*;
+
 pub mod pipeline;
 pub use pipeline::*;
 
+pub mod layout;
+pub use layout::*;
+
+pub mod preprocess;
+pub use preprocess::*;
+
+pub mod reflect;
+pub use reflect::*;
+
+#[cfg(feature = "hot-reload")]
+pub mod reload;
+#[cfg(feature = "hot-reload")]
+pub use reload::*;
+
+pub mod compute;
+pub use compute::*;
+
+pub mod bounds;
+pub use bounds::*;
+
 pub mod mesh;
 pub use mesh::*;
 
+pub mod meshdata;
+pub use meshdata::*;
+
+pub mod tangent;
+pub use tangent::*;
+
 pub mod instanced;
 pub use instanced::*;
 
@@ -1,27 +1,104 @@
 use std::mem::size_of;
 
+use anyhow::Result;
+
+use super::{Display, MultisampledTarget, Shader, ShaderReflection, DEPTH_FORMAT};
+
+/// Vertex attributes of a type stored in a vertex or instance buffer.
+///
+/// Usually derived with `#[derive(HasLayout)]`, see `AttributeFormat` for the supported field types.
 pub trait HasLayout {
+    /// Attributes of `Self`, with shader locations starting at `shader_offset`.
     fn layout(shader_offset: u32) -> Vec<wgpu::VertexAttribute>;
 }
 
+/// Common colour blending modes.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum BlendMode {
+    /// Overwrite the target.
+    Replace,
+    /// Blend by the source alpha, for straight (non-premultiplied) alpha.
+    Alpha,
+    /// Blend colours that are already multiplied by their alpha.
+    Premultiplied,
+    /// Add the source colour to the target, for glows and particles.
+    Additive,
+}
+
+impl BlendMode {
+    pub fn state(self) -> wgpu::BlendState {
+        match self {
+            BlendMode::Replace => wgpu::BlendState::REPLACE,
+            BlendMode::Alpha => wgpu::BlendState::ALPHA_BLENDING,
+            BlendMode::Premultiplied => wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING,
+            BlendMode::Additive => {
+                let add = wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
+                };
+                wgpu::BlendState {
+                    color: add,
+                    alpha: add,
+                }
+            }
+        }
+    }
+}
+
+/// Builds a render pipeline, defaulting to opaque, back-face culled triangle lists with a single
+/// colour target.
+///
+/// The first colour target has the format passed to `build`, and its blend state and write mask
+/// are set by `with_blend` and `with_write_mask`. Further targets, for example G-buffer
+/// attachments, are added with `push_color_target`.
 pub struct RenderPipelineBuilder<'a> {
     label: Option<&'static str>,
-    module: Option<&'a wgpu::ShaderModule>,
-    module_entry_point: &'static str,
+    vertex_module: Option<&'a wgpu::ShaderModule>,
+    vertex_entry_point: &'static str,
+    fragment_module: Option<&'a wgpu::ShaderModule>,
+    fragment_entry_point: &'static str,
     layout: Option<&'a wgpu::PipelineLayout>,
+    vertex_reflection: Option<&'a ShaderReflection>,
     buffer_layouts: Vec<(BufferLayoutType, u64)>,
+    blend: Option<wgpu::BlendState>,
+    write_mask: wgpu::ColorWrite,
+    extra_targets: Vec<wgpu::ColorTargetState>,
+    primitive: wgpu::PrimitiveState,
     depth_state: Option<wgpu::DepthStencilState>,
+    sample_count: u32,
+}
+
+impl<'a> Default for RenderPipelineBuilder<'a> {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl<'a> RenderPipelineBuilder<'a> {
     pub fn new() -> RenderPipelineBuilder<'a> {
         RenderPipelineBuilder {
             label: None,
-            module: None,
-            module_entry_point: "main",
+            vertex_module: None,
+            vertex_entry_point: "main",
+            fragment_module: None,
+            fragment_entry_point: "main",
             layout: None,
+            vertex_reflection: None,
             buffer_layouts: Vec::new(),
+            blend: Some(wgpu::BlendState::REPLACE),
+            write_mask: wgpu::ColorWrite::ALL,
+            extra_targets: Vec::new(),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleList,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: Some(wgpu::Face::Back),
+                polygon_mode: wgpu::PolygonMode::Fill,
+                ..Default::default()
+            },
             depth_state: None,
+            sample_count: 1,
         }
     }
 
@@ -30,13 +107,49 @@ impl<'a> RenderPipelineBuilder<'a> {
         self
     }
 
+    /// Uses `module` for both the vertex and fragment stages.
     pub fn with_module(&mut self, module: &'a wgpu::ShaderModule) -> &mut Self {
-        self.module = Some(module);
+        self.vertex_module = Some(module);
+        self.fragment_module = Some(module);
+        self.vertex_reflection = None;
         self
     }
 
+    /// Uses `shader` for both the vertex and fragment stages, along with its pipeline layout.
+    ///
+    /// The vertex buffer layouts are checked against the shader's vertex inputs when building.
+    pub fn with_shader(&mut self, shader: &'a Shader) -> &mut Self {
+        self.with_module(&shader.module);
+        self.vertex_reflection = Some(&shader.reflection);
+        self.layout = Some(&shader.pipeline_layout);
+        self
+    }
+
+    /// Uses `ep` as the entry point of both the vertex and fragment stages.
     pub fn with_module_entry_point(&mut self, ep: &'static str) -> &mut Self {
-        self.module_entry_point = ep;
+        self.vertex_entry_point = ep;
+        self.fragment_entry_point = ep;
+        self
+    }
+
+    pub fn with_vertex_module(&mut self, module: &'a wgpu::ShaderModule) -> &mut Self {
+        self.vertex_module = Some(module);
+        self.vertex_reflection = None;
+        self
+    }
+
+    pub fn with_vertex_entry_point(&mut self, ep: &'static str) -> &mut Self {
+        self.vertex_entry_point = ep;
+        self
+    }
+
+    pub fn with_fragment_module(&mut self, module: &'a wgpu::ShaderModule) -> &mut Self {
+        self.fragment_module = Some(module);
+        self
+    }
+
+    pub fn with_fragment_entry_point(&mut self, ep: &'static str) -> &mut Self {
+        self.fragment_entry_point = ep;
         self
     }
 
@@ -71,37 +184,245 @@ impl<'a> RenderPipelineBuilder<'a> {
         self
     }
 
+    /// Blend mode of the first colour target.
+    pub fn with_blend(&mut self, blend: BlendMode) -> &mut Self {
+        self.blend = Some(blend.state());
+        self
+    }
+
+    /// Blend state of the first colour target, `None` disables blending.
+    pub fn with_blend_state(&mut self, blend: Option<wgpu::BlendState>) -> &mut Self {
+        self.blend = blend;
+        self
+    }
+
+    /// Channels of the first colour target that are written.
+    pub fn with_write_mask(&mut self, write_mask: wgpu::ColorWrite) -> &mut Self {
+        self.write_mask = write_mask;
+        self
+    }
+
+    /// Adds another colour target after the first, writing every channel.
+    pub fn push_color_target(
+        &mut self,
+        format: wgpu::TextureFormat,
+        blend: BlendMode,
+    ) -> &mut Self {
+        self.push_color_target_state(wgpu::ColorTargetState {
+            format,
+            blend: Some(blend.state()),
+            write_mask: wgpu::ColorWrite::ALL,
+        })
+    }
+
+    /// Adds another colour target after the first.
+    pub fn push_color_target_state(&mut self, target: wgpu::ColorTargetState) -> &mut Self {
+        self.extra_targets.push(target);
+        self
+    }
+
+    /// Sets the primitive topology. Strip topologies restart strips at the maximum `u32` index.
+    pub fn with_topology(&mut self, topology: wgpu::PrimitiveTopology) -> &mut Self {
+        self.primitive.topology = topology;
+        self.primitive.strip_index_format = match topology {
+            wgpu::PrimitiveTopology::LineStrip | wgpu::PrimitiveTopology::TriangleStrip => {
+                Some(wgpu::IndexFormat::Uint32)
+            }
+            _ => None,
+        };
+        self
+    }
+
+    /// Index format of strips, needed when drawing strips with 16 bit index buffers.
+    pub fn with_strip_index_format(&mut self, format: Option<wgpu::IndexFormat>) -> &mut Self {
+        self.primitive.strip_index_format = format;
+        self
+    }
+
+    /// Faces to cull, `None` draws both sides.
+    pub fn with_cull_mode(&mut self, cull_mode: Option<wgpu::Face>) -> &mut Self {
+        self.primitive.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn with_front_face(&mut self, front_face: wgpu::FrontFace) -> &mut Self {
+        self.primitive.front_face = front_face;
+        self
+    }
+
+    /// `Line` and `Point` need `Features::NON_FILL_POLYGON_MODE`.
+    pub fn with_polygon_mode(&mut self, polygon_mode: wgpu::PolygonMode) -> &mut Self {
+        self.primitive.polygon_mode = polygon_mode;
+        self
+    }
+
+    /// Draws triangle edges only, needs `Features::NON_FILL_POLYGON_MODE`.
+    pub fn with_wireframe(&mut self) -> &mut Self {
+        self.with_polygon_mode(wgpu::PolygonMode::Line)
+    }
+
+    /// Enables depth testing and depth writes, against a `DEPTH_FORMAT` attachment unless
+    /// `with_depth_format` says otherwise. Other depth and stencil settings are kept.
     pub fn with_depth_stencil(&mut self, depth_compare: wgpu::CompareFunction) -> &mut Self {
-        self.depth_state = Some(wgpu::DepthStencilState {
-            format: wgpu::TextureFormat::Depth32Float,
-            depth_write_enabled: true,
-            depth_compare,
-            stencil: wgpu::StencilState::default(),
-            bias: wgpu::DepthBiasState::default(),
-        });
+        let state = self.depth_state_mut();
+        state.depth_compare = depth_compare;
+        state.depth_write_enabled = true;
+        self
+    }
+
+    /// Format of the depth attachment, a format with stencil is needed for `with_stencil`.
+    pub fn with_depth_format(&mut self, format: wgpu::TextureFormat) -> &mut Self {
+        self.depth_state_mut().format = format;
+        self
+    }
+
+    /// Enables or disables depth writes, for example to draw transparent objects or decals.
+    pub fn with_depth_write(&mut self, enabled: bool) -> &mut Self {
+        self.depth_state_mut().depth_write_enabled = enabled;
         self
     }
 
+    /// Offsets depth values, for example to avoid shadow acne or z-fighting decals.
+    pub fn with_depth_bias(&mut self, constant: i32, slope_scale: f32, clamp: f32) -> &mut Self {
+        self.depth_state_mut().bias = wgpu::DepthBiasState {
+            constant,
+            slope_scale,
+            clamp,
+        };
+        self
+    }
+
+    pub fn with_stencil(&mut self, stencil: wgpu::StencilState) -> &mut Self {
+        self.depth_state_mut().stencil = stencil;
+        self
+    }
+
+    /// Depth state to modify, enabling an always passing depth test if there is none yet.
+    fn depth_state_mut(&mut self) -> &mut wgpu::DepthStencilState {
+        self.depth_state
+            .get_or_insert_with(|| wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: false,
+                depth_compare: wgpu::CompareFunction::Always,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            })
+    }
+
+    /// Number of samples per pixel of the render targets, `build_for_display` and
+    /// `build_for_target` set this themselves.
+    pub fn with_sample_count(&mut self, sample_count: u32) -> &mut Self {
+        self.sample_count = sample_count;
+        self
+    }
+
+    /// Colour targets the pipeline is built with, the first of which has `format`.
+    pub fn color_targets(&self, format: wgpu::TextureFormat) -> Vec<wgpu::ColorTargetState> {
+        let mut targets = vec![wgpu::ColorTargetState {
+            format,
+            blend: self.blend,
+            write_mask: self.write_mask,
+        }];
+        targets.extend_from_slice(&self.extra_targets);
+        targets
+    }
+
+    pub fn primitive_state(&self) -> wgpu::PrimitiveState {
+        self.primitive
+    }
+
+    /// Depth and stencil state, `None` if no depth or stencil setting has been made.
+    pub fn depth_stencil_state(&self) -> Option<wgpu::DepthStencilState> {
+        self.depth_state.clone()
+    }
+
+    pub fn multisample_state(&self) -> wgpu::MultisampleState {
+        wgpu::MultisampleState {
+            count: self.sample_count,
+            mask: !0,
+            alpha_to_coverage_enabled: false,
+        }
+    }
+
+    /// Builds a pipeline rendering to `dpy`, matching its format and sample count.
+    pub fn build_for_display(&mut self, dpy: &Display) -> wgpu::RenderPipeline {
+        self.sample_count = dpy.sample_count;
+        self.build(&dpy.device, dpy.sc_desc.format)
+    }
+
+    /// Builds a pipeline rendering to `target`, matching its format and sample count.
+    pub fn build_for_target(
+        &mut self,
+        device: &wgpu::Device,
+        target: &MultisampledTarget,
+    ) -> wgpu::RenderPipeline {
+        self.sample_count = target.sample_count;
+        self.build(device, target.format())
+    }
+
+    /// Like `build_for_display`, but returns an error if the vertex buffer layouts do not match
+    /// the shader set with `with_shader`.
+    pub fn try_build_for_display(&mut self, dpy: &Display) -> Result<wgpu::RenderPipeline> {
+        self.sample_count = dpy.sample_count;
+        self.try_build(&dpy.device, dpy.sc_desc.format)
+    }
+
+    /// Like `build`, but returns an error if the vertex buffer layouts do not match the shader
+    /// set with `with_shader`.
+    pub fn try_build(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> Result<wgpu::RenderPipeline> {
+        if let Some(reflection) = self.vertex_reflection {
+            let attributes: Vec<&[wgpu::VertexAttribute]> = self
+                .buffer_layouts
+                .iter()
+                .map(|(t, _)| t.attributes())
+                .collect();
+            reflection.check_vertex_layout(self.vertex_entry_point, &attributes)?;
+        }
+        Ok(self.build_unchecked(device, format))
+    }
+
+    /// # Panics
+    ///
+    /// If a shader module is missing, or the vertex buffer layouts do not match the shader set
+    /// with `with_shader`.
     pub fn build(&mut self, device: &wgpu::Device, format: wgpu::TextureFormat) -> wgpu::RenderPipeline {
-        let module = self
-            .module
-            .expect("Cannot construct render pipeline without shader module.");
+        self.try_build(device, format)
+            .unwrap_or_else(|e| panic!("Cannot construct render pipeline: {:#}", e))
+    }
+
+    fn build_unchecked(
+        &mut self,
+        device: &wgpu::Device,
+        format: wgpu::TextureFormat,
+    ) -> wgpu::RenderPipeline {
+        let vertex_module = self
+            .vertex_module
+            .expect("Cannot construct render pipeline without vertex shader module.");
+        let fragment_module = self
+            .fragment_module
+            .expect("Cannot construct render pipeline without fragment shader module.");
+
+        let targets = self.color_targets(format);
 
         let mut buffers = Vec::new();
         for vb in &self.buffer_layouts {
-            match &*vb {
+            match vb {
                 (BufferLayoutType::Vertex(v), s) => {
                     buffers.push(wgpu::VertexBufferLayout {
                         array_stride: *s,
                         step_mode: wgpu::InputStepMode::Vertex,
-                        attributes: &v,
+                        attributes: v,
                     });
                 }
                 (BufferLayoutType::Instance(v), s) => {
                     buffers.push(wgpu::VertexBufferLayout {
                         array_stride: *s,
                         step_mode: wgpu::InputStepMode::Instance,
-                        attributes: &v,
+                        attributes: v,
                     });
                 }
             }
@@ -112,33 +433,18 @@ impl<'a> RenderPipelineBuilder<'a> {
                 label: self.label,
                 layout: self.layout,
                 vertex: wgpu::VertexState {
-                    module: module,
-                    entry_point: self.module_entry_point,
+                    module: vertex_module,
+                    entry_point: self.vertex_entry_point,
                     buffers: &buffers,
                 },
                 fragment: Some(wgpu::FragmentState {
-                    module: module,
-                    entry_point: self.module_entry_point,
-                    targets: &[wgpu::ColorTargetState {
-                        format: format,
-                        blend: Some(wgpu::BlendState::REPLACE),
-                        write_mask: wgpu::ColorWrite::ALL,
-                    }],
+                    module: fragment_module,
+                    entry_point: self.fragment_entry_point,
+                    targets: &targets,
                 }),
-                primitive: wgpu::PrimitiveState {
-                    topology: wgpu::PrimitiveTopology::TriangleList,
-                    strip_index_format: None,
-                    front_face: wgpu::FrontFace::Ccw,
-                    cull_mode: Some(wgpu::Face::Back),
-                    polygon_mode: wgpu::PolygonMode::Fill,
-                    ..Default::default()
-                },
-                depth_stencil: self.depth_state.to_owned(),
-                multisample: wgpu::MultisampleState {
-                    count: 1,
-                    mask: !0,
-                    alpha_to_coverage_enabled: false,
-                },
+                primitive: self.primitive_state(),
+                depth_stencil: self.depth_stencil_state(),
+                multisample: self.multisample_state(),
             })
     }
 }
@@ -149,6 +455,13 @@ enum BufferLayoutType {
 }
 
 impl BufferLayoutType {
+    fn attributes(&self) -> &[wgpu::VertexAttribute] {
+        match self {
+            BufferLayoutType::Vertex(v) => v,
+            BufferLayoutType::Instance(v) => v,
+        }
+    }
+
     fn attribute_count(&self) -> u32 {
         match self {
             BufferLayoutType::Vertex(v) => v.len() as u32,
@@ -0,0 +1,377 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Context, Result};
+
+use super::Shader;
+
+/// Names defined before preprocessing a shader, choosing one of its permutations.
+///
+/// Defines with a value replace matching identifiers in the shader source, defines without one
+/// only affect `#ifdef` and `#ifndef`.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct ShaderDefines {
+    defines: BTreeMap<String, String>,
+}
+
+impl ShaderDefines {
+    pub fn new() -> ShaderDefines {
+        ShaderDefines::default()
+    }
+
+    /// Defines `name` without a value.
+    pub fn with(mut self, name: &str) -> Self {
+        self.define(name, "");
+        self
+    }
+
+    /// Defines `name` as `value`.
+    pub fn with_value(mut self, name: &str, value: impl ToString) -> Self {
+        self.define(name, value);
+        self
+    }
+
+    pub fn define(&mut self, name: &str, value: impl ToString) {
+        self.defines.insert(name.to_string(), value.to_string());
+    }
+
+    pub fn undefine(&mut self, name: &str) {
+        self.defines.remove(name);
+    }
+
+    pub fn is_defined(&self, name: &str) -> bool {
+        self.defines.contains_key(name)
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.defines.get(name).map(|s| s.as_str())
+    }
+}
+
+/// A line of a source file.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SourceLocation {
+    pub file: PathBuf,
+    /// 1-based line number.
+    pub line: usize,
+}
+
+impl fmt::Display for SourceLocation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.file.display(), self.line)
+    }
+}
+
+/// WGSL source with its `#` directives resolved.
+///
+/// Supported directives are:
+///
+/// * `#include "path"` - Inserts a file, relative to the including file. Each file is included at
+///   most once per shader, later includes of the same file are ignored.
+/// * `#define NAME [value]` and `#undef NAME`
+/// * `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif`
+///
+/// Parse errors are reported at their original file and line through `source_map`. naga does not
+/// record where validation errors occur, so those name the root file and the failing function
+/// instead.
+#[derive(Clone, Debug)]
+pub struct PreprocessedShader {
+    pub source: String,
+    /// Original location of each line of `source`.
+    pub source_map: Vec<SourceLocation>,
+    /// Canonical path of every file read, starting with the root file if it was read from disk.
+    pub files: Vec<PathBuf>,
+    /// Path of the root file, as passed to `from_file` or `from_source`.
+    pub path: PathBuf,
+}
+
+impl PreprocessedShader {
+    /// Preprocesses the file at `path`.
+    pub fn from_file<P: AsRef<Path>>(path: P, defines: &ShaderDefines) -> Result<Self> {
+        let mut preprocessor = Preprocessor::new(defines, path.as_ref());
+        preprocessor.include(path.as_ref(), None)?;
+        Ok(preprocessor.finish())
+    }
+
+    /// Preprocesses `src`, resolving includes and reporting errors as if it were read from `path`.
+    pub fn from_source<P: AsRef<Path>>(
+        src: &str,
+        path: P,
+        defines: &ShaderDefines,
+    ) -> Result<Self> {
+        let mut preprocessor = Preprocessor::new(defines, path.as_ref());
+        preprocessor.process(src, path.as_ref())?;
+        Ok(preprocessor.finish())
+    }
+
+    /// Original location of `line` of `source`, 1-based.
+    pub fn location(&self, line: usize) -> Option<&SourceLocation> {
+        line.checked_sub(1).and_then(|i| self.source_map.get(i))
+    }
+
+    /// Describes a naga parse error of `source`, at its original location.
+    pub(crate) fn parse_error(&self, error: &naga::front::wgsl::ParseError) -> anyhow::Error {
+        let (line, column) = error.location();
+        match self.location(line) {
+            Some(location) => {
+                let text = self.source.lines().nth(line - 1).unwrap_or("");
+                anyhow!("{}:{}: {}\n    {}", location, column, error, text.trim())
+            }
+            None => anyhow!("{}", error),
+        }
+    }
+}
+
+/// One level of `#ifdef` nesting.
+struct Condition {
+    /// Whether the enclosing block is emitted.
+    parent_active: bool,
+    /// Whether the current branch is emitted.
+    active: bool,
+    seen_else: bool,
+    start: SourceLocation,
+}
+
+struct Preprocessor {
+    defines: ShaderDefines,
+    included: HashSet<PathBuf>,
+    output: PreprocessedShader,
+}
+
+impl Preprocessor {
+    fn new(defines: &ShaderDefines, path: &Path) -> Preprocessor {
+        Preprocessor {
+            defines: defines.clone(),
+            included: HashSet::new(),
+            output: PreprocessedShader {
+                source: String::new(),
+                source_map: Vec::new(),
+                files: Vec::new(),
+                path: path.to_path_buf(),
+            },
+        }
+    }
+
+    fn finish(self) -> PreprocessedShader {
+        self.output
+    }
+
+    fn include(&mut self, path: &Path, from: Option<&SourceLocation>) -> Result<()> {
+        let read = || -> Result<(PathBuf, String)> {
+            let canonical = path.canonicalize()?;
+            let src = std::fs::read_to_string(&canonical)?;
+            Ok((canonical, src))
+        };
+        let (canonical, src) = read().with_context(|| match from {
+            Some(from) => format!("{}: Unable to include {}", from, path.display()),
+            None => format!("Unable to read shader {}", path.display()),
+        })?;
+
+        if self.included.insert(canonical.clone()) {
+            self.output.files.push(canonical);
+            self.process(&src, path)?;
+        }
+        Ok(())
+    }
+
+    fn process(&mut self, src: &str, path: &Path) -> Result<()> {
+        let mut conditions: Vec<Condition> = Vec::new();
+
+        for (i, line) in src.lines().enumerate() {
+            let location = SourceLocation {
+                file: path.to_path_buf(),
+                line: i + 1,
+            };
+            let active = conditions.last().map_or(true, |c| c.active);
+
+            let directive = match line.trim_start().strip_prefix('#') {
+                Some(directive) => directive.trim(),
+                None => {
+                    if active {
+                        self.push_line(line, location);
+                    }
+                    continue;
+                }
+            };
+            let (name, args) = match directive.find(char::is_whitespace) {
+                Some(i) => (&directive[..i], directive[i..].trim()),
+                None => (directive, ""),
+            };
+
+            match name {
+                "ifdef" | "ifndef" => {
+                    let defined = self.defines.is_defined(identifier(args, &location)?);
+                    conditions.push(Condition {
+                        parent_active: active,
+                        active: active && defined == (name == "ifdef"),
+                        seen_else: false,
+                        start: location,
+                    });
+                }
+                "else" => match conditions.last_mut() {
+                    Some(c) if !c.seen_else => {
+                        c.active = c.parent_active && !c.active;
+                        c.seen_else = true;
+                    }
+                    Some(_) => bail!("{}: Duplicate #else", location),
+                    None => bail!("{}: #else without #ifdef", location),
+                },
+                "endif" => {
+                    if conditions.pop().is_none() {
+                        bail!("{}: #endif without #ifdef", location);
+                    }
+                }
+                _ if !active => {}
+                "define" => {
+                    let (define, value) = match args.find(char::is_whitespace) {
+                        Some(i) => (&args[..i], args[i..].trim()),
+                        None => (args, ""),
+                    };
+                    let define = identifier(define, &location)?;
+                    self.defines.define(define, value);
+                }
+                "undef" => {
+                    let define = identifier(args, &location)?;
+                    self.defines.undefine(define);
+                }
+                "include" => {
+                    let include = args
+                        .strip_prefix('"')
+                        .and_then(|a| a.strip_suffix('"'))
+                        .ok_or_else(|| anyhow!("{}: Expected #include \"path\"", location))?;
+                    let include = path.parent().unwrap_or_else(|| Path::new("")).join(include);
+                    self.include(&include, Some(&location))?;
+                }
+                _ => bail!("{}: Unknown directive #{}", location, name),
+            }
+        }
+
+        if let Some(c) = conditions.last() {
+            bail!("{}: Unterminated #ifdef", c.start);
+        }
+        Ok(())
+    }
+
+    /// Appends `line` with defines substituted.
+    fn push_line(&mut self, line: &str, location: SourceLocation) {
+        let source = &mut self.output.source;
+        let mut rest = line;
+        while let Some(start) = rest.find(is_identifier_char) {
+            source.push_str(&rest[..start]);
+            rest = &rest[start..];
+            let end = rest
+                .find(|c: char| !is_identifier_char(c))
+                .unwrap_or(rest.len());
+            let (word, after) = rest.split_at(end);
+
+            // Words starting with a digit are numbers, such as `1e5`, and are left alone
+            match self.defines.get(word) {
+                Some(value) if !value.is_empty() => source.push_str(value),
+                _ => source.push_str(word),
+            }
+            rest = after;
+        }
+        source.push_str(rest);
+        source.push('\n');
+        self.output.source_map.push(location);
+    }
+}
+
+fn is_identifier_start(c: char) -> bool {
+    c.is_ascii_alphabetic() || c == '_'
+}
+
+fn is_identifier_char(c: char) -> bool {
+    c.is_ascii_alphanumeric() || c == '_'
+}
+
+fn identifier<'a>(s: &'a str, location: &SourceLocation) -> Result<&'a str> {
+    let valid = s.starts_with(is_identifier_start) && s.chars().all(is_identifier_char);
+    if !valid {
+        bail!("{}: Expected a name, found `{}`", location, s);
+    }
+    Ok(s)
+}
+
+struct CachedShader {
+    shader: Arc<Shader>,
+    files: Vec<PathBuf>,
+}
+
+/// Preprocessed and reflected shaders, cached per file and permutation.
+#[derive(Default)]
+pub struct ShaderCache {
+    shaders: HashMap<(PathBuf, ShaderDefines), CachedShader>,
+}
+
+impl ShaderCache {
+    pub fn new() -> ShaderCache {
+        ShaderCache::default()
+    }
+
+    /// Returns the shader at `path` with `defines`, loading it on first use.
+    ///
+    /// Shaders are cached by canonical path, so different paths to the same file share a shader.
+    pub fn get<P: AsRef<Path>>(
+        &mut self,
+        device: &wgpu::Device,
+        path: P,
+        defines: &ShaderDefines,
+    ) -> Result<Arc<Shader>> {
+        let key = (canonicalize_lossy(path.as_ref()), defines.clone());
+        if let Some(cached) = self.shaders.get(&key) {
+            return Ok(cached.shader.clone());
+        }
+
+        let preprocessed = PreprocessedShader::from_file(&key.0, defines)?;
+        let label = key.0.file_stem().and_then(|s| s.to_str());
+        let shader = Arc::new(Shader::from_preprocessed(device, &preprocessed, label)?);
+        self.shaders.insert(
+            key,
+            CachedShader {
+                shader: shader.clone(),
+                files: preprocessed.files,
+            },
+        );
+        Ok(shader)
+    }
+
+    /// Drops every cached shader that read `file`, directly or through an include.
+    ///
+    /// `file` may have been deleted since, as long as its directory still exists.
+    ///
+    /// Returns the number of permutations dropped.
+    pub fn invalidate<P: AsRef<Path>>(&mut self, file: P) -> usize {
+        let file = canonicalize_lossy(file.as_ref());
+        let before = self.shaders.len();
+        self.shaders
+            .retain(|_, cached| !cached.files.contains(&file));
+        before - self.shaders.len()
+    }
+
+    /// Every file read by a cached shader.
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.shaders
+            .values()
+            .flat_map(|cached| cached.files.iter().map(|f| f.as_path()))
+    }
+
+    pub fn clear(&mut self) {
+        self.shaders.clear();
+    }
+}
+
+/// Canonicalizes `path`, or only its directory if the file itself no longer exists.
+fn canonicalize_lossy(path: &Path) -> PathBuf {
+    if let Ok(path) = path.canonicalize() {
+        return path;
+    }
+    match (path.parent(), path.file_name()) {
+        (Some(dir), Some(name)) => dir
+            .canonicalize()
+            .map_or_else(|_| path.to_path_buf(), |dir| dir.join(name)),
+        _ => path.to_path_buf(),
+    }
+}
@@ -0,0 +1,553 @@
+use std::fmt::Write;
+use std::path::Path;
+
+use super::{PreprocessedShader, ShaderDefines};
+use anyhow::{anyhow, bail, Context, Result};
+use naga::{ImageClass, ImageDimension, ScalarKind, StorageAccess, StorageClass, TypeInner};
+
+/// A resource declared by a shader with `[[group(g), binding(b)]]`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReflectedBinding {
+    pub name: Option<String>,
+    pub group: u32,
+    pub binding: u32,
+    /// Stages whose entry points use the resource. Empty if no entry point uses it.
+    pub visibility: wgpu::ShaderStage,
+    /// Float textures are filterable only if sampled through a filtering sampler, and samplers
+    /// used with a depth texture are non-filtering unless they are comparison samplers.
+    pub ty: wgpu::BindingType,
+}
+
+/// A `[[location(n)]]` input of a vertex entry point.
+#[derive(Clone, Debug, PartialEq)]
+pub struct VertexInput {
+    pub name: Option<String>,
+    pub location: u32,
+    /// Format matching the shader type, e.g. `Float32x3` for `vec3<f32>`. Any vertex format
+    /// with the same scalar type is accepted by the pipeline.
+    pub format: wgpu::VertexFormat,
+}
+
+/// An entry point declared by a shader.
+#[derive(Clone, Debug, PartialEq)]
+pub struct ReflectedEntryPoint {
+    pub name: String,
+    pub stage: wgpu::ShaderStage,
+    /// Location inputs, only filled in for vertex entry points.
+    pub vertex_inputs: Vec<VertexInput>,
+    /// Workgroup size of compute entry points.
+    pub workgroup_size: [u32; 3],
+}
+
+/// Resource bindings and entry points of a WGSL shader, found by parsing it with naga.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct ShaderReflection {
+    /// Bindings sorted by group, then binding.
+    pub bindings: Vec<ReflectedBinding>,
+    pub entry_points: Vec<ReflectedEntryPoint>,
+}
+
+impl ShaderReflection {
+    /// Parses and validates `src`, returning the parser's annotated error on failure.
+    pub fn from_wgsl(src: &str) -> Result<ShaderReflection> {
+        let module = naga::front::wgsl::parse_str(src).map_err(|e| anyhow!(e.emit_to_string()))?;
+        ShaderReflection::from_module(&module)
+    }
+
+    /// Parses and validates a preprocessed shader, reporting parse errors at their original file
+    /// and line, and validation errors in the root file.
+    pub fn from_preprocessed(shader: &PreprocessedShader) -> Result<ShaderReflection> {
+        let module =
+            naga::front::wgsl::parse_str(&shader.source).map_err(|e| shader.parse_error(&e))?;
+        ShaderReflection::from_module(&module)
+            .with_context(|| format!("In shader {}", shader.path.display()))
+    }
+
+    fn from_module(module: &naga::Module) -> Result<ShaderReflection> {
+        let info = naga::valid::Validator::new(naga::valid::ValidationFlags::all())
+            .validate(module)
+            .context("Shader failed validation")?;
+
+        let mut bindings = Vec::new();
+        let mut handles = Vec::new();
+        for (handle, var) in module.global_variables.iter() {
+            let binding = match &var.binding {
+                Some(binding) => binding,
+                None => continue,
+            };
+
+            let mut visibility = wgpu::ShaderStage::NONE;
+            for (i, ep) in module.entry_points.iter().enumerate() {
+                if !info.get_entry_point(i)[handle].is_empty() {
+                    visibility |= stage(ep.stage);
+                }
+            }
+
+            handles.push(handle);
+            bindings.push(ReflectedBinding {
+                name: var.name.clone(),
+                group: binding.group,
+                binding: binding.binding,
+                visibility,
+                ty: binding_type(module, var)?,
+            });
+        }
+
+        // Texture and sampler pairs used by `textureSample*` calls, as binding indices
+        let mut pairs = Vec::new();
+        for i in 0..module.entry_points.len() {
+            for key in &info.get_entry_point(i).sampling_set {
+                let texture = handles.iter().position(|&h| h == key.image);
+                let sampler = handles.iter().position(|&h| h == key.sampler);
+                if let (Some(texture), Some(sampler)) = (texture, sampler) {
+                    pairs.push((texture, sampler));
+                }
+            }
+        }
+
+        // Depth textures cannot be filtered, so samplers used with them must not filter either
+        for &(texture, sampler) in &pairs {
+            if let wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Depth,
+                ..
+            } = bindings[texture].ty
+            {
+                if let wgpu::BindingType::Sampler {
+                    ref mut filtering,
+                    comparison: false,
+                } = bindings[sampler].ty
+                {
+                    *filtering = false;
+                }
+            }
+        }
+
+        // Float textures are only filterable if sampled through a filtering sampler. Others, such
+        // as textures only read with `textureLoad`, also accept formats like `R32Float`.
+        for &(texture, sampler) in &pairs {
+            if let wgpu::BindingType::Sampler {
+                filtering: true,
+                comparison: false,
+            } = bindings[sampler].ty
+            {
+                if let wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { ref mut filterable },
+                    ..
+                } = bindings[texture].ty
+                {
+                    *filterable = true;
+                }
+            }
+        }
+        bindings.sort_by_key(|b| (b.group, b.binding));
+
+        let mut entry_points = Vec::new();
+        for ep in &module.entry_points {
+            let mut vertex_inputs = Vec::new();
+            if ep.stage == naga::ShaderStage::Vertex {
+                for arg in &ep.function.arguments {
+                    match (&arg.binding, &module.types[arg.ty].inner) {
+                        (Some(binding), inner) => {
+                            push_vertex_input(&mut vertex_inputs, &arg.name, binding, inner)?
+                        }
+                        (None, TypeInner::Struct { members, .. }) => {
+                            for member in members {
+                                if let Some(binding) = &member.binding {
+                                    let inner = &module.types[member.ty].inner;
+                                    push_vertex_input(
+                                        &mut vertex_inputs,
+                                        &member.name,
+                                        binding,
+                                        inner,
+                                    )?;
+                                }
+                            }
+                        }
+                        (None, _) => {}
+                    }
+                }
+                vertex_inputs.sort_by_key(|i| i.location);
+            }
+
+            entry_points.push(ReflectedEntryPoint {
+                name: ep.name.clone(),
+                stage: stage(ep.stage),
+                vertex_inputs,
+                workgroup_size: ep.workgroup_size,
+            });
+        }
+
+        Ok(ShaderReflection {
+            bindings,
+            entry_points,
+        })
+    }
+
+    /// Returns the entry point called `name` for `stage`.
+    pub fn entry_point(
+        &self,
+        name: &str,
+        stage: wgpu::ShaderStage,
+    ) -> Option<&ReflectedEntryPoint> {
+        self.entry_points
+            .iter()
+            .find(|ep| ep.name == name && ep.stage == stage)
+    }
+
+    /// Number of bind groups the shader needs, one past the highest group used.
+    pub fn group_count(&self) -> u32 {
+        self.bindings.iter().map(|b| b.group + 1).max().unwrap_or(0)
+    }
+
+    /// Layout entries of bind group `group`, empty if the shader declares nothing in it.
+    pub fn bind_group_layout_entries(&self, group: u32) -> Vec<wgpu::BindGroupLayoutEntry> {
+        self.bindings
+            .iter()
+            .filter(|b| b.group == group)
+            .map(|b| wgpu::BindGroupLayoutEntry {
+                binding: b.binding,
+                visibility: b.visibility,
+                ty: b.ty,
+                count: None,
+            })
+            .collect()
+    }
+
+    /// Creates a layout for each bind group up to `group_count`.
+    pub fn create_bind_group_layouts(&self, device: &wgpu::Device) -> Vec<wgpu::BindGroupLayout> {
+        (0..self.group_count())
+            .map(|group| {
+                device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: None,
+                    entries: &self.bind_group_layout_entries(group),
+                })
+            })
+            .collect()
+    }
+
+    /// Checks that the attributes of `buffers` provide every input of the vertex entry point
+    /// `entry_point`, with a matching scalar type.
+    pub fn check_vertex_layout(
+        &self,
+        entry_point: &str,
+        buffers: &[&[wgpu::VertexAttribute]],
+    ) -> Result<()> {
+        let ep = self
+            .entry_point(entry_point, wgpu::ShaderStage::VERTEX)
+            .ok_or_else(|| anyhow!("Shader has no vertex entry point `{}`", entry_point))?;
+
+        let mut errors = String::new();
+        for input in &ep.vertex_inputs {
+            let name = input.name.as_deref().unwrap_or("<unnamed>");
+            let attribute = buffers
+                .iter()
+                .flat_map(|b| b.iter())
+                .find(|a| a.shader_location == input.location);
+            match attribute {
+                None => writeln!(
+                    errors,
+                    "  input `{}` at location {} ({:?}) has no vertex attribute",
+                    name, input.location, input.format
+                )?,
+                Some(a) if scalar_kind(a.format) != scalar_kind(input.format) => writeln!(
+                    errors,
+                    "  input `{}` at location {} is {:?} but the vertex attribute is {:?}",
+                    name, input.location, input.format, a.format
+                )?,
+                Some(_) => {}
+            }
+        }
+
+        if !errors.is_empty() {
+            bail!(
+                "Vertex buffer layouts do not match the inputs of entry point `{}`:\n{}",
+                entry_point,
+                errors.trim_end()
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A shader module, its reflection and the layouts generated from it.
+pub struct Shader {
+    pub module: wgpu::ShaderModule,
+    pub reflection: ShaderReflection,
+    /// One layout per bind group, indexed by group.
+    pub bind_group_layouts: Vec<wgpu::BindGroupLayout>,
+    pub pipeline_layout: wgpu::PipelineLayout,
+}
+
+impl Shader {
+    /// Reflects `src`, then creates its module and layouts.
+    pub fn from_wgsl(device: &wgpu::Device, src: &str, label: Option<&str>) -> Result<Shader> {
+        let reflection = ShaderReflection::from_wgsl(src)?;
+        Ok(Shader::create(device, src, reflection, label))
+    }
+
+    /// Reflects a preprocessed shader, then creates its module and layouts.
+    pub fn from_preprocessed(
+        device: &wgpu::Device,
+        shader: &PreprocessedShader,
+        label: Option<&str>,
+    ) -> Result<Shader> {
+        let reflection = ShaderReflection::from_preprocessed(shader)?;
+        Ok(Shader::create(device, &shader.source, reflection, label))
+    }
+
+    /// Preprocesses and reflects the WGSL file at `path`, labelled with its file stem.
+    pub fn from_file<P: AsRef<Path>>(device: &wgpu::Device, path: P) -> Result<Shader> {
+        Shader::from_file_with_defines(device, path, &ShaderDefines::new())
+    }
+
+    /// Preprocesses the WGSL file at `path` with `defines`, then reflects it.
+    pub fn from_file_with_defines<P: AsRef<Path>>(
+        device: &wgpu::Device,
+        path: P,
+        defines: &ShaderDefines,
+    ) -> Result<Shader> {
+        let path = path.as_ref();
+        let label = path.file_stem().and_then(|s| s.to_str());
+        let shader = PreprocessedShader::from_file(path, defines)?;
+        Shader::from_preprocessed(device, &shader, label)
+            .with_context(|| format!("Unable to load shader {}", path.display()))
+    }
+
+    fn create(
+        device: &wgpu::Device,
+        src: &str,
+        reflection: ShaderReflection,
+        label: Option<&str>,
+    ) -> Shader {
+        let module = device.create_shader_module(&wgpu::ShaderModuleDescriptor {
+            label,
+            flags: wgpu::ShaderFlags::all(),
+            source: wgpu::ShaderSource::Wgsl(src.into()),
+        });
+        let bind_group_layouts = reflection.create_bind_group_layouts(device);
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label,
+            bind_group_layouts: &bind_group_layouts.iter().collect::<Vec<_>>(),
+            push_constant_ranges: &[],
+        });
+
+        Shader {
+            module,
+            reflection,
+            bind_group_layouts,
+            pipeline_layout,
+        }
+    }
+
+    /// Layout of bind group `group`.
+    ///
+    /// # Panics
+    ///
+    /// If the shader declares no bindings in `group` or any later group.
+    pub fn bind_group_layout(&self, group: u32) -> &wgpu::BindGroupLayout {
+        &self.bind_group_layouts[group as usize]
+    }
+}
+
+fn stage(stage: naga::ShaderStage) -> wgpu::ShaderStage {
+    match stage {
+        naga::ShaderStage::Vertex => wgpu::ShaderStage::VERTEX,
+        naga::ShaderStage::Fragment => wgpu::ShaderStage::FRAGMENT,
+        naga::ShaderStage::Compute => wgpu::ShaderStage::COMPUTE,
+    }
+}
+
+fn binding_type(module: &naga::Module, var: &naga::GlobalVariable) -> Result<wgpu::BindingType> {
+    let name = var.name.as_deref().unwrap_or("<unnamed>");
+    let ty = match (var.class, &module.types[var.ty].inner) {
+        (StorageClass::Uniform, _) => wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Uniform,
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        (StorageClass::Storage, _) => wgpu::BindingType::Buffer {
+            ty: wgpu::BufferBindingType::Storage {
+                read_only: !var.storage_access.contains(StorageAccess::STORE),
+            },
+            has_dynamic_offset: false,
+            min_binding_size: None,
+        },
+        (StorageClass::Handle, TypeInner::Sampler { comparison }) => wgpu::BindingType::Sampler {
+            filtering: true,
+            comparison: *comparison,
+        },
+        (
+            StorageClass::Handle,
+            TypeInner::Image {
+                dim,
+                arrayed,
+                class,
+            },
+        ) => {
+            let view_dimension = view_dimension(*dim, *arrayed);
+            match class {
+                ImageClass::Sampled { kind, multi } => wgpu::BindingType::Texture {
+                    sample_type: match kind {
+                        // Set by `from_module` if sampled through a filtering sampler
+                        ScalarKind::Float => wgpu::TextureSampleType::Float { filterable: false },
+                        ScalarKind::Sint => wgpu::TextureSampleType::Sint,
+                        ScalarKind::Uint => wgpu::TextureSampleType::Uint,
+                        ScalarKind::Bool => bail!("Texture `{}` has bool texels", name),
+                    },
+                    view_dimension,
+                    multisampled: *multi,
+                },
+                ImageClass::Depth => wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Depth,
+                    view_dimension,
+                    multisampled: false,
+                },
+                ImageClass::Storage(format) => wgpu::BindingType::StorageTexture {
+                    access: storage_texture_access(var.storage_access),
+                    format: storage_format(*format),
+                    view_dimension,
+                },
+            }
+        }
+        (class, _) => bail!(
+            "Binding `{}` has unsupported storage class {:?}",
+            name,
+            class
+        ),
+    };
+    Ok(ty)
+}
+
+fn view_dimension(dim: ImageDimension, arrayed: bool) -> wgpu::TextureViewDimension {
+    match (dim, arrayed) {
+        (ImageDimension::D1, _) => wgpu::TextureViewDimension::D1,
+        (ImageDimension::D2, false) => wgpu::TextureViewDimension::D2,
+        (ImageDimension::D2, true) => wgpu::TextureViewDimension::D2Array,
+        (ImageDimension::D3, _) => wgpu::TextureViewDimension::D3,
+        (ImageDimension::Cube, false) => wgpu::TextureViewDimension::Cube,
+        (ImageDimension::Cube, true) => wgpu::TextureViewDimension::CubeArray,
+    }
+}
+
+fn storage_texture_access(access: StorageAccess) -> wgpu::StorageTextureAccess {
+    if access.contains(StorageAccess::LOAD | StorageAccess::STORE) {
+        wgpu::StorageTextureAccess::ReadWrite
+    } else if access.contains(StorageAccess::STORE) {
+        wgpu::StorageTextureAccess::WriteOnly
+    } else {
+        wgpu::StorageTextureAccess::ReadOnly
+    }
+}
+
+fn storage_format(format: naga::StorageFormat) -> wgpu::TextureFormat {
+    macro_rules! same_name {
+        ($($name:ident),*) => {
+            match format {
+                $(naga::StorageFormat::$name => wgpu::TextureFormat::$name,)*
+            }
+        };
+    }
+    same_name!(
+        R8Unorm,
+        R8Snorm,
+        R8Uint,
+        R8Sint,
+        R16Uint,
+        R16Sint,
+        R16Float,
+        Rg8Unorm,
+        Rg8Snorm,
+        Rg8Uint,
+        Rg8Sint,
+        R32Uint,
+        R32Sint,
+        R32Float,
+        Rg16Uint,
+        Rg16Sint,
+        Rg16Float,
+        Rgba8Unorm,
+        Rgba8Snorm,
+        Rgba8Uint,
+        Rgba8Sint,
+        Rgb10a2Unorm,
+        Rg11b10Float,
+        Rg32Uint,
+        Rg32Sint,
+        Rg32Float,
+        Rgba16Uint,
+        Rgba16Sint,
+        Rgba16Float,
+        Rgba32Uint,
+        Rgba32Sint,
+        Rgba32Float
+    )
+}
+
+fn push_vertex_input(
+    inputs: &mut Vec<VertexInput>,
+    name: &Option<String>,
+    binding: &naga::Binding,
+    inner: &TypeInner,
+) -> Result<()> {
+    let location = match binding {
+        naga::Binding::Location { location, .. } => *location,
+        naga::Binding::BuiltIn(_) => return Ok(()),
+    };
+
+    let format = match *inner {
+        TypeInner::Scalar { kind, width } => vertex_format(kind, width, 1),
+        TypeInner::Vector { size, kind, width } => vertex_format(kind, width, size as u8),
+        _ => None,
+    }
+    .ok_or_else(|| {
+        anyhow!(
+            "Vertex input `{}` at location {} has unsupported type {:?}",
+            name.as_deref().unwrap_or("<unnamed>"),
+            location,
+            inner
+        )
+    })?;
+
+    inputs.push(VertexInput {
+        name: name.clone(),
+        location,
+        format,
+    });
+    Ok(())
+}
+
+fn vertex_format(kind: ScalarKind, width: u8, size: u8) -> Option<wgpu::VertexFormat> {
+    use wgpu::VertexFormat as Vf;
+    let format = match (kind, width, size) {
+        (ScalarKind::Float, 4, 1) => Vf::Float32,
+        (ScalarKind::Float, 4, 2) => Vf::Float32x2,
+        (ScalarKind::Float, 4, 3) => Vf::Float32x3,
+        (ScalarKind::Float, 4, 4) => Vf::Float32x4,
+        (ScalarKind::Float, 8, 1) => Vf::Float64,
+        (ScalarKind::Float, 8, 2) => Vf::Float64x2,
+        (ScalarKind::Float, 8, 3) => Vf::Float64x3,
+        (ScalarKind::Float, 8, 4) => Vf::Float64x4,
+        (ScalarKind::Uint, 4, 1) => Vf::Uint32,
+        (ScalarKind::Uint, 4, 2) => Vf::Uint32x2,
+        (ScalarKind::Uint, 4, 3) => Vf::Uint32x3,
+        (ScalarKind::Uint, 4, 4) => Vf::Uint32x4,
+        (ScalarKind::Sint, 4, 1) => Vf::Sint32,
+        (ScalarKind::Sint, 4, 2) => Vf::Sint32x2,
+        (ScalarKind::Sint, 4, 3) => Vf::Sint32x3,
+        (ScalarKind::Sint, 4, 4) => Vf::Sint32x4,
+        _ => return None,
+    };
+    Some(format)
+}
+
+/// Scalar type a vertex format is read as in the shader, normalized formats are read as floats.
+fn scalar_kind(format: wgpu::VertexFormat) -> ScalarKind {
+    use wgpu::VertexFormat as Vf;
+    match format {
+        Vf::Uint8x2 | Vf::Uint8x4 | Vf::Uint16x2 | Vf::Uint16x4 => ScalarKind::Uint,
+        Vf::Uint32 | Vf::Uint32x2 | Vf::Uint32x3 | Vf::Uint32x4 => ScalarKind::Uint,
+        Vf::Sint8x2 | Vf::Sint8x4 | Vf::Sint16x2 | Vf::Sint16x4 => ScalarKind::Sint,
+        Vf::Sint32 | Vf::Sint32x2 | Vf::Sint32x3 | Vf::Sint32x4 => ScalarKind::Sint,
+        _ => ScalarKind::Float,
+    }
+}
@@ -0,0 +1,322 @@
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{bail, Result};
+
+use super::{Display, PreprocessedShader, Shader, ShaderDefines};
+
+/// Detects changes to a set of files by polling their modification times.
+#[derive(Clone, Debug, Default)]
+pub struct FileWatcher {
+    files: Vec<(PathBuf, Option<SystemTime>)>,
+}
+
+impl FileWatcher {
+    pub fn new() -> FileWatcher {
+        FileWatcher::default()
+    }
+
+    /// Replaces the watched files, recording their current modification times.
+    pub fn watch<I, P>(&mut self, files: I)
+    where
+        I: IntoIterator<Item = P>,
+        P: AsRef<Path>,
+    {
+        self.files = files
+            .into_iter()
+            .map(|f| (f.as_ref().to_path_buf(), modified(f.as_ref())))
+            .collect();
+    }
+
+    pub fn files(&self) -> impl Iterator<Item = &Path> {
+        self.files.iter().map(|(f, _)| f.as_path())
+    }
+
+    /// Returns true if any file was modified, created or removed since the last call.
+    pub fn poll(&mut self) -> bool {
+        let mut changed = false;
+        for (file, last) in &mut self.files {
+            let now = modified(file);
+            if now != *last {
+                *last = now;
+                changed = true;
+            }
+        }
+        changed
+    }
+}
+
+fn modified(file: &Path) -> Option<SystemTime> {
+    std::fs::metadata(file).and_then(|m| m.modified()).ok()
+}
+
+/// Identifies a shader loaded by a `ShaderReloader`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShaderId(usize);
+
+/// Identifies a pipeline built by a `ShaderReloader`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineId(usize);
+
+type BuildPipeline = Box<dyn Fn(&Display, &Shader) -> Result<wgpu::RenderPipeline>>;
+
+struct WatchedShader {
+    path: PathBuf,
+    defines: ShaderDefines,
+    shader: Shader,
+    watcher: FileWatcher,
+}
+
+struct WatchedPipeline {
+    shader: ShaderId,
+    build: BuildPipeline,
+    pipeline: wgpu::RenderPipeline,
+}
+
+/// Reloads shaders when their files change and rebuilds the pipelines that use them, for
+/// iterating on WGSL without restarting.
+///
+/// Load shaders with `ShaderReloader::shader_from_file` in place of `shader_from_file`, and
+/// build pipelines from them with `add_pipeline`. Every file a shader includes is watched. If a
+/// shader fails to preprocess or validate, or any of its pipelines fails to build, the error is
+/// logged and the old shader and pipelines are kept until the files change again.
+///
+/// wgpu 0.8 has no error scopes, so to catch the errors wgpu reports while reloading, the
+/// reloader takes over the device's uncaptured error handler whenever it reloads. Errors
+/// reported outside a reload go to the handler set with `on_uncaptured_error`, or are logged if
+/// there is none. Set a handler there rather than on the device, as the device's handler is
+/// replaced on every reload.
+///
+/// Only available with the `hot-reload` feature. Intended for development, ship shaders with
+/// `Shader::from_file` or `ShaderCache` instead.
+pub struct ShaderReloader {
+    /// Minimum time between checks for changed files in `update`.
+    pub poll_interval: Duration,
+    last_poll: Option<Instant>,
+    shaders: Vec<WatchedShader>,
+    pipelines: Vec<WatchedPipeline>,
+    errors: Arc<Mutex<ErrorSink>>,
+}
+
+impl Default for ShaderReloader {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl ShaderReloader {
+    pub fn new() -> ShaderReloader {
+        ShaderReloader {
+            poll_interval: Duration::from_millis(250),
+            last_poll: None,
+            shaders: Vec::new(),
+            pipelines: Vec::new(),
+            errors: Arc::new(Mutex::new(ErrorSink::default())),
+        }
+    }
+
+    /// Handles the errors wgpu reports outside a reload, in place of the device's uncaptured
+    /// error handler.
+    pub fn on_uncaptured_error<F>(&mut self, handler: F)
+    where
+        F: Fn(wgpu::Error) + Send + 'static,
+    {
+        self.errors.lock().unwrap().handler = Some(Box::new(handler));
+    }
+
+    /// Loads the shader at `path` like `shader_from_file`, and watches it and its includes.
+    pub fn shader_from_file<P: AsRef<Path>>(
+        &mut self,
+        device: &wgpu::Device,
+        path: P,
+    ) -> Result<ShaderId> {
+        self.shader_from_file_with_defines(device, path, &ShaderDefines::new())
+    }
+
+    /// Loads the shader at `path` with `defines` and watches it and its includes.
+    pub fn shader_from_file_with_defines<P: AsRef<Path>>(
+        &mut self,
+        device: &wgpu::Device,
+        path: P,
+        defines: &ShaderDefines,
+    ) -> Result<ShaderId> {
+        let path = path.as_ref().to_path_buf();
+        let mut watcher = FileWatcher::new();
+        let shader = load(device, &path, defines, &mut watcher)?;
+
+        self.shaders.push(WatchedShader {
+            path,
+            defines: defines.clone(),
+            shader,
+            watcher,
+        });
+        Ok(ShaderId(self.shaders.len() - 1))
+    }
+
+    /// Builds a pipeline from `shader` with `build`, which is called again whenever the shader
+    /// is reloaded.
+    ///
+    /// `build` usually configures a `RenderPipelineBuilder` with `with_shader` and returns its
+    /// `try_build_for_display`.
+    pub fn add_pipeline<F>(
+        &mut self,
+        dpy: &Display,
+        shader: ShaderId,
+        build: F,
+    ) -> Result<PipelineId>
+    where
+        F: Fn(&Display, &Shader) -> Result<wgpu::RenderPipeline> + 'static,
+    {
+        let pipeline = build(dpy, &self.shaders[shader.0].shader)?;
+        self.pipelines.push(WatchedPipeline {
+            shader,
+            build: Box::new(build),
+            pipeline,
+        });
+        Ok(PipelineId(self.pipelines.len() - 1))
+    }
+
+    pub fn shader(&self, id: ShaderId) -> &Shader {
+        &self.shaders[id.0].shader
+    }
+
+    pub fn pipeline(&self, id: PipelineId) -> &wgpu::RenderPipeline {
+        &self.pipelines[id.0].pipeline
+    }
+
+    /// Reloads shaders whose files changed, at most once per `poll_interval`. Call once per
+    /// frame.
+    ///
+    /// Returns the shaders that were reloaded, bind groups created from their old layouts should
+    /// be recreated if the bindings changed.
+    pub fn update(&mut self, dpy: &Display) -> Vec<ShaderId> {
+        let now = Instant::now();
+        if let Some(last) = self.last_poll {
+            if now - last < self.poll_interval {
+                return Vec::new();
+            }
+        }
+        self.last_poll = Some(now);
+
+        let changed: Vec<ShaderId> = self
+            .shaders
+            .iter_mut()
+            .enumerate()
+            .filter_map(|(i, s)| s.watcher.poll().then_some(ShaderId(i)))
+            .collect();
+        changed
+            .into_iter()
+            .filter(|id| self.reload(dpy, *id))
+            .collect()
+    }
+
+    /// Reloads `id` and rebuilds its pipelines now, whether or not its files changed.
+    ///
+    /// Returns false and keeps the old shader and pipelines if anything fails.
+    pub fn reload(&mut self, dpy: &Display, id: ShaderId) -> bool {
+        let watched = &mut self.shaders[id.0];
+        let errors = &self.errors;
+        let shader = match capture_errors(&dpy.device, errors, || {
+            load(
+                &dpy.device,
+                &watched.path,
+                &watched.defines,
+                &mut watched.watcher,
+            )
+        }) {
+            Ok(shader) => shader,
+            Err(e) => {
+                log::error!(
+                    "Failed to reload shader {}: {:#}",
+                    watched.path.display(),
+                    e
+                );
+                return false;
+            }
+        };
+
+        let mut rebuilt = Vec::new();
+        for (i, pipeline) in self.pipelines.iter().enumerate() {
+            if pipeline.shader != id {
+                continue;
+            }
+            match capture_errors(&dpy.device, errors, || (pipeline.build)(dpy, &shader)) {
+                Ok(new) => rebuilt.push((i, new)),
+                Err(e) => {
+                    log::error!(
+                        "Failed to rebuild pipeline for shader {}: {:#}",
+                        watched.path.display(),
+                        e
+                    );
+                    return false;
+                }
+            }
+        }
+
+        log::info!("Reloaded shader {}", watched.path.display());
+        watched.shader = shader;
+        for (i, new) in rebuilt {
+            self.pipelines[i].pipeline = new;
+        }
+        true
+    }
+}
+
+/// Receives the device's uncaptured errors once a `ShaderReloader` has taken over its handler.
+#[derive(Default)]
+struct ErrorSink {
+    /// Errors reported during the current reload, if one is running.
+    captured: Option<Vec<String>>,
+    handler: Option<Box<dyn Fn(wgpu::Error) + Send>>,
+}
+
+impl ErrorSink {
+    fn report(&mut self, error: wgpu::Error) {
+        match (&mut self.captured, &self.handler) {
+            (Some(captured), _) => captured.push(error.to_string()),
+            (None, Some(handler)) => handler(error),
+            (None, None) => log::error!("Uncaptured wgpu error: {}", error),
+        }
+    }
+}
+
+/// Runs `f`, turning the errors wgpu reports while it runs into an error result.
+///
+/// Installs a handler on `device` reporting to `errors`, which stays in place afterwards.
+fn capture_errors<T, F>(device: &wgpu::Device, errors: &Arc<Mutex<ErrorSink>>, f: F) -> Result<T>
+where
+    F: FnOnce() -> Result<T>,
+{
+    let sink = errors.clone();
+    device.on_uncaptured_error(move |e| sink.lock().unwrap().report(e));
+
+    errors.lock().unwrap().captured = Some(Vec::new());
+    let result = f();
+    let captured = errors.lock().unwrap().captured.take().unwrap_or_default();
+    if !captured.is_empty() {
+        bail!("{}", captured.join("\n"));
+    }
+    result
+}
+
+/// Loads a shader and watches the files it read. The files are watched even if loading fails,
+/// so it is retried when they change again.
+fn load(
+    device: &wgpu::Device,
+    path: &Path,
+    defines: &ShaderDefines,
+    watcher: &mut FileWatcher,
+) -> Result<Shader> {
+    let preprocessed = PreprocessedShader::from_file(path, defines);
+    match &preprocessed {
+        Ok(preprocessed) => watcher.watch(&preprocessed.files),
+        // Keep watching the last known includes, the root file may be mid-save
+        Err(_) if watcher.files().next().is_some() => {}
+        Err(_) => watcher.watch([path]),
+    }
+
+    let preprocessed = preprocessed?;
+    let label = path.file_stem().and_then(|s| s.to_str());
+    Shader::from_preprocessed(device, &preprocessed, label)
+}
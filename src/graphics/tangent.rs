@@ -0,0 +1,100 @@
+use std::collections::HashMap;
+
+use crate::graphics::Vertex;
+
+/// Adapts indexed triangles to the interface expected by `mikktspace`.
+struct TangentGeometry<'a, V>
+where
+    V: Vertex,
+{
+    vertices: &'a [V],
+    indices: &'a [u32],
+    /// Generated tangent of each triangle corner, indexed the same as `indices`.
+    tangents: Vec<[f32; 4]>,
+}
+
+impl<'a, V> TangentGeometry<'a, V>
+where
+    V: Vertex,
+{
+    fn vertex(&self, face: usize, vert: usize) -> &V {
+        &self.vertices[self.indices[face * 3 + vert] as usize]
+    }
+}
+
+impl<'a, V> mikktspace::Geometry for TangentGeometry<'a, V>
+where
+    V: Vertex,
+{
+    fn num_faces(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    fn num_vertices_of_face(&self, _face: usize) -> usize {
+        3
+    }
+
+    fn position(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).position()
+    }
+
+    fn normal(&self, face: usize, vert: usize) -> [f32; 3] {
+        self.vertex(face, vert).normal()
+    }
+
+    fn tex_coord(&self, face: usize, vert: usize) -> [f32; 2] {
+        self.vertex(face, vert).texture_coord()
+    }
+
+    fn set_tangent_encoded(&mut self, tangent: [f32; 4], face: usize, vert: usize) {
+        self.tangents[face * 3 + vert] = tangent;
+    }
+}
+
+/// Generates MikkTSpace tangents for indexed triangles, setting them with `Vertex::set_tangent`.
+///
+/// A vertex shared by triangles which need different tangents is split, the first tangent stays with
+/// the original vertex and copies are appended for the others. Returns `false` and leaves the mesh
+/// untouched if tangents could not be generated, or if an index is out of range.
+pub fn generate_tangents<V>(vertices: &mut Vec<V>, indices: &mut [u32]) -> bool
+where
+    V: Vertex,
+{
+    if indices.iter().any(|&i| i as usize >= vertices.len()) {
+        return false;
+    }
+
+    let mut geometry = TangentGeometry {
+        vertices,
+        indices,
+        tangents: vec![[0.0; 4]; indices.len() - indices.len() % 3],
+    };
+    if !mikktspace::generate_tangents(&mut geometry) {
+        return false;
+    }
+    let tangents = geometry.tangents;
+
+    let mut assigned: Vec<Option<[f32; 4]>> = vec![None; vertices.len()];
+    let mut splits: HashMap<(u32, [u32; 4]), u32> = HashMap::new();
+    for (index, tangent) in indices.iter_mut().zip(tangents) {
+        let i = *index as usize;
+        match assigned[i] {
+            None => {
+                assigned[i] = Some(tangent);
+                vertices[i].set_tangent(tangent);
+            }
+            Some(t) if t == tangent => {}
+            Some(_) => {
+                let key = (*index, tangent.map(f32::to_bits));
+                *index = *splits.entry(key).or_insert_with(|| {
+                    let mut vertex = vertices[i];
+                    vertex.set_tangent(tangent);
+                    vertices.push(vertex);
+                    vertices.len() as u32 - 1
+                });
+            }
+        }
+    }
+
+    true
+}
@@ -2,6 +2,9 @@ use super::Display;
 use anyhow::Result;
 use image::EncodableLayout;
 
+/// Format of depth textures, and of the depth state set by `RenderPipelineBuilder`.
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
 pub struct Image {
     pub data: Vec<u8>,
     pub size: wgpu::Extent3d,
@@ -12,19 +15,28 @@ impl Image {
     pub fn load_from_memory(src: &[u8]) -> Result<Image> {
         let image = image::load_from_memory(src)?.to_rgba8();
 
-        Ok(Image {
-            data: image.as_bytes().to_vec(),
+        Ok(Image::from_rgba8(
+            image.width(),
+            image.height(),
+            image.as_bytes().to_vec(),
+        ))
+    }
+
+    /// Wraps already decoded, tightly packed RGBA8 pixel data.
+    pub fn from_rgba8(width: u32, height: u32, data: Vec<u8>) -> Image {
+        Image {
+            data,
             size: wgpu::Extent3d {
-                width: image.width(),
-                height: image.height(),
+                width,
+                height,
                 depth_or_array_layers: 1,
             },
             layout: wgpu::ImageDataLayout {
                 offset: 0,
-                bytes_per_row: std::num::NonZeroU32::new(image.width() * 4),
-                rows_per_image: std::num::NonZeroU32::new(image.height()),
+                bytes_per_row: std::num::NonZeroU32::new(width * 4),
+                rows_per_image: std::num::NonZeroU32::new(height),
             },
-        })
+        }
     }
 }
 
@@ -33,22 +45,81 @@ pub struct Texture {
     pub texture: wgpu::Texture,
     pub view: wgpu::TextureView,
     pub sampler: wgpu::Sampler,
+    pub size: wgpu::Extent3d,
+    pub format: wgpu::TextureFormat,
 }
 
 impl Texture {
+    /// Loads an image from memory into an sRGB texture, suitable for colour data.
     pub fn new_from_bytes(
         dpy: &Display,
         src: &[u8],
         label: Option<&'static str>,
     ) -> Result<Texture> {
         let image = Image::load_from_memory(src)?;
+        Ok(Texture::new_from_image(
+            dpy,
+            &image,
+            wgpu::TextureFormat::Rgba8UnormSrgb,
+            label,
+        ))
+    }
+
+    /// Loads an image from memory into a linear texture, suitable for non-colour data such as normal maps.
+    pub fn new_linear_from_bytes(
+        dpy: &Display,
+        src: &[u8],
+        label: Option<&'static str>,
+    ) -> Result<Texture> {
+        let image = Image::load_from_memory(src)?;
+        Ok(Texture::new_from_image(
+            dpy,
+            &image,
+            wgpu::TextureFormat::Rgba8Unorm,
+            label,
+        ))
+    }
+
+    /// Uploads a decoded image into a new texture of the given format, sampled with linear
+    /// filtering and clamped to its edges.
+    pub fn new_from_image(
+        dpy: &Display,
+        image: &Image,
+        format: wgpu::TextureFormat,
+        label: Option<&'static str>,
+    ) -> Texture {
+        Texture::new_from_image_with_sampler(
+            dpy,
+            image,
+            format,
+            &wgpu::SamplerDescriptor {
+                address_mode_u: wgpu::AddressMode::ClampToEdge,
+                address_mode_v: wgpu::AddressMode::ClampToEdge,
+                address_mode_w: wgpu::AddressMode::ClampToEdge,
+                mag_filter: wgpu::FilterMode::Linear,
+                min_filter: wgpu::FilterMode::Linear,
+                mipmap_filter: wgpu::FilterMode::Nearest,
+                ..Default::default()
+            },
+            label,
+        )
+    }
+
+    /// Uploads a decoded image into a new texture of the given format, sampled with `sampler`.
+    pub fn new_from_image_with_sampler(
+        dpy: &Display,
+        image: &Image,
+        format: wgpu::TextureFormat,
+        sampler: &wgpu::SamplerDescriptor,
+        label: Option<&'static str>,
+    ) -> Texture {
         let texture = dpy.device.create_texture(&wgpu::TextureDescriptor {
-            label: label,
+            label,
             size: image.size,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            format,
             usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::COPY_DST,
         });
 
@@ -64,7 +135,46 @@ impl Texture {
         );
 
         let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
-        let sampler = dpy.device.create_sampler(&wgpu::SamplerDescriptor {
+        let sampler = dpy.device.create_sampler(sampler);
+
+        Texture {
+            texture,
+            view,
+            sampler,
+            size: image.size,
+            format,
+        }
+    }
+
+    /// Creates a texture which can be used as a colour attachment and copied out of.
+    ///
+    /// Used as the offscreen target of headless displays and of displays built with `with_capture`.
+    pub fn new_render_target(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: Option<&'static str>,
+    ) -> Texture {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT
+                | wgpu::TextureUsage::SAMPLED
+                | wgpu::TextureUsage::COPY_SRC,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
@@ -74,30 +184,131 @@ impl Texture {
             ..Default::default()
         });
 
-        Ok(Texture {
+        Texture {
             texture,
             view,
             sampler,
-        })
+            size,
+            format,
+        }
     }
 
-    // TODO: Make sampler usable
-    pub fn new_depth_texture(dpy: &Display) -> Texture {
-        let texture = dpy.device.create_texture(&wgpu::TextureDescriptor {
-            label: None,
-            size: wgpu::Extent3d {
-                width: dpy.sc_desc.width,
-                height: dpy.sc_desc.height,
-                depth_or_array_layers: 1,
-            },
+    /// Creates a texture that compute shaders can write to, and that can be sampled or copied out.
+    pub fn new_storage_texture(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        label: Option<&'static str>,
+    ) -> Texture {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
             mip_level_count: 1,
             sample_count: 1,
             dimension: wgpu::TextureDimension::D2,
-            format: wgpu::TextureFormat::Depth32Float,
+            format,
+            usage: wgpu::TextureUsage::STORAGE
+                | wgpu::TextureUsage::SAMPLED
+                | wgpu::TextureUsage::COPY_SRC,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            address_mode_u: wgpu::AddressMode::ClampToEdge,
+            address_mode_v: wgpu::AddressMode::ClampToEdge,
+            address_mode_w: wgpu::AddressMode::ClampToEdge,
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            mipmap_filter: wgpu::FilterMode::Nearest,
+            ..Default::default()
+        });
+
+        Texture {
+            texture,
+            view,
+            sampler,
+            size,
+            format,
+        }
+    }
+
+    /// Creates a multisampled colour target, to be resolved into a single sampled texture.
+    pub fn new_multisampled(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: Option<&'static str>,
+    ) -> Texture {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsage::RENDER_ATTACHMENT,
+        });
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor::default());
+
+        Texture {
+            texture,
+            view,
+            sampler,
+            size,
+            format,
+        }
+    }
+
+    /// Creates a depth texture matching the size and sample count of the display's render target.
+    pub fn new_depth_texture(dpy: &Display) -> Texture {
+        Texture::new_depth(
+            &dpy.device,
+            dpy.sc_desc.width,
+            dpy.sc_desc.height,
+            dpy.sample_count,
+            None,
+        )
+    }
+
+    // TODO: Make sampler usable
+    pub fn new_depth(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        sample_count: u32,
+        label: Option<&'static str>,
+    ) -> Texture {
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label,
+            size,
+            mip_level_count: 1,
+            sample_count,
+            dimension: wgpu::TextureDimension::D2,
+            format: DEPTH_FORMAT,
             usage: wgpu::TextureUsage::SAMPLED | wgpu::TextureUsage::RENDER_ATTACHMENT,
         });
 
-        let sampler = dpy.device.create_sampler(&wgpu::SamplerDescriptor {
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
             address_mode_u: wgpu::AddressMode::ClampToEdge,
             address_mode_v: wgpu::AddressMode::ClampToEdge,
             address_mode_w: wgpu::AddressMode::ClampToEdge,
@@ -115,6 +326,55 @@ impl Texture {
             texture,
             sampler,
             view,
+            size,
+            format: DEPTH_FORMAT,
+        }
+    }
+}
+
+/// A multisampled colour target along with the single sampled texture it resolves into, for
+/// anti-aliased rendering into a texture rather than a display.
+///
+/// Pipelines drawing into it are built with `RenderPipelineBuilder::build_for_target`, and render
+/// passes use `color_attachment` along with a depth texture created by `Texture::new_depth` with
+/// the same sample count.
+#[derive(Debug)]
+pub struct MultisampledTarget {
+    pub msaa: Texture,
+    /// Resolved image, which can be sampled or read back with `Texture::to_image`.
+    pub resolve: Texture,
+    pub sample_count: u32,
+}
+
+impl MultisampledTarget {
+    pub fn new(
+        device: &wgpu::Device,
+        width: u32,
+        height: u32,
+        format: wgpu::TextureFormat,
+        sample_count: u32,
+        label: Option<&'static str>,
+    ) -> MultisampledTarget {
+        MultisampledTarget {
+            msaa: Texture::new_multisampled(device, width, height, format, sample_count, label),
+            resolve: Texture::new_render_target(device, width, height, format, label),
+            sample_count,
+        }
+    }
+
+    pub fn format(&self) -> wgpu::TextureFormat {
+        self.resolve.format
+    }
+
+    /// Colour attachment rendering into `msaa` and resolving into `resolve`.
+    pub fn color_attachment(
+        &self,
+        ops: wgpu::Operations<wgpu::Color>,
+    ) -> wgpu::RenderPassColorAttachment<'_> {
+        wgpu::RenderPassColorAttachment {
+            view: &self.msaa.view,
+            resolve_target: Some(&self.resolve.view),
+            ops,
         }
     }
 }
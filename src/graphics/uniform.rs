@@ -0,0 +1,217 @@
+use std::marker::PhantomData;
+
+use bytemuck::Pod;
+use nalgebra::{Matrix2, Matrix4, Point2, Point3, Vector2, Vector3, Vector4};
+
+pub use magneto_derive::UniformLayout;
+
+use super::{DeviceUtilExt, Display};
+
+/// A type with a known layout in WGSL uniform buffers.
+///
+/// Implemented for `f32`, `u32`, `i32`, vectors of them as arrays or nalgebra types, `mat2x2`
+/// and `mat4x4` as nalgebra matrices or arrays of columns, arrays of 4 component vectors and
+/// arrays of structs deriving `UniformLayout`. `mat3x3` has 16 byte columns in WGSL, so it is
+/// passed as `[[f32; 4]; 3]`. Arrays of scalars or smaller vectors have a 16 byte stride in
+/// uniform buffers and are not implemented.
+///
+/// Derive it for uniform structs, which checks their padding at compile time:
+///
+/// ```
+/// use bytemuck::{Pod, Zeroable};
+/// use magneto::graphics::UniformLayout;
+///
+/// #[repr(C)]
+/// #[derive(Copy, Clone, Pod, Zeroable, UniformLayout)]
+/// struct Light {
+///     position: [f32; 3],
+///     intensity: f32,
+///     colour: [f32; 3],
+///     #[uniform(skip)]
+///     _padding: u32,
+/// }
+/// ```
+///
+/// A `vec3<f32>` is aligned to 16 bytes, so forgetting the padding before it fails to compile:
+///
+/// ```compile_fail
+/// # use bytemuck::{Pod, Zeroable};
+/// # use magneto::graphics::UniformLayout;
+/// #[repr(C)]
+/// #[derive(Copy, Clone, Pod, Zeroable, UniformLayout)]
+/// struct Light {
+///     position: [f32; 3],
+///     colour: [f32; 3],
+/// }
+/// ```
+pub trait UniformLayout {
+    /// Alignment in uniform buffers, in bytes.
+    const ALIGN: usize;
+
+    /// Size in uniform buffers, in bytes. Members after this type start at their own alignment
+    /// past this size.
+    const SIZE: usize;
+}
+
+macro_rules! impl_uniform_layout {
+    ($($ty:ty => ($align:literal, $size:literal),)*) => {
+        $(
+            impl UniformLayout for $ty {
+                const ALIGN: usize = $align;
+                const SIZE: usize = $size;
+            }
+        )*
+    };
+}
+
+impl_uniform_layout! {
+    f32 => (4, 4),
+    u32 => (4, 4),
+    i32 => (4, 4),
+    [f32; 2] => (8, 8),
+    [u32; 2] => (8, 8),
+    [i32; 2] => (8, 8),
+    [f32; 3] => (16, 12),
+    [u32; 3] => (16, 12),
+    [i32; 3] => (16, 12),
+    [f32; 4] => (16, 16),
+    [u32; 4] => (16, 16),
+    [i32; 4] => (16, 16),
+    [[f32; 2]; 2] => (8, 16),
+    Vector2<f32> => (8, 8),
+    Vector2<u32> => (8, 8),
+    Vector2<i32> => (8, 8),
+    Vector3<f32> => (16, 12),
+    Vector3<u32> => (16, 12),
+    Vector3<i32> => (16, 12),
+    Vector4<f32> => (16, 16),
+    Vector4<u32> => (16, 16),
+    Vector4<i32> => (16, 16),
+    Point2<f32> => (8, 8),
+    Point3<f32> => (16, 12),
+    Matrix2<f32> => (8, 16),
+    Matrix4<f32> => (16, 64),
+}
+
+macro_rules! impl_uniform_layout_array {
+    ($($ty:ty,)*) => {
+        $(
+            impl<const N: usize> UniformLayout for [$ty; N] {
+                const ALIGN: usize = 16;
+                const SIZE: usize = 16 * N;
+            }
+        )*
+    };
+}
+
+impl_uniform_layout_array! {
+    [f32; 4],
+    [u32; 4],
+    [i32; 4],
+    Vector4<f32>,
+    Vector4<u32>,
+    Vector4<i32>,
+}
+
+/// A struct deriving `UniformLayout`, which makes arrays of it `UniformLayout` too.
+pub trait UniformStruct: UniformLayout {}
+
+impl<T: UniformStruct, const N: usize> UniformLayout for [T; N] {
+    const ALIGN: usize = 16;
+    const SIZE: usize = T::SIZE * N;
+}
+
+/// A uniform buffer holding a `T`, with a bind group binding it.
+///
+/// The layout of `T` is checked against the WGSL uniform layout rules when the buffer is created,
+/// or when `T` is defined if it derives `UniformLayout`.
+pub struct UniformBuffer<T: Pod + UniformLayout> {
+    value: T,
+    buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+}
+
+impl<T: Pod + UniformLayout> UniformBuffer<T> {
+    /// Creates a layout with a single uniform buffer holding a `T` at binding 0.
+    pub fn create_bind_group_layout(
+        dpy: &Display,
+        visibility: wgpu::ShaderStage,
+    ) -> wgpu::BindGroupLayout {
+        dpy.device
+            .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: None,
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: wgpu::BufferSize::new(LayoutOf::<T>::SIZE as u64),
+                    },
+                    count: None,
+                }],
+            })
+    }
+
+    /// Creates a buffer holding `value` and binds it at binding 0 of `layout`.
+    pub fn new(dpy: &Display, layout: &wgpu::BindGroupLayout, value: T) -> UniformBuffer<T> {
+        let _ = LayoutOf::<T>::SIZE;
+        let buffer = dpy.device.init_uniform_buffer(bytemuck::bytes_of(&value));
+        let bind_group = dpy.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: None,
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: buffer.as_entire_binding(),
+            }],
+        });
+
+        UniformBuffer {
+            value,
+            buffer,
+            bind_group,
+        }
+    }
+
+    pub fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Replaces the value and writes it to the buffer.
+    pub fn set(&mut self, dpy: &Display, value: T) {
+        self.value = value;
+        self.write(dpy);
+    }
+
+    /// Modifies the value with `f` and writes it to the buffer.
+    pub fn update<F: FnOnce(&mut T)>(&mut self, dpy: &Display, f: F) {
+        f(&mut self.value);
+        self.write(dpy);
+    }
+
+    pub fn buffer(&self) -> &wgpu::Buffer {
+        &self.buffer
+    }
+
+    pub fn bind_group(&self) -> &wgpu::BindGroup {
+        &self.bind_group
+    }
+
+    fn write(&self, dpy: &Display) {
+        dpy.queue
+            .write_buffer(&self.buffer, 0, bytemuck::bytes_of(&self.value));
+    }
+}
+
+/// Checks that the Rust layout of `T` is a valid uniform buffer, failing to compile if not.
+struct LayoutOf<T>(PhantomData<T>);
+
+impl<T: Pod + UniformLayout> LayoutOf<T> {
+    const SIZE: usize = {
+        assert!(
+            std::mem::size_of::<T>() % 16 == 0,
+            "Uniform buffers must be a multiple of 16 bytes, add padding at the end"
+        );
+        T::SIZE
+    };
+}
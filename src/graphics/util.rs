@@ -1,17 +1,20 @@
 use std::path::Path;
-use std::fs::File;
-use std::io::Read;
 
 use anyhow::Result;
 
+use super::{PreprocessedShader, Shader, ShaderDefines};
+
 use wgpu::util::DeviceExt;
 
 pub trait DeviceUtilExt {
     fn init_vertex_buffer(&self, data: &[u8]) -> wgpu::Buffer;
     fn init_index_buffer(&self, data: &[u32]) -> wgpu::Buffer;
     fn init_uniform_buffer(&self, data: &[u8]) -> wgpu::Buffer;
+    fn init_storage_buffer(&self, data: &[u8]) -> wgpu::Buffer;
     fn shader_from_memory(&self, src: &str, name: Option<&str>) -> wgpu::ShaderModule;
     fn shader_from_file<P: AsRef<Path>>(&self, path: P) -> Result<wgpu::ShaderModule>;
+    fn reflect_shader_from_memory(&self, src: &str, label: Option<&str>) -> Result<Shader>;
+    fn reflect_shader_from_file<P: AsRef<Path>>(&self, path: P) -> Result<Shader>;
 }
 
 impl DeviceUtilExt for wgpu::Device {
@@ -38,12 +41,30 @@ impl DeviceUtilExt for wgpu::Device {
     }
 
     /// Utility function to create a writable uniform buffer with the given data.
+    ///
+    /// The buffer can be copied back out for readback.
     fn init_uniform_buffer(&self, data: &[u8]) -> wgpu::Buffer {
         self
             .create_buffer_init(&wgpu::util::BufferInitDescriptor {
                 label: None,
                 contents: data,
-                usage: wgpu::BufferUsage::UNIFORM | wgpu::BufferUsage::COPY_DST,
+                usage: wgpu::BufferUsage::UNIFORM
+                    | wgpu::BufferUsage::COPY_DST
+                    | wgpu::BufferUsage::COPY_SRC,
+            })
+    }
+
+    /// Utility function to create a storage buffer with the given data.
+    ///
+    /// The buffer can be written from the CPU and copied back out for readback.
+    fn init_storage_buffer(&self, data: &[u8]) -> wgpu::Buffer {
+        self
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: None,
+                contents: data,
+                usage: wgpu::BufferUsage::STORAGE
+                    | wgpu::BufferUsage::COPY_DST
+                    | wgpu::BufferUsage::COPY_SRC,
             })
     }
 
@@ -58,13 +79,26 @@ impl DeviceUtilExt for wgpu::Device {
     }
 
     /// Utility function to create a shader module with the given WGSL source file.
+    ///
+    /// The file is preprocessed first, see `PreprocessedShader`. To reload the shader when the
+    /// file changes, load it with `ShaderReloader::shader_from_file` instead.
     fn shader_from_file<P: AsRef<Path>>(&self, path: P) -> Result<wgpu::ShaderModule> {
         // Get file stem for shader label
-        let shader_label = path.as_ref().file_stem().map(|s| s.to_str() ).flatten();
-        let mut shader_file = File::open(path.as_ref())?;
-        let mut shader_source = String::new();
-        shader_file.read_to_string(&mut shader_source)?;
+        let shader_label = path.as_ref().file_stem().and_then(|s| s.to_str());
+        let shader = PreprocessedShader::from_file(path.as_ref(), &ShaderDefines::new())?;
+
+        Ok(self.shader_from_memory(&shader.source, shader_label))
+    }
+
+    /// Creates a shader module from a WGSL source string, along with its reflection and the bind
+    /// group and pipeline layouts it declares.
+    fn reflect_shader_from_memory(&self, src: &str, label: Option<&str>) -> Result<Shader> {
+        Shader::from_wgsl(self, src, label)
+    }
 
-        Ok(self.shader_from_memory(&shader_source, shader_label))
+    /// Creates a shader module from a WGSL source file, along with its reflection and the bind
+    /// group and pipeline layouts it declares.
+    fn reflect_shader_from_file<P: AsRef<Path>>(&self, path: P) -> Result<Shader> {
+        Shader::from_file(self, path)
     }
 }
\ No newline at end of file
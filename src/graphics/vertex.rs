@@ -6,13 +6,83 @@ use bytemuck::{Pod, Zeroable};
 /// Must also implement the `HasLayout` trait (for layout of values)
 /// as well as the `Pod` and `Zeroable` traits (for converison of data into bytes).
 pub trait Vertex: HasLayout + Pod + Zeroable {
+    /// Whether this vertex stores a tangent.
+    ///
+    /// If so, model loaders and mesh generators compute MikkTSpace tangents and build vertices with `with_tangent`.
+    const HAS_TANGENT: bool = false;
+
     /// Create `Self` with the provided features. Note that you do not have to use all of this data.
     fn with_features(position: [f32; 3], normal: [f32; 3], texture_coord: [f32; 2]) -> Self;
+
+    /// Create `Self` with the provided features and a tangent, whose `w` component is the bitangent sign.
+    ///
+    /// By default the tangent is discarded.
+    fn with_tangent(
+        position: [f32; 3],
+        normal: [f32; 3],
+        texture_coord: [f32; 2],
+        _tangent: [f32; 4],
+    ) -> Self {
+        Self::with_features(position, normal, texture_coord)
+    }
+
+    /// Relative position of the vertex, used for bounds, tangent generation and mesh transforms.
+    fn position(&self) -> [f32; 3];
+
+    /// Direction of the normal. Vertices without a normal return zero.
+    fn normal(&self) -> [f32; 3] {
+        [0.0; 3]
+    }
+
+    /// Texture coordinate. Vertices without a texture coordinate return zero.
+    fn texture_coord(&self) -> [f32; 2] {
+        [0.0; 2]
+    }
+
+    /// Tangent, with the bitangent sign in `w`. Vertices without a tangent return zero.
+    fn tangent(&self) -> [f32; 4] {
+        [0.0; 4]
+    }
+
+    /// Replaces the position, used by `MeshData::transform`.
+    ///
+    /// Like the other setters, by default the vertex is rebuilt with `with_tangent`, which resets
+    /// any attribute other than the position, normal, texture coordinate and tangent. Vertices with
+    /// other attributes, such as a colour, should override the setters to keep them.
+    fn set_position(&mut self, position: [f32; 3]) {
+        *self = Self::with_tangent(
+            position,
+            self.normal(),
+            self.texture_coord(),
+            self.tangent(),
+        );
+    }
+
+    /// Replaces the normal, used by `MeshData::transform`. See `set_position`.
+    fn set_normal(&mut self, normal: [f32; 3]) {
+        *self = Self::with_tangent(
+            self.position(),
+            normal,
+            self.texture_coord(),
+            self.tangent(),
+        );
+    }
+
+    /// Replaces the tangent, used by `generate_tangents` and `MeshData::transform`. See
+    /// `set_position`.
+    fn set_tangent(&mut self, tangent: [f32; 4]) {
+        *self = Self::with_tangent(
+            self.position(),
+            self.normal(),
+            self.texture_coord(),
+            tangent,
+        );
+    }
 }
 
 /// Basic Vertex Representation.
 #[repr(C)]
-#[derive(Copy, Clone, Debug, Pod, Zeroable)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, HasLayout)]
 pub struct BasicVertex {
     /// Relative position.
     pub position: [f32; 3],
@@ -22,18 +92,103 @@ pub struct BasicVertex {
     pub texture_coord: [f32; 2],
 }
 
-impl HasLayout for BasicVertex {
-    fn layout(shader_offset: u32) -> Vec<wgpu::VertexAttribute> {
-        wgpu::vertex_attr_array![shader_offset=>Float32x3,shader_offset+1=>Float32x3,shader_offset+2=>Float32x2].to_vec()
+impl Vertex for BasicVertex {
+    fn with_features(position: [f32; 3], normal: [f32; 3], texture_coord: [f32; 2]) -> Self {
+        BasicVertex {
+            position,
+            normal,
+            texture_coord,
+        }
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    fn normal(&self) -> [f32; 3] {
+        self.normal
+    }
+
+    fn texture_coord(&self) -> [f32; 2] {
+        self.texture_coord
+    }
+
+    fn set_position(&mut self, position: [f32; 3]) {
+        self.position = position;
+    }
+
+    fn set_normal(&mut self, normal: [f32; 3]) {
+        self.normal = normal;
     }
+
+    fn set_tangent(&mut self, _tangent: [f32; 4]) {}
 }
 
-impl Vertex for BasicVertex {
+/// Vertex Representation with a tangent, for normal mapping.
+#[repr(C)]
+#[derive(Copy, Clone, Debug, Pod, Zeroable, HasLayout)]
+pub struct TangentVertex {
+    /// Relative position.
+    pub position: [f32; 3],
+    /// Direction of normal.
+    pub normal: [f32; 3],
+    /// Texture coordinate.
+    pub texture_coord: [f32; 2],
+    /// Direction of tangent, with the bitangent sign in `w`.
+    pub tangent: [f32; 4],
+}
+
+impl Vertex for TangentVertex {
+    const HAS_TANGENT: bool = true;
+
     fn with_features(position: [f32; 3], normal: [f32; 3], texture_coord: [f32; 2]) -> Self {
-        BasicVertex {
+        TangentVertex {
+            position,
+            normal,
+            texture_coord,
+            tangent: [0.0; 4],
+        }
+    }
+
+    fn with_tangent(
+        position: [f32; 3],
+        normal: [f32; 3],
+        texture_coord: [f32; 2],
+        tangent: [f32; 4],
+    ) -> Self {
+        TangentVertex {
             position,
             normal,
             texture_coord,
+            tangent,
         }
     }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    fn normal(&self) -> [f32; 3] {
+        self.normal
+    }
+
+    fn texture_coord(&self) -> [f32; 2] {
+        self.texture_coord
+    }
+
+    fn tangent(&self) -> [f32; 4] {
+        self.tangent
+    }
+
+    fn set_position(&mut self, position: [f32; 3]) {
+        self.position = position;
+    }
+
+    fn set_normal(&mut self, normal: [f32; 3]) {
+        self.normal = normal;
+    }
+
+    fn set_tangent(&mut self, tangent: [f32; 4]) {
+        self.tangent = tangent;
+    }
 }
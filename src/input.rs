@@ -0,0 +1,399 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt::Debug;
+use std::path::Path;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use winit::event::{MouseButton, VirtualKeyCode as Keycode};
+
+pub mod state;
+pub use state::*;
+
+/// Pixels of scrolling treated as one line, for devices that report pixel deltas.
+pub const PIXELS_PER_LINE: f32 = 20.0;
+
+// Names of the actions and axes read by the camera controllers, bound by `ActionMap::default`.
+
+/// Axis moving forward (positive) and back.
+pub const MOVE_FORWARD: &str = "move_forward";
+/// Axis moving right (positive) and left.
+pub const MOVE_RIGHT: &str = "move_right";
+/// Axis moving up (positive) and down.
+pub const MOVE_UP: &str = "move_up";
+/// Axis rolling clockwise (positive) and anticlockwise.
+pub const ROLL: &str = "roll";
+/// Axis turning right (positive) and left.
+pub const LOOK_X: &str = "look_x";
+/// Axis looking up (positive) and down.
+pub const LOOK_Y: &str = "look_y";
+/// Axis zooming in (positive) and out.
+pub const ZOOM: &str = "zoom";
+/// Action multiplying movement speed while held.
+pub const SPRINT: &str = "sprint";
+
+/// A gamepad button, named by its position on the pad.
+///
+/// Gamepads are not read by this crate, an `InputSource` reports their state from whichever
+/// library the application uses.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamepadButton {
+    South,
+    East,
+    North,
+    West,
+    LeftBumper,
+    RightBumper,
+    LeftStick,
+    RightStick,
+    Select,
+    Start,
+    DPadUp,
+    DPadDown,
+    DPadLeft,
+    DPadRight,
+}
+
+/// An analog gamepad input. Sticks range from -1 to 1 with up and right positive, triggers from
+/// 0 to 1.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GamepadAxis {
+    LeftStickX,
+    LeftStickY,
+    RightStickX,
+    RightStickY,
+    LeftTrigger,
+    RightTrigger,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MouseAxis {
+    X,
+    Y,
+}
+
+/// A digital input that can trigger an action.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Binding {
+    Key(Keycode),
+    MouseButton(MouseButton),
+    GamepadButton(GamepadButton),
+}
+
+/// Where the value of an axis binding comes from.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AxisSource {
+    /// -1 while `negative` is held, 1 while `positive` is held, 0 for both or neither.
+    Buttons {
+        negative: Binding,
+        positive: Binding,
+    },
+    /// Mouse motion this frame, in pixels.
+    MouseMotion(MouseAxis),
+    /// Scroll wheel motion this frame, in lines.
+    MouseWheel(MouseAxis),
+    GamepadAxis(GamepadAxis),
+}
+
+fn one() -> f32 {
+    1.0
+}
+
+/// An input bound to an axis, with a multiplier applied to its value.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AxisBinding {
+    #[serde(flatten)]
+    pub source: AxisSource,
+    #[serde(default = "one")]
+    pub scale: f32,
+}
+
+impl AxisBinding {
+    pub fn new(source: AxisSource) -> AxisBinding {
+        AxisBinding { source, scale: 1.0 }
+    }
+
+    pub fn keys(negative: Keycode, positive: Keycode) -> AxisBinding {
+        AxisBinding::new(AxisSource::Buttons {
+            negative: Binding::Key(negative),
+            positive: Binding::Key(positive),
+        })
+    }
+
+    pub fn gamepad(axis: GamepadAxis) -> AxisBinding {
+        AxisBinding::new(AxisSource::GamepadAxis(axis))
+    }
+
+    pub fn with_scale(mut self, scale: f32) -> AxisBinding {
+        self.scale = scale;
+        self
+    }
+}
+
+/// Current state of the input devices, as read by an `ActionMap`.
+///
+/// Analog inputs default to zero, so a source only needs to report the devices it tracks.
+pub trait InputSource {
+    fn is_down(&self, binding: &Binding) -> bool;
+
+    /// Mouse motion since the last frame, in pixels.
+    fn mouse_motion(&self) -> (f32, f32) {
+        (0.0, 0.0)
+    }
+
+    /// Scroll wheel motion since the last frame, in lines.
+    fn mouse_wheel(&self) -> (f32, f32) {
+        (0.0, 0.0)
+    }
+
+    fn gamepad_axis(&self, _axis: GamepadAxis) -> f32 {
+        0.0
+    }
+}
+
+/// A set of held keys is the simplest input source, reporting only the keyboard.
+impl InputSource for HashSet<Keycode> {
+    fn is_down(&self, binding: &Binding) -> bool {
+        match binding {
+            Binding::Key(key) => self.contains(key),
+            _ => false,
+        }
+    }
+}
+
+/// State of every action for one frame, produced by `ActionMap::evaluate`.
+#[derive(Clone, Debug, Default)]
+pub struct Actions {
+    pressed: HashSet<String>,
+    axes: HashMap<String, f32>,
+}
+
+impl Actions {
+    /// Returns true if any input bound to the action `name` is held.
+    pub fn pressed(&self, name: &str) -> bool {
+        self.pressed.contains(name)
+    }
+
+    /// Value of the axis `name`, or 0 if it is not bound.
+    pub fn axis(&self, name: &str) -> f32 {
+        self.axes.get(name).copied().unwrap_or(0.0)
+    }
+}
+
+fn default_dead_zone() -> f32 {
+    0.15
+}
+
+/// Maps named actions and axes to the inputs that drive them.
+///
+/// Maps can be saved to and loaded from TOML, for example:
+///
+/// ```toml
+/// [actions]
+/// sprint = [{ key = "LShift" }, { gamepad_button = "left_stick" }]
+///
+/// [axes]
+/// move_forward = [
+///     { buttons = { negative = { key = "S" }, positive = { key = "W" } } },
+///     { gamepad_axis = "left_stick_y" },
+/// ]
+/// zoom = [{ mouse_wheel = "y", scale = 0.5 }]
+/// ```
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ActionMap {
+    #[serde(default)]
+    actions: BTreeMap<String, Vec<Binding>>,
+    #[serde(default)]
+    axes: BTreeMap<String, Vec<AxisBinding>>,
+    /// Gamepad axis values smaller than this are treated as 0. 0.15 if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub gamepad_dead_zone: Option<f32>,
+}
+
+impl ActionMap {
+    /// Creates a map with nothing bound.
+    pub fn new() -> ActionMap {
+        ActionMap {
+            actions: BTreeMap::new(),
+            axes: BTreeMap::new(),
+            gamepad_dead_zone: None,
+        }
+    }
+
+    /// Adds `binding` to the action `name`.
+    pub fn bind_action(&mut self, name: &str, binding: Binding) -> &mut Self {
+        self.actions
+            .entry(name.to_string())
+            .or_default()
+            .push(binding);
+        self
+    }
+
+    /// Adds `binding` to the axis `name`.
+    pub fn bind_axis(&mut self, name: &str, binding: AxisBinding) -> &mut Self {
+        self.axes.entry(name.to_string()).or_default().push(binding);
+        self
+    }
+
+    /// Replaces every binding of the action `name`.
+    pub fn rebind_action(&mut self, name: &str, bindings: Vec<Binding>) {
+        self.actions.insert(name.to_string(), bindings);
+    }
+
+    /// Replaces every binding of the axis `name`.
+    pub fn rebind_axis(&mut self, name: &str, bindings: Vec<AxisBinding>) {
+        self.axes.insert(name.to_string(), bindings);
+    }
+
+    /// Removes the action or axis `name`.
+    pub fn unbind(&mut self, name: &str) {
+        self.actions.remove(name);
+        self.axes.remove(name);
+    }
+
+    pub fn action_bindings(&self, name: &str) -> &[Binding] {
+        self.actions
+            .get(name)
+            .map(Vec::as_slice)
+            .unwrap_or_default()
+    }
+
+    pub fn axis_bindings(&self, name: &str) -> &[AxisBinding] {
+        self.axes.get(name).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Replaces the bindings of every action and axis named in `other`, keeping the rest. The
+    /// dead zone is only replaced if `other` sets one.
+    ///
+    /// Useful for applying a user's config on top of the defaults.
+    pub fn merge(&mut self, other: ActionMap) {
+        self.actions.extend(other.actions);
+        self.axes.extend(other.axes);
+        if other.gamepad_dead_zone.is_some() {
+            self.gamepad_dead_zone = other.gamepad_dead_zone;
+        }
+    }
+
+    pub fn from_toml(source: &str) -> Result<ActionMap> {
+        Ok(toml::from_str(source)?)
+    }
+
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string_pretty(self)?)
+    }
+
+    /// Loads a map from a TOML file.
+    pub fn load<P>(path: P) -> Result<ActionMap>
+    where
+        P: AsRef<Path> + Debug,
+    {
+        let source = std::fs::read_to_string(path.as_ref())
+            .with_context(|| format!("Unable to read input config {:?}", path))?;
+        ActionMap::from_toml(&source)
+            .with_context(|| format!("Unable to parse input config {:?}", path))
+    }
+
+    /// Saves the map to a TOML file.
+    pub fn save<P>(&self, path: P) -> Result<()>
+    where
+        P: AsRef<Path> + Debug,
+    {
+        std::fs::write(path.as_ref(), self.to_toml()?)
+            .with_context(|| format!("Unable to write input config {:?}", path))
+    }
+
+    /// Reads the state of every action and axis from `input`.
+    ///
+    /// Buttons and gamepad axes bound to the same axis are summed and clamped to -1..1, so binding
+    /// two keys does not double the speed. Mouse motion and wheel values are added unclamped.
+    pub fn evaluate(&self, input: &dyn InputSource) -> Actions {
+        let dead_zone = self.gamepad_dead_zone.unwrap_or_else(default_dead_zone);
+        let pressed = self
+            .actions
+            .iter()
+            .filter(|(_, bindings)| bindings.iter().any(|b| input.is_down(b)))
+            .map(|(name, _)| name.clone())
+            .collect();
+
+        let axes = self
+            .axes
+            .iter()
+            .map(|(name, bindings)| {
+                let mut digital = 0.0;
+                let mut relative = 0.0;
+                for binding in bindings {
+                    match binding.source {
+                        AxisSource::Buttons { negative, positive } => {
+                            let value = input.is_down(&positive) as i32 as f32
+                                - input.is_down(&negative) as i32 as f32;
+                            digital += value * binding.scale;
+                        }
+                        AxisSource::GamepadAxis(axis) => {
+                            let value = input.gamepad_axis(axis);
+                            if value.abs() >= dead_zone {
+                                digital += value * binding.scale;
+                            }
+                        }
+                        AxisSource::MouseMotion(axis) => {
+                            relative += select(input.mouse_motion(), axis) * binding.scale;
+                        }
+                        AxisSource::MouseWheel(axis) => {
+                            relative += select(input.mouse_wheel(), axis) * binding.scale;
+                        }
+                    }
+                }
+                (name.clone(), digital.clamp(-1.0, 1.0) + relative)
+            })
+            .collect();
+
+        Actions { pressed, axes }
+    }
+}
+
+fn select(value: (f32, f32), axis: MouseAxis) -> f32 {
+    match axis {
+        MouseAxis::X => value.0,
+        MouseAxis::Y => value.1,
+    }
+}
+
+/// The bindings used by the camera controllers: WASD to move, space and left control for up
+/// and down, Q and E to roll, left shift to sprint, and the arrow keys or right stick to look.
+impl Default for ActionMap {
+    fn default() -> Self {
+        let mut map = ActionMap::new();
+        map.bind_axis(MOVE_FORWARD, AxisBinding::keys(Keycode::S, Keycode::W))
+            .bind_axis(MOVE_FORWARD, AxisBinding::gamepad(GamepadAxis::LeftStickY))
+            .bind_axis(MOVE_RIGHT, AxisBinding::keys(Keycode::A, Keycode::D))
+            .bind_axis(MOVE_RIGHT, AxisBinding::gamepad(GamepadAxis::LeftStickX))
+            .bind_axis(
+                MOVE_UP,
+                AxisBinding::keys(Keycode::LControl, Keycode::Space),
+            )
+            .bind_axis(MOVE_UP, AxisBinding::gamepad(GamepadAxis::RightTrigger))
+            .bind_axis(
+                MOVE_UP,
+                AxisBinding::gamepad(GamepadAxis::LeftTrigger).with_scale(-1.0),
+            )
+            .bind_axis(ROLL, AxisBinding::keys(Keycode::Q, Keycode::E))
+            .bind_axis(
+                ROLL,
+                AxisBinding::new(AxisSource::Buttons {
+                    negative: Binding::GamepadButton(GamepadButton::LeftBumper),
+                    positive: Binding::GamepadButton(GamepadButton::RightBumper),
+                }),
+            )
+            .bind_axis(LOOK_X, AxisBinding::keys(Keycode::Left, Keycode::Right))
+            .bind_axis(LOOK_X, AxisBinding::gamepad(GamepadAxis::RightStickX))
+            .bind_axis(LOOK_Y, AxisBinding::keys(Keycode::Down, Keycode::Up))
+            .bind_axis(LOOK_Y, AxisBinding::gamepad(GamepadAxis::RightStickY))
+            .bind_axis(ZOOM, AxisBinding::keys(Keycode::PageDown, Keycode::PageUp))
+            .bind_action(SPRINT, Binding::Key(Keycode::LShift))
+            .bind_action(SPRINT, Binding::GamepadButton(GamepadButton::LeftStick));
+        map
+    }
+}
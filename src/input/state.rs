@@ -0,0 +1,269 @@
+use std::collections::{HashMap, HashSet};
+use std::hash::Hash;
+
+use winit::dpi::PhysicalPosition;
+use winit::event::{
+    DeviceEvent, ElementState, Event, ModifiersState, MouseButton, MouseScrollDelta,
+    VirtualKeyCode as Keycode, WindowEvent,
+};
+
+use super::{Binding, GamepadAxis, GamepadButton, InputSource, PIXELS_PER_LINE};
+
+/// Held, just pressed and just released state of a set of buttons.
+#[derive(Clone, Debug)]
+struct ButtonState<T> {
+    down: HashSet<T>,
+    pressed: HashSet<T>,
+    released: HashSet<T>,
+}
+
+impl<T> Default for ButtonState<T> {
+    fn default() -> Self {
+        ButtonState {
+            down: HashSet::new(),
+            pressed: HashSet::new(),
+            released: HashSet::new(),
+        }
+    }
+}
+
+impl<T> ButtonState<T>
+where
+    T: Copy + Eq + Hash,
+{
+    fn set(&mut self, button: T, state: ElementState) {
+        match state {
+            // Key repeat sends more presses while held, which are not new presses
+            ElementState::Pressed => {
+                if self.down.insert(button) {
+                    self.pressed.insert(button);
+                }
+            }
+            ElementState::Released => {
+                if self.down.remove(&button) {
+                    self.released.insert(button);
+                }
+            }
+        }
+    }
+
+    fn release_all(&mut self) {
+        self.released.extend(self.down.drain());
+    }
+
+    fn end_frame(&mut self) {
+        self.pressed.clear();
+        self.released.clear();
+    }
+}
+
+/// Keyboard, mouse and gamepad state assembled from winit events.
+///
+/// Pass every event to `handle_event`. Per-frame state (just pressed and released buttons, mouse
+/// motion, scroll and text) is reset when winit starts a new batch of events with
+/// `Event::NewEvents`, so it is valid from `MainEventsCleared` through `RedrawRequested`.
+///
+/// Gamepads are not read from winit, report them with `set_gamepad_button` and
+/// `set_gamepad_axis`.
+#[derive(Clone, Debug, Default)]
+pub struct Input {
+    keys: ButtonState<Keycode>,
+    mouse_buttons: ButtonState<MouseButton>,
+    gamepad_buttons: ButtonState<GamepadButton>,
+    gamepad_axes: HashMap<GamepadAxis, f32>,
+    modifiers: ModifiersState,
+    cursor_position: Option<PhysicalPosition<f64>>,
+    mouse_motion: (f64, f64),
+    scroll: (f32, f32),
+    text: String,
+}
+
+impl Input {
+    pub fn new() -> Input {
+        Input::default()
+    }
+
+    /// Updates the state from a winit event.
+    pub fn handle_event<T>(&mut self, event: &Event<T>) {
+        match event {
+            Event::NewEvents(_) => self.end_frame(),
+            Event::WindowEvent { event, .. } => self.handle_window_event(event),
+            Event::DeviceEvent {
+                event: DeviceEvent::MouseMotion { delta },
+                ..
+            } => {
+                self.mouse_motion.0 += delta.0;
+                self.mouse_motion.1 += delta.1;
+            }
+            _ => {}
+        }
+    }
+
+    fn handle_window_event(&mut self, event: &WindowEvent) {
+        match event {
+            WindowEvent::KeyboardInput { input, .. } => {
+                if let Some(key) = input.virtual_keycode {
+                    self.keys.set(key, input.state);
+                }
+            }
+            WindowEvent::MouseInput { state, button, .. } => {
+                self.mouse_buttons.set(*button, *state);
+            }
+            WindowEvent::CursorMoved { position, .. } => {
+                self.cursor_position = Some(*position);
+            }
+            WindowEvent::CursorLeft { .. } => {
+                self.cursor_position = None;
+            }
+            WindowEvent::MouseWheel { delta, .. } => {
+                let (x, y) = match delta {
+                    MouseScrollDelta::LineDelta(x, y) => (*x, *y),
+                    MouseScrollDelta::PixelDelta(pos) => (
+                        pos.x as f32 / PIXELS_PER_LINE,
+                        pos.y as f32 / PIXELS_PER_LINE,
+                    ),
+                };
+                self.scroll.0 += x;
+                self.scroll.1 += y;
+            }
+            WindowEvent::ReceivedCharacter(c) if !c.is_control() => {
+                self.text.push(*c);
+            }
+            WindowEvent::ModifiersChanged(modifiers) => {
+                self.modifiers = *modifiers;
+            }
+            // Release events are not delivered while unfocused, so drop everything held
+            WindowEvent::Focused(false) => {
+                self.keys.release_all();
+                self.mouse_buttons.release_all();
+                self.modifiers = ModifiersState::empty();
+            }
+            _ => {}
+        }
+    }
+
+    /// Clears the per-frame state. Called automatically on `Event::NewEvents`, only call this
+    /// directly when not passing every event through `handle_event`.
+    pub fn end_frame(&mut self) {
+        self.keys.end_frame();
+        self.mouse_buttons.end_frame();
+        self.gamepad_buttons.end_frame();
+        self.mouse_motion = (0.0, 0.0);
+        self.scroll = (0.0, 0.0);
+        self.text.clear();
+    }
+
+    /// Returns true while `key` is held.
+    pub fn key_down(&self, key: Keycode) -> bool {
+        self.keys.down.contains(&key)
+    }
+
+    /// Returns true if `key` was pressed this frame.
+    pub fn key_pressed(&self, key: Keycode) -> bool {
+        self.keys.pressed.contains(&key)
+    }
+
+    /// Returns true if `key` was released this frame.
+    pub fn key_released(&self, key: Keycode) -> bool {
+        self.keys.released.contains(&key)
+    }
+
+    /// Every key currently held.
+    pub fn keys_down(&self) -> &HashSet<Keycode> {
+        &self.keys.down
+    }
+
+    /// Returns true while `button` is held.
+    pub fn mouse_down(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.down.contains(&button)
+    }
+
+    /// Returns true if `button` was pressed this frame.
+    pub fn mouse_pressed(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.pressed.contains(&button)
+    }
+
+    /// Returns true if `button` was released this frame.
+    pub fn mouse_released(&self, button: MouseButton) -> bool {
+        self.mouse_buttons.released.contains(&button)
+    }
+
+    /// Every mouse button currently held.
+    pub fn mouse_buttons_down(&self) -> &HashSet<MouseButton> {
+        &self.mouse_buttons.down
+    }
+
+    pub fn modifiers(&self) -> ModifiersState {
+        self.modifiers
+    }
+
+    /// Position of the cursor in the window, or `None` if it is outside the window.
+    pub fn cursor_position(&self) -> Option<PhysicalPosition<f64>> {
+        self.cursor_position
+    }
+
+    /// Raw, unaccelerated mouse motion this frame. Keeps reporting while the cursor is grabbed.
+    pub fn mouse_motion(&self) -> (f64, f64) {
+        self.mouse_motion
+    }
+
+    /// Scroll wheel motion this frame, in lines.
+    pub fn scroll(&self) -> (f32, f32) {
+        self.scroll
+    }
+
+    /// Text typed this frame, without control characters.
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    pub fn set_gamepad_button(&mut self, button: GamepadButton, down: bool) {
+        let state = if down {
+            ElementState::Pressed
+        } else {
+            ElementState::Released
+        };
+        self.gamepad_buttons.set(button, state);
+    }
+
+    pub fn set_gamepad_axis(&mut self, axis: GamepadAxis, value: f32) {
+        self.gamepad_axes.insert(axis, value);
+    }
+
+    /// Returns true while `button` is held.
+    pub fn gamepad_down(&self, button: GamepadButton) -> bool {
+        self.gamepad_buttons.down.contains(&button)
+    }
+
+    /// Returns true if `button` was pressed this frame.
+    pub fn gamepad_pressed(&self, button: GamepadButton) -> bool {
+        self.gamepad_buttons.pressed.contains(&button)
+    }
+
+    /// Returns true if `button` was released this frame.
+    pub fn gamepad_released(&self, button: GamepadButton) -> bool {
+        self.gamepad_buttons.released.contains(&button)
+    }
+}
+
+impl InputSource for Input {
+    fn is_down(&self, binding: &Binding) -> bool {
+        match *binding {
+            Binding::Key(key) => self.key_down(key),
+            Binding::MouseButton(button) => self.mouse_down(button),
+            Binding::GamepadButton(button) => self.gamepad_down(button),
+        }
+    }
+
+    fn mouse_motion(&self) -> (f32, f32) {
+        (self.mouse_motion.0 as f32, self.mouse_motion.1 as f32)
+    }
+
+    fn mouse_wheel(&self) -> (f32, f32) {
+        self.scroll
+    }
+
+    fn gamepad_axis(&self, axis: GamepadAxis) -> f32 {
+        self.gamepad_axes.get(&axis).copied().unwrap_or(0.0)
+    }
+}
@@ -1,3 +1,13 @@
+// Lets `#[derive(HasLayout)]` refer to `::magneto` inside this crate too.
+extern crate self as magneto;
+
+pub mod app;
 pub mod camera;
 pub mod graphics;
+pub mod input;
 pub mod model;
+
+#[doc(hidden)]
+pub mod __private {
+    pub use wgpu;
+}
@@ -1,89 +1,475 @@
+use std::collections::HashMap;
 use std::convert::TryInto;
 use std::fmt::Debug;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-use anyhow::Result;
-use wgpu::util::DeviceExt;
+use anyhow::{bail, Context, Result};
+use nalgebra::Vector3;
 
-use crate::graphics::{Display, Mesh, Vertex};
+use crate::graphics::{Bounds, Display, Mesh, MeshData, Texture, Vertex};
 
-// TODO: Implement material loading
+pub mod gltf;
+
+/// A texture map referenced by a material, along with the path it was loaded from.
+///
+/// `path` is `None` for textures embedded in the model file. Materials of one model that use the
+/// same image share its texture.
+pub struct TextureMap {
+    pub path: Option<PathBuf>,
+    pub texture: Arc<Texture>,
+}
+
+/// Surface properties of a model, as described by an MTL file or a glTF material.
 pub struct Material {
+    pub name: String,
+    /// Ambient colour (`Ka`).
+    pub ambient: [f32; 3],
+    /// Diffuse colour (`Kd`).
     pub diffuse: [f32; 3],
+    /// Specular colour (`Ks`).
+    pub specular: [f32; 3],
+    /// Emissive colour (`Ke`).
+    pub emissive: [f32; 3],
+    /// Specular exponent (`Ns`).
+    pub shininess: f32,
+    /// Opacity, where 1.0 is fully opaque (`d`).
+    pub dissolve: f32,
+    /// PBR metalness (`Pm`), 0.0 if unspecified.
+    pub metallic: f32,
+    /// PBR roughness (`Pr`), 1.0 if unspecified.
+    pub roughness: f32,
+    /// Diffuse colour map (`map_Kd`), loaded as sRGB.
+    pub diffuse_texture: Option<TextureMap>,
+    /// Normal map (`map_Bump`), loaded as linear.
+    pub normal_texture: Option<TextureMap>,
+    /// Specular colour map (`map_Ks`), loaded as sRGB.
+    pub specular_texture: Option<TextureMap>,
+    /// Opacity map (`map_d`), loaded as linear.
+    pub dissolve_texture: Option<TextureMap>,
+    /// PBR metalness in the blue channel and roughness in the green channel, loaded as linear.
+    pub metallic_roughness_texture: Option<TextureMap>,
+    /// Ambient occlusion in the red channel, loaded as linear.
+    pub occlusion_texture: Option<TextureMap>,
+    /// Emissive colour map, loaded as sRGB.
+    pub emissive_texture: Option<TextureMap>,
+}
+
+/// A single mesh of a model, and the index of the material it uses in `ObjModel::materials`.
+pub struct ModelMesh {
+    pub name: String,
+    pub mesh: Mesh,
+    pub material_id: Option<usize>,
+}
+
+/// CPU-side geometry of a single mesh of a model, before it is uploaded as a `ModelMesh`.
+pub struct ModelMeshData<V>
+where
+    V: Vertex,
+{
+    pub name: String,
+    pub data: MeshData<V>,
+    pub material_id: Option<usize>,
 }
 
 pub struct ObjModel {
-    pub meshes: Vec<Mesh>,
+    pub meshes: Vec<ModelMesh>,
     pub materials: Vec<Material>,
+    /// Bounds of all meshes combined.
+    pub bounds: Bounds,
+}
+
+/// Combines all of `bounds`, returning the default empty bounds if there are none.
+fn union_bounds<I>(bounds: I) -> Bounds
+where
+    I: IntoIterator<Item = Bounds>,
+{
+    bounds
+        .into_iter()
+        .reduce(|a, b| a.union(&b))
+        .unwrap_or_default()
+}
+
+/// Textures already loaded by an OBJ model, keyed by path and whether they are sRGB.
+type TextureCache = HashMap<(PathBuf, bool), Arc<Texture>>;
+
+/// Loads the texture `name` relative to `dir`, or reuses it from `cache`. Returns `None` if `name`
+/// is empty.
+fn load_texture_map(
+    dpy: &Display,
+    dir: &Path,
+    cache: &mut TextureCache,
+    name: &str,
+    srgb: bool,
+) -> Result<Option<TextureMap>> {
+    if name.is_empty() {
+        return Ok(None);
+    }
+
+    let path = dir.join(name);
+    if let Some(texture) = cache.get(&(path.clone(), srgb)) {
+        return Ok(Some(TextureMap {
+            path: Some(path),
+            texture: texture.clone(),
+        }));
+    }
+
+    let bytes =
+        std::fs::read(&path).with_context(|| format!("Unable to read texture {:?}", path))?;
+    let texture = if srgb {
+        Texture::new_from_bytes(dpy, &bytes, None)
+    } else {
+        Texture::new_linear_from_bytes(dpy, &bytes, None)
+    }
+    .with_context(|| format!("Unable to decode texture {:?}", path))?;
+    let texture = Arc::new(texture);
+    cache.insert((path.clone(), srgb), texture.clone());
+
+    Ok(Some(TextureMap {
+        path: Some(path),
+        texture,
+    }))
+}
+
+/// Parses an RGB triple from an unrecognised MTL parameter, such as `Ke`.
+fn parse_color(value: Option<&String>) -> Option<[f32; 3]> {
+    let values: Vec<f32> = value?
+        .split_whitespace()
+        .map(|v| v.parse().ok())
+        .collect::<Option<_>>()?;
+    values.try_into().ok()
+}
+
+/// Parses a single float from an unrecognised MTL parameter, such as `Pm`.
+fn parse_float(value: Option<&String>) -> Option<f32> {
+    value?.trim().parse().ok()
+}
+
+/// How normals are generated for meshes which do not provide them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum NormalGeneration {
+    /// Each triangle uses its face normal. Vertices are duplicated so that no two faces share one.
+    Flat,
+    /// Each vertex uses the area weighted average of the normals of the faces sharing it.
+    Smooth,
+}
+
+/// Computes area weighted vertex normals from indexed triangles.
+///
+/// Vertices not referenced by any non-degenerate triangle get a zero normal.
+fn smooth_normals(positions: &[[f32; 3]], indices: &[u32]) -> Vec<[f32; 3]> {
+    let mut normals = vec![Vector3::zeros(); positions.len()];
+    for tri in indices.chunks(3) {
+        let [a, b, c] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+        // The cross product's length is twice the triangle's area, weighting larger faces more
+        let n = face_normal(positions[a], positions[b], positions[c]);
+        normals[a] += n;
+        normals[b] += n;
+        normals[c] += n;
+    }
+
+    normals
+        .into_iter()
+        .map(|n| n.try_normalize(f32::EPSILON).unwrap_or(n).into())
+        .collect()
+}
+
+/// Returns the unnormalized normal of a counter-clockwise triangle.
+fn face_normal(a: [f32; 3], b: [f32; 3], c: [f32; 3]) -> Vector3<f32> {
+    let a = Vector3::from(a);
+    (Vector3::from(b) - a).cross(&(Vector3::from(c) - a))
+}
+
+/// Checks that `indices` form whole triangles referencing only existing vertices.
+fn validate_indices(name: &str, indices: &[u32], vertex_count: usize) -> Result<()> {
+    if indices.len() % 3 != 0 {
+        bail!(
+            "Mesh {:?} has {} indices, which is not a whole number of triangles.",
+            name,
+            indices.len()
+        );
+    }
+    if let Some(i) = indices.iter().find(|&&i| i as usize >= vertex_count) {
+        bail!(
+            "Mesh {:?} references vertex {} but only has {} vertices.",
+            name,
+            i,
+            vertex_count
+        );
+    }
+    Ok(())
+}
+
+/// Splits a flat list of floats into fixed size arrays, checking that there is one per vertex.
+fn attribute<const N: usize>(
+    name: &str,
+    attribute: &str,
+    data: &[f32],
+    vertex_count: usize,
+) -> Result<Vec<[f32; N]>> {
+    if data.len() != vertex_count * N {
+        bail!(
+            "Mesh {:?} has {} {} values, expected {} for {} vertices.",
+            name,
+            data.len(),
+            attribute,
+            vertex_count * N,
+            vertex_count
+        );
+    }
+    Ok(data
+        .chunks_exact(N)
+        .map(|c| c.try_into().unwrap())
+        .collect())
 }
 
+/// Builds vertices and indices from a tobj mesh, filling in missing texture coordinates and normals.
+fn assemble_vertices<V>(
+    name: &str,
+    mesh: &tobj::Mesh,
+    normal_generation: NormalGeneration,
+) -> Result<(Vec<V>, Vec<u32>)>
+where
+    V: Vertex,
+{
+    if mesh.positions.len() % 3 != 0 {
+        bail!(
+            "Mesh {:?} has {} position values, which is not a multiple of 3.",
+            name,
+            mesh.positions.len()
+        );
+    }
+    let vertex_count = mesh.positions.len() / 3;
+    let positions: Vec<[f32; 3]> = attribute(name, "position", &mesh.positions, vertex_count)?;
+    validate_indices(name, &mesh.indices, vertex_count)?;
+
+    let texture_coords: Vec<[f32; 2]> = if mesh.texcoords.is_empty() {
+        vec![[0.0; 2]; vertex_count]
+    } else {
+        attribute(name, "texture coordinate", &mesh.texcoords, vertex_count)?
+    };
+
+    if !mesh.normals.is_empty() {
+        let normals: Vec<[f32; 3]> = attribute(name, "normal", &mesh.normals, vertex_count)?;
+        let vertices = positions
+            .iter()
+            .zip(&normals)
+            .zip(&texture_coords)
+            .map(|((pos, norm), tc)| V::with_features(*pos, *norm, *tc))
+            .collect();
+        return Ok((vertices, mesh.indices.clone()));
+    }
+
+    match normal_generation {
+        NormalGeneration::Smooth => {
+            let normals = smooth_normals(&positions, &mesh.indices);
+            let vertices = positions
+                .iter()
+                .zip(&normals)
+                .zip(&texture_coords)
+                .map(|((pos, norm), tc)| V::with_features(*pos, *norm, *tc))
+                .collect();
+            Ok((vertices, mesh.indices.clone()))
+        }
+        NormalGeneration::Flat => {
+            let mut vertices = Vec::with_capacity(mesh.indices.len());
+            for tri in mesh.indices.chunks(3) {
+                let [a, b, c] = [tri[0] as usize, tri[1] as usize, tri[2] as usize];
+                let n = face_normal(positions[a], positions[b], positions[c]);
+                let n: [f32; 3] = n.try_normalize(f32::EPSILON).unwrap_or(n).into();
+                for &i in &[a, b, c] {
+                    vertices.push(V::with_features(positions[i], n, texture_coords[i]));
+                }
+            }
+            let indices = (0..vertices.len() as u32).collect();
+            Ok((vertices, indices))
+        }
+    }
+}
+
+/// Finds the first face of OBJ `source` that references a vertex, texture coordinate or normal
+/// not defined before it, to explain tobj's out of bounds errors.
+fn describe_out_of_bounds_face(source: &str) -> Option<String> {
+    const ATTRIBUTES: [&str; 3] = ["vertex", "texture coordinate", "normal"];
+    let mut counts = [0; 3];
+    for (line_number, line) in source.lines().enumerate() {
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => counts[0] += 1,
+            Some("vt") => counts[1] += 1,
+            Some("vn") => counts[2] += 1,
+            Some("f") => {
+                for vertex in words {
+                    for (attribute, index) in vertex.split('/').enumerate().take(3) {
+                        if index.is_empty() {
+                            continue;
+                        }
+                        // Negative indices count back from the last definition
+                        let index: i64 = index.parse().ok()?;
+                        let count = counts[attribute];
+                        if index == 0 || index.unsigned_abs() > count {
+                            return Some(format!(
+                                "Face on line {} references {} {} but only {} are defined before it.",
+                                line_number + 1,
+                                ATTRIBUTES[attribute],
+                                index,
+                                count
+                            ));
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+/// The models of an OBJ file, and its materials or the error loading them.
+type ObjContents = (
+    Vec<tobj::Model>,
+    std::result::Result<Vec<tobj::Material>, tobj::LoadError>,
+);
+
+/// Loads an OBJ file with tobj, triangulated and with a single index per vertex.
+fn load_obj(path: &Path) -> Result<ObjContents> {
+    let options = tobj::LoadOptions {
+        single_index: true,
+        triangulate: true,
+        ..Default::default()
+    };
+    match tobj::load_obj(path, &options) {
+        Ok(obj) => Ok(obj),
+        Err(
+            e @ (tobj::LoadError::FaceVertexOutOfBounds
+            | tobj::LoadError::FaceTexCoordOutOfBounds
+            | tobj::LoadError::FaceNormalOutOfBounds),
+        ) => {
+            let detail = std::fs::read_to_string(path)
+                .ok()
+                .and_then(|source| describe_out_of_bounds_face(&source));
+            match detail {
+                Some(detail) => bail!("Invalid OBJ file {:?}: {}", path, detail),
+                None => Err(e.into()),
+            }
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Builds the CPU-side mesh of a tobj model, generating tangents if `V` stores them.
+fn obj_mesh_data<V>(
+    model: tobj::Model,
+    normal_generation: NormalGeneration,
+) -> Result<ModelMeshData<V>>
+where
+    V: Vertex,
+{
+    let (vertices, indices) = assemble_vertices::<V>(&model.name, &model.mesh, normal_generation)?;
+    let mut data = MeshData::new(vertices, indices);
+    if V::HAS_TANGENT && !data.generate_tangents() {
+        log::warn!("Unable to generate tangents for mesh {:?}", model.name);
+    }
+
+    Ok(ModelMeshData {
+        name: model.name,
+        data,
+        material_id: model.mesh.material_id,
+    })
+}
+
+/// Loads the meshes of a Wavefront OBJ model without uploading them or loading materials.
+///
+/// Normals are generated with `normal_generation` for meshes which have none, and texture
+/// coordinates default to `[0.0, 0.0]`.
+pub fn load_obj_meshes<P, V>(
+    path: P,
+    normal_generation: NormalGeneration,
+) -> Result<Vec<ModelMeshData<V>>>
+where
+    P: AsRef<Path> + Debug,
+    V: Vertex,
+{
+    let (models, _) = load_obj(path.as_ref())?;
+    models
+        .into_iter()
+        .map(|model| obj_mesh_data(model, normal_generation))
+        .collect()
+}
+
+/// Loads a Wavefront OBJ model, generating smooth normals for meshes which have none.
 pub fn load_model<P, V>(path: P, dpy: &Display) -> Result<ObjModel>
 where
     P: AsRef<Path> + Debug,
     V: Vertex,
 {
-    let (models, mats) = tobj::load_obj(
-        path,
-        &tobj::LoadOptions {
-            single_index: true,
-            triangulate: true,
-            ..Default::default()
-        },
-    )?;
+    load_model_with_normals::<P, V>(path, dpy, NormalGeneration::Smooth)
+}
+
+/// Loads a Wavefront OBJ model, generating normals with `normal_generation` for meshes which have none.
+///
+/// Meshes without texture coordinates get `[0.0, 0.0]` for every vertex.
+pub fn load_model_with_normals<P, V>(
+    path: P,
+    dpy: &Display,
+    normal_generation: NormalGeneration,
+) -> Result<ObjModel>
+where
+    P: AsRef<Path> + Debug,
+    V: Vertex,
+{
+    let path_dir = path
+        .as_ref()
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+
+    let (models, mats) = load_obj(path.as_ref())?;
 
     let mats = mats?;
+    let dir = path_dir.as_path();
 
     let mut meshes = Vec::new();
     let mut materials = Vec::new();
 
     for model in models {
-        let mesh = &model.mesh;
-        let vertices: Vec<V> = mesh
-            .positions
-            .chunks(3)
-            .zip(mesh.texcoords.chunks(2))
-            .zip(mesh.normals.chunks(3))
-            .map(|((pos, tc), norm)| {
-                V::with_features(
-                    pos.try_into().unwrap(),
-                    norm.try_into().unwrap(),
-                    tc.try_into().unwrap(),
-                )
-            })
-            .collect();
-
-        let vb = dpy
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: None,
-                contents: bytemuck::cast_slice(&vertices),
-                usage: wgpu::BufferUsage::VERTEX,
-            });
-
-        let ib = dpy
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: None,
-                contents: bytemuck::cast_slice(&mesh.indices),
-                usage: wgpu::BufferUsage::INDEX,
-            });
-
-        let mesh = Mesh {
-            vertex_buffer: vb,
-            index_buffer: Some(ib),
-            count: mesh.indices.len() as u32,
-        };
-
-        meshes.push(mesh);
+        let data = obj_mesh_data::<V>(model, normal_generation)?;
+        meshes.push(ModelMesh {
+            mesh: Mesh::from_mesh_data(&dpy.device, &data.data),
+            name: data.name,
+            material_id: data.material_id,
+        });
     }
 
+    let mut textures = TextureCache::new();
     for material in mats {
+        let mut load = |name: &str, srgb| load_texture_map(dpy, dir, &mut textures, name, srgb);
         materials.push(Material {
+            ambient: material.ambient,
             diffuse: material.diffuse,
+            specular: material.specular,
+            emissive: parse_color(material.unknown_param.get("Ke")).unwrap_or([0.0; 3]),
+            shininess: material.shininess,
+            dissolve: material.dissolve,
+            metallic: parse_float(material.unknown_param.get("Pm")).unwrap_or(0.0),
+            roughness: parse_float(material.unknown_param.get("Pr")).unwrap_or(1.0),
+            diffuse_texture: load(&material.diffuse_texture, true)?,
+            normal_texture: load(&material.normal_texture, false)?,
+            specular_texture: load(&material.specular_texture, true)?,
+            dissolve_texture: load(&material.dissolve_texture, false)?,
+            metallic_roughness_texture: None,
+            occlusion_texture: None,
+            emissive_texture: None,
+            name: material.name,
         })
     }
 
-    let model = ObjModel { meshes, materials };
+    let bounds = union_bounds(meshes.iter().map(|m| m.mesh.bounds));
+    let model = ObjModel {
+        meshes,
+        materials,
+        bounds,
+    };
 
     Ok(model)
 }
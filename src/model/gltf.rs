@@ -0,0 +1,476 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{bail, Context, Result};
+use nalgebra::{Matrix4, Vector3};
+
+use super::{smooth_normals, union_bounds, validate_indices, Material, ModelMesh, TextureMap};
+use crate::graphics::{Bounds, Display, Image, Mesh, MeshData, Texture, Vertex};
+
+/// Projection parameters of a camera defined in a glTF file.
+#[derive(Copy, Clone, Debug)]
+pub enum GltfProjection {
+    Perspective {
+        /// Vertical field of view in radians.
+        yfov: f32,
+        aspect_ratio: Option<f32>,
+        znear: f32,
+        /// `None` for an infinite projection.
+        zfar: Option<f32>,
+    },
+    Orthographic {
+        xmag: f32,
+        ymag: f32,
+        znear: f32,
+        zfar: f32,
+    },
+}
+
+/// A camera defined in a glTF file. Cameras are placed in the scene by the nodes referencing them,
+/// and look down the node's +Z axis once converted to left-handed coordinates.
+#[derive(Clone, Debug)]
+pub struct GltfCamera {
+    pub name: Option<String>,
+    pub projection: GltfProjection,
+}
+
+/// A node of the glTF scene hierarchy.
+pub struct GltfNode {
+    pub name: Option<String>,
+    /// Transform relative to the parent node.
+    pub transform: Matrix4<f32>,
+    /// Indices into `GltfModel::nodes`.
+    pub children: Vec<usize>,
+    /// Indices into `GltfModel::meshes`, one for each primitive of the node's mesh.
+    pub meshes: Vec<usize>,
+    /// Index into `GltfModel::cameras`.
+    pub camera: Option<usize>,
+}
+
+/// A model loaded from a glTF 2.0 file.
+///
+/// Data is converted from glTF's right-handed coordinates to the left-handed coordinates used by
+/// the cameras, by negating Z. Triangles are rewound so they still face outwards.
+pub struct GltfModel {
+    pub meshes: Vec<ModelMesh>,
+    pub materials: Vec<Material>,
+    pub nodes: Vec<GltfNode>,
+    /// Indices into `nodes` of the root nodes of the default scene.
+    pub roots: Vec<usize>,
+    pub cameras: Vec<GltfCamera>,
+}
+
+impl GltfModel {
+    /// Computes the world transform of every node, indexed the same as `nodes`.
+    ///
+    /// Nodes that are not reachable from `roots` are not part of the scene and get `None`. Fails if
+    /// a node is reached twice, as the hierarchy then has a cycle or a node with several parents.
+    pub fn world_transforms(&self) -> Result<Vec<Option<Matrix4<f32>>>> {
+        let mut transforms = vec![None; self.nodes.len()];
+        let mut stack: Vec<(usize, Matrix4<f32>)> = self
+            .roots
+            .iter()
+            .map(|&i| (i, Matrix4::identity()))
+            .collect();
+
+        while let Some((index, parent)) = stack.pop() {
+            if transforms[index].is_some() {
+                bail!(
+                    "glTF node {} is reached more than once, nodes must form a tree.",
+                    index
+                );
+            }
+            let node = &self.nodes[index];
+            let world = parent * node.transform;
+            transforms[index] = Some(world);
+            stack.extend(node.children.iter().map(|&c| (c, world)));
+        }
+
+        Ok(transforms)
+    }
+
+    /// Computes the world space bounds of every mesh placed in the scene by a node reachable from
+    /// `roots`. Fails if the node hierarchy is not a tree, see `world_transforms`.
+    pub fn bounds(&self) -> Result<Bounds> {
+        let transforms = self.world_transforms()?;
+        Ok(union_bounds(
+            self.nodes
+                .iter()
+                .zip(transforms)
+                .filter_map(|(node, t)| Some((node, t?)))
+                .flat_map(|(node, t)| {
+                    node.meshes
+                        .iter()
+                        .map(move |&m| self.meshes[m].mesh.bounds.transform(&t))
+                }),
+        ))
+    }
+}
+
+/// Converts decoded glTF image data to tightly packed RGBA8.
+fn to_rgba8(data: &::gltf::image::Data) -> Result<Image> {
+    use ::gltf::image::Format;
+
+    // 16-bit formats are stored little endian, keep the most significant byte.
+    let (channels, stride) = match data.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 | Format::B8G8R8 => (3, 1),
+        Format::R8G8B8A8 | Format::B8G8R8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+    };
+    let bgr = matches!(data.format, Format::B8G8R8 | Format::B8G8R8A8);
+
+    let texel_count = (data.width * data.height) as usize;
+    if data.pixels.len() != texel_count * channels * stride {
+        bail!("glTF image data does not match its dimensions.");
+    }
+
+    let mut rgba = Vec::with_capacity(texel_count * 4);
+    for texel in data.pixels.chunks(channels * stride) {
+        let channel = |c: usize| texel[c * stride + stride - 1];
+        let mut px = [0, 0, 0, 255];
+        for (c, value) in px.iter_mut().enumerate().take(channels) {
+            *value = channel(c);
+        }
+        if bgr {
+            px.swap(0, 2);
+        }
+        rgba.extend_from_slice(&px);
+    }
+
+    Ok(Image::from_rgba8(data.width, data.height, rgba))
+}
+
+/// Maps a glTF sampler to the equivalent wgpu sampler.
+///
+/// Filters the file leaves unset are linear. Wrap modes default to repeat, as in glTF.
+pub fn sampler_descriptor(sampler: &::gltf::texture::Sampler) -> wgpu::SamplerDescriptor<'static> {
+    use ::gltf::texture::{MagFilter, MinFilter, WrappingMode};
+
+    let address_mode = |mode| match mode {
+        WrappingMode::ClampToEdge => wgpu::AddressMode::ClampToEdge,
+        WrappingMode::MirroredRepeat => wgpu::AddressMode::MirrorRepeat,
+        WrappingMode::Repeat => wgpu::AddressMode::Repeat,
+    };
+    let mag_filter = match sampler.mag_filter() {
+        Some(MagFilter::Nearest) => wgpu::FilterMode::Nearest,
+        Some(MagFilter::Linear) | None => wgpu::FilterMode::Linear,
+    };
+    let (min_filter, mipmap_filter) = match sampler.min_filter() {
+        Some(MinFilter::Nearest) | Some(MinFilter::NearestMipmapNearest) => {
+            (wgpu::FilterMode::Nearest, wgpu::FilterMode::Nearest)
+        }
+        Some(MinFilter::NearestMipmapLinear) => {
+            (wgpu::FilterMode::Nearest, wgpu::FilterMode::Linear)
+        }
+        Some(MinFilter::LinearMipmapNearest) => {
+            (wgpu::FilterMode::Linear, wgpu::FilterMode::Nearest)
+        }
+        Some(MinFilter::Linear) | Some(MinFilter::LinearMipmapLinear) | None => {
+            (wgpu::FilterMode::Linear, wgpu::FilterMode::Linear)
+        }
+    };
+
+    wgpu::SamplerDescriptor {
+        address_mode_u: address_mode(sampler.wrap_s()),
+        address_mode_v: address_mode(sampler.wrap_t()),
+        address_mode_w: wgpu::AddressMode::Repeat,
+        mag_filter,
+        min_filter,
+        mipmap_filter,
+        ..Default::default()
+    }
+}
+
+/// Textures already uploaded, keyed by glTF image index, sampler index and whether the image is
+/// sRGB, so materials sharing an image share its texture.
+type TextureCache = HashMap<(usize, Option<usize>, bool), Arc<Texture>>;
+
+/// Uploads the image used by `texture`, returning it along with its path if it is external.
+fn load_texture_map(
+    dpy: &Display,
+    dir: &Path,
+    images: &[::gltf::image::Data],
+    cache: &mut TextureCache,
+    texture: ::gltf::Texture,
+    srgb: bool,
+) -> Result<TextureMap> {
+    let source = texture.source();
+    let path = match source.source() {
+        ::gltf::image::Source::Uri { uri, .. } if !uri.starts_with("data:") => Some(dir.join(uri)),
+        _ => None,
+    };
+
+    let key = (source.index(), texture.sampler().index(), srgb);
+    if let Some(texture) = cache.get(&key) {
+        return Ok(TextureMap {
+            path,
+            texture: texture.clone(),
+        });
+    }
+
+    let data = images
+        .get(source.index())
+        .context("glTF texture references a missing image.")?;
+    let image = to_rgba8(data)?;
+
+    let format = if srgb {
+        wgpu::TextureFormat::Rgba8UnormSrgb
+    } else {
+        wgpu::TextureFormat::Rgba8Unorm
+    };
+    let texture = Arc::new(Texture::new_from_image_with_sampler(
+        dpy,
+        &image,
+        format,
+        &sampler_descriptor(&texture.sampler()),
+        None,
+    ));
+    cache.insert(key, texture.clone());
+
+    Ok(TextureMap { path, texture })
+}
+
+fn load_material(
+    dpy: &Display,
+    dir: &Path,
+    images: &[::gltf::image::Data],
+    cache: &mut TextureCache,
+    material: ::gltf::Material,
+) -> Result<Material> {
+    let name = material.name().unwrap_or_default();
+    let pbr = material.pbr_metallic_roughness();
+    let base_color = pbr.base_color_factor();
+
+    // Meshes only read texture coordinate set 0, so textures meant for another set are sampled with it
+    let mut load = |texture: Option<(::gltf::Texture, u32)>, map: &str, srgb| {
+        texture
+            .map(|(texture, tex_coord)| {
+                if tex_coord != 0 {
+                    log::warn!(
+                        "Material {:?} uses texture coordinate set {} for its {} map, only set 0 is loaded.",
+                        name,
+                        tex_coord,
+                        map
+                    );
+                }
+                load_texture_map(dpy, dir, images, cache, texture, srgb)
+            })
+            .transpose()
+    };
+
+    Ok(Material {
+        name: name.to_string(),
+        ambient: [0.0; 3],
+        diffuse: [base_color[0], base_color[1], base_color[2]],
+        specular: [0.0; 3],
+        emissive: material.emissive_factor(),
+        shininess: 0.0,
+        dissolve: base_color[3],
+        metallic: pbr.metallic_factor(),
+        roughness: pbr.roughness_factor(),
+        diffuse_texture: load(
+            pbr.base_color_texture()
+                .map(|i| (i.texture(), i.tex_coord())),
+            "base colour",
+            true,
+        )?,
+        normal_texture: load(
+            material
+                .normal_texture()
+                .map(|n| (n.texture(), n.tex_coord())),
+            "normal",
+            false,
+        )?,
+        specular_texture: None,
+        dissolve_texture: None,
+        metallic_roughness_texture: load(
+            pbr.metallic_roughness_texture()
+                .map(|i| (i.texture(), i.tex_coord())),
+            "metallic roughness",
+            false,
+        )?,
+        occlusion_texture: load(
+            material
+                .occlusion_texture()
+                .map(|o| (o.texture(), o.tex_coord())),
+            "occlusion",
+            false,
+        )?,
+        emissive_texture: load(
+            material
+                .emissive_texture()
+                .map(|i| (i.texture(), i.tex_coord())),
+            "emissive",
+            true,
+        )?,
+    })
+}
+
+fn load_primitive<V>(
+    dpy: &Display,
+    buffers: &[::gltf::buffer::Data],
+    name: &str,
+    primitive: &::gltf::Primitive,
+) -> Result<Mesh>
+where
+    V: Vertex,
+{
+    if primitive.mode() != ::gltf::mesh::Mode::Triangles {
+        bail!(
+            "Unsupported glTF primitive mode {:?}, only triangles are supported.",
+            primitive.mode()
+        );
+    }
+
+    let reader = primitive.reader(|buffer| buffers.get(buffer.index()).map(|d| &d.0[..]));
+    let positions: Vec<[f32; 3]> = reader
+        .read_positions()
+        .context("glTF primitive has no positions.")?
+        .collect();
+    let texture_coords: Vec<[f32; 2]> = match reader.read_tex_coords(0) {
+        Some(tc) => tc.into_f32().collect(),
+        None => vec![[0.0; 2]; positions.len()],
+    };
+
+    let indices: Vec<u32> = match reader.read_indices() {
+        Some(indices) => indices.into_u32().collect(),
+        None => (0..positions.len() as u32).collect(),
+    };
+    validate_indices(name, &indices, positions.len())?;
+
+    let normals: Vec<[f32; 3]> = match reader.read_normals() {
+        Some(normals) => normals.collect(),
+        None => smooth_normals(&positions, &indices),
+    };
+    if normals.len() != positions.len() || texture_coords.len() != positions.len() {
+        bail!("glTF primitive attributes have mismatched lengths.");
+    }
+
+    // Tangents provided by the file are used as-is, otherwise they are generated if needed
+    let tangents: Option<Vec<[f32; 4]>> = reader
+        .read_tangents()
+        .filter(|_| V::HAS_TANGENT)
+        .map(|t| t.collect::<Vec<_>>())
+        .filter(|t| t.len() == positions.len());
+
+    let vertices: Vec<V> = (0..positions.len())
+        .map(|i| match tangents {
+            Some(ref t) => V::with_tangent(positions[i], normals[i], texture_coords[i], t[i]),
+            None => V::with_features(positions[i], normals[i], texture_coords[i]),
+        })
+        .collect();
+    let mut data = MeshData::new(vertices, indices).transformed(&to_left_handed());
+    if V::HAS_TANGENT && tangents.is_none() && !data.generate_tangents() {
+        log::warn!("Unable to generate tangents for mesh {:?}", name);
+    }
+
+    Ok(Mesh::from_mesh_data(&dpy.device, &data))
+}
+
+/// Mirrors glTF's right-handed coordinates into left-handed ones by negating Z.
+fn to_left_handed() -> Matrix4<f32> {
+    Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, 1.0, -1.0))
+}
+
+/// Loads a glTF 2.0 model from a `.gltf` or `.glb` file.
+///
+/// Buffers and images may be embedded or external, external files are resolved relative to `path`.
+/// Each mesh primitive becomes one `ModelMesh`. Only texture coordinate set 0 is loaded, a warning
+/// is logged for material textures using another set.
+pub fn load_gltf<P, V>(path: P, dpy: &Display) -> Result<GltfModel>
+where
+    P: AsRef<Path> + Debug,
+    V: Vertex,
+{
+    let dir: PathBuf = path
+        .as_ref()
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let (document, buffers, images) = ::gltf::import(path.as_ref())
+        .with_context(|| format!("Unable to import glTF file {:?}", path))?;
+
+    let mut textures = TextureCache::new();
+    let materials = document
+        .materials()
+        .map(|m| load_material(dpy, &dir, &images, &mut textures, m))
+        .collect::<Result<Vec<_>>>()?;
+
+    // Primitives are flattened into `meshes`, remember where each glTF mesh starts.
+    let mut meshes = Vec::new();
+    let mut mesh_ranges = Vec::new();
+    for mesh in document.meshes() {
+        let start = meshes.len();
+        let name = mesh.name().unwrap_or_default();
+        for primitive in mesh.primitives() {
+            meshes.push(ModelMesh {
+                name: name.to_string(),
+                mesh: load_primitive::<V>(dpy, &buffers, name, &primitive)?,
+                material_id: primitive.material().index(),
+            });
+        }
+        mesh_ranges.push(start..meshes.len());
+    }
+
+    let nodes = document
+        .nodes()
+        .map(|node| GltfNode {
+            name: node.name().map(str::to_string),
+            transform: to_left_handed()
+                * Matrix4::from(node.transform().matrix())
+                * to_left_handed(),
+            children: node.children().map(|c| c.index()).collect(),
+            meshes: node
+                .mesh()
+                .map(|m| mesh_ranges[m.index()].clone().collect())
+                .unwrap_or_default(),
+            camera: node.camera().map(|c| c.index()),
+        })
+        .collect();
+
+    let roots = document
+        .default_scene()
+        .or_else(|| document.scenes().next())
+        .map(|scene| scene.nodes().map(|n| n.index()).collect())
+        .unwrap_or_default();
+
+    let cameras = document
+        .cameras()
+        .map(|camera| GltfCamera {
+            name: camera.name().map(str::to_string),
+            projection: match camera.projection() {
+                ::gltf::camera::Projection::Perspective(p) => GltfProjection::Perspective {
+                    yfov: p.yfov(),
+                    aspect_ratio: p.aspect_ratio(),
+                    znear: p.znear(),
+                    zfar: p.zfar(),
+                },
+                ::gltf::camera::Projection::Orthographic(o) => GltfProjection::Orthographic {
+                    xmag: o.xmag(),
+                    ymag: o.ymag(),
+                    znear: o.znear(),
+                    zfar: o.zfar(),
+                },
+            },
+        })
+        .collect();
+
+    let model = GltfModel {
+        meshes,
+        materials,
+        nodes,
+        roots,
+        cameras,
+    };
+    model
+        .world_transforms()
+        .with_context(|| format!("Invalid node hierarchy in glTF file {:?}", path))?;
+    Ok(model)
+}
@@ -0,0 +1,78 @@
+//! Tests for the fixed timestep and frame statistics used by the app runner.
+
+use std::time::Duration;
+
+use magneto::app::{FixedTimestep, FrameStats};
+
+fn ms(ms: u64) -> Duration {
+    Duration::from_millis(ms)
+}
+
+fn assert_near(actual: f32, expected: f32) {
+    assert!(
+        (actual - expected).abs() < 1e-4,
+        "expected {}, got {}",
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn fixed_timestep_counts_steps_and_alpha() {
+    let mut timestep = FixedTimestep::new(ms(10), ms(250));
+    assert_eq!(timestep.step(), ms(10));
+
+    assert_eq!(timestep.advance(ms(4)), 0);
+    assert_near(timestep.alpha(), 0.4);
+
+    // The remainder carries over into the next frame
+    assert_eq!(timestep.advance(ms(17)), 2);
+    assert_near(timestep.alpha(), 0.1);
+
+    assert_eq!(timestep.advance(ms(9)), 1);
+    assert_near(timestep.alpha(), 0.0);
+
+    assert_eq!(timestep.advance(Duration::ZERO), 0);
+}
+
+#[test]
+fn fixed_timestep_caps_long_frames() {
+    let mut timestep = FixedTimestep::new(ms(10), ms(50));
+    assert_eq!(timestep.advance(Duration::from_secs(3)), 5);
+    assert_near(timestep.alpha(), 0.0);
+}
+
+#[test]
+#[should_panic(expected = "Fixed timestep must be above zero")]
+fn fixed_timestep_rejects_zero_step() {
+    FixedTimestep::new(Duration::ZERO, ms(250));
+}
+
+#[test]
+fn frame_stats_average_recent_frames() {
+    let mut stats = FrameStats::new();
+    assert_eq!(stats.average_frame_time(), Duration::ZERO);
+    assert_eq!(stats.fps(), 0.0);
+
+    for frame_time in [ms(10), ms(20), ms(30), ms(20)] {
+        stats.record(frame_time);
+    }
+    assert_eq!(stats.frame_count, 4);
+    assert_eq!(stats.frame_time, ms(20));
+    assert_eq!(stats.average_frame_time(), ms(20));
+    assert_eq!(stats.min_frame_time(), ms(10));
+    assert_eq!(stats.max_frame_time(), ms(30));
+    assert_near(stats.fps(), 50.0);
+}
+
+#[test]
+fn frame_stats_forget_old_frames() {
+    let mut stats = FrameStats::new();
+    stats.record(Duration::from_secs(1));
+    for _ in 0..120 {
+        stats.record(ms(5));
+    }
+    assert_eq!(stats.frame_count, 121);
+    assert_eq!(stats.max_frame_time(), ms(5));
+    assert_near(stats.fps(), 200.0);
+}
@@ -0,0 +1,175 @@
+//! Tests for the name-based bind group layout and bind group builders.
+
+use std::path::Path;
+
+use magneto::graphics::{
+    validate_bind_group, BindGroupLayoutBuilder, DeviceUtilExt, ResourceKind, ShaderReflection,
+    Texture,
+};
+
+mod common;
+
+fn material_layout() -> BindGroupLayoutBuilder {
+    BindGroupLayoutBuilder::new()
+        .with_texture(
+            "albedo",
+            0,
+            wgpu::ShaderStage::FRAGMENT,
+            wgpu::TextureViewDimension::D2,
+            wgpu::TextureSampleType::Float { filterable: true },
+        )
+        .with_sampler("albedo_sampler", 1, wgpu::ShaderStage::FRAGMENT, true)
+        .with_dynamic_uniform("object", 4, wgpu::ShaderStage::VERTEX)
+}
+
+#[test]
+fn layout_from_reflection_uses_variable_names() {
+    let src = std::fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/reflect/material.wgsl"),
+    )
+    .unwrap();
+    let reflection = ShaderReflection::from_wgsl(&src).unwrap();
+    let builder = BindGroupLayoutBuilder::from_reflection(&reflection, 1);
+
+    let entries: Vec<_> = builder
+        .entries()
+        .iter()
+        .map(|e| (e.name.as_str(), e.entry.binding))
+        .collect();
+    assert_eq!(
+        entries,
+        [("albedo", 0), ("albedo_sampler", 1), ("lights", 3)]
+    );
+    assert!(builder.validate().is_ok());
+}
+
+#[test]
+fn layout_rejects_duplicates() {
+    let err = material_layout()
+        .with_uniform("camera", 1, wgpu::ShaderStage::VERTEX)
+        .validate()
+        .unwrap_err();
+    assert!(err.to_string().contains("both use binding 1"), "{}", err);
+
+    let err = material_layout()
+        .with_comparison_sampler("albedo", 2, wgpu::ShaderStage::FRAGMENT)
+        .validate()
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("two entries named `albedo`"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn validate_bind_group_checks_resources() {
+    let layout = material_layout();
+    let entries = layout.entries();
+    let object = ResourceKind::Buffer {
+        size: wgpu::BufferSize::new(64),
+    };
+    let validate = |resources: &[(&str, ResourceKind)]| {
+        validate_bind_group(entries, resources)
+            .map_err(|e| e.to_string())
+            .err()
+    };
+
+    assert_eq!(
+        validate(&[
+            ("object", object),
+            ("albedo_sampler", ResourceKind::Sampler),
+            ("albedo", ResourceKind::TextureView),
+        ]),
+        None
+    );
+    assert_eq!(
+        validate(&[("albedo", ResourceKind::TextureView)]).as_deref(),
+        Some("Bind group is missing entries: albedo_sampler, object")
+    );
+    assert_eq!(
+        validate(&[("objects", object)]).as_deref(),
+        Some("Bind group layout has no entry named `objects`")
+    );
+    assert_eq!(
+        validate(&[("object", object), ("object", object)]).as_deref(),
+        Some("Bind group entry `object` is set more than once")
+    );
+    assert_eq!(
+        validate(&[("albedo", ResourceKind::Sampler)]).as_deref(),
+        Some("Bind group entry `albedo` expects a texture view, but was given a sampler")
+    );
+    assert_eq!(
+        validate(&[("object", ResourceKind::Buffer { size: None })]).as_deref(),
+        Some(
+            "Bind group entry `object` has a dynamic offset, so its buffer needs an explicit size"
+        )
+    );
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn bind_group_checks_resources_against_layout() {
+    let dpy = common::headless_display();
+
+    let layout = material_layout().build(&dpy).unwrap();
+    assert_eq!(layout.dynamic_offset_count(), 1);
+
+    let texture =
+        Texture::new_storage_texture(&dpy.device, 4, 4, wgpu::TextureFormat::Rgba8Unorm, None);
+    let uniforms = dpy.device.init_uniform_buffer(&[0; 256]);
+
+    let err = layout
+        .bind_group()
+        .with_texture("albedo", "albedo_sampler", &texture)
+        .build(&dpy)
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("missing entries: object"),
+        "{}",
+        err
+    );
+
+    let err = layout
+        .bind_group()
+        .with_texture("albedo", "albedo_sampler", &texture)
+        .with_buffer("objects", &uniforms)
+        .build(&dpy)
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("no entry named `objects`"),
+        "{}",
+        err
+    );
+
+    let err = layout
+        .bind_group()
+        .with_texture_view("albedo", &texture.view)
+        .with_buffer("albedo_sampler", &uniforms)
+        .with_buffer("object", &uniforms)
+        .build(&dpy)
+        .unwrap_err();
+    assert!(
+        err.to_string()
+            .contains("`albedo_sampler` expects a sampler, but was given a buffer"),
+        "{}",
+        err
+    );
+
+    let err = layout
+        .bind_group()
+        .with_texture("albedo", "albedo_sampler", &texture)
+        .with_sampler("albedo_sampler", &texture.sampler)
+        .build(&dpy)
+        .unwrap_err();
+    assert!(err.to_string().contains("set more than once"), "{}", err);
+
+    // Resources may be given in any order
+    layout
+        .bind_group()
+        .with_buffer_range("object", &uniforms, 0, wgpu::BufferSize::new(64))
+        .with_sampler("albedo_sampler", &texture.sampler)
+        .with_texture_view("albedo", &texture.view)
+        .build(&dpy)
+        .unwrap();
+}
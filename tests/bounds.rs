@@ -0,0 +1,76 @@
+//! Tests for bounding boxes and spheres.
+
+use magneto::graphics::{Aabb, BasicVertex, BoundingSphere, Bounds, Vertex};
+use nalgebra::{Matrix4, Point3, Vector3};
+
+#[test]
+fn aabb_union_contains_both() {
+    let a = Aabb::new(Point3::new(0.0, 0.0, 0.0), Point3::new(1.0, 1.0, 1.0));
+    let b = Aabb::new(Point3::new(-1.0, 0.5, 2.0), Point3::new(0.5, 3.0, 4.0));
+    let union = a.union(&b);
+    assert_eq!(union.min, Point3::new(-1.0, 0.0, 0.0));
+    assert_eq!(union.max, Point3::new(1.0, 3.0, 4.0));
+    assert_eq!(b.union(&a), union);
+}
+
+#[test]
+fn aabb_transform_encloses_rotated_box() {
+    let aabb = Aabb::new(Point3::new(-1.0, -2.0, -3.0), Point3::new(1.0, 2.0, 3.0));
+
+    let moved = aabb.transform(
+        &(Matrix4::new_translation(&Vector3::new(10.0, 0.0, 0.0))
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(2.0, 1.0, -1.0))),
+    );
+    assert_eq!(moved.min, Point3::new(8.0, -2.0, -3.0));
+    assert_eq!(moved.max, Point3::new(12.0, 2.0, 3.0));
+
+    // A quarter turn around Y swaps the X and Z extents
+    let rotated = aabb.transform(&Matrix4::from_euler_angles(
+        0.0,
+        std::f32::consts::FRAC_PI_2,
+        0.0,
+    ));
+    assert!((rotated.half_extents() - Vector3::new(3.0, 2.0, 1.0)).norm() < 1e-5);
+    assert!(rotated.center().coords.norm() < 1e-5);
+}
+
+#[test]
+fn sphere_union_contains_both() {
+    let a = BoundingSphere::new(Point3::origin(), 1.0);
+    let b = BoundingSphere::new(Point3::new(4.0, 0.0, 0.0), 1.0);
+    let union = a.union(&b);
+    assert_eq!(union.center, Point3::new(2.0, 0.0, 0.0));
+    assert_eq!(union.radius, 3.0);
+
+    // A sphere already containing the other is returned unchanged
+    let inner = BoundingSphere::new(Point3::new(0.5, 0.0, 0.0), 0.25);
+    assert_eq!(a.union(&inner), a);
+    assert_eq!(inner.union(&a), a);
+}
+
+#[test]
+fn sphere_transform_uses_largest_scale() {
+    let sphere = BoundingSphere::new(Point3::new(1.0, 0.0, 0.0), 2.0);
+    let transformed = sphere.transform(
+        &(Matrix4::new_translation(&Vector3::new(0.0, 5.0, 0.0))
+            * Matrix4::new_nonuniform_scaling(&Vector3::new(1.0, 3.0, -2.0))),
+    );
+    assert_eq!(transformed.center, Point3::new(1.0, 5.0, 0.0));
+    assert_eq!(transformed.radius, 6.0);
+}
+
+#[test]
+fn bounds_from_vertices() {
+    let vertex = |p| BasicVertex::with_features(p, [0.0, 1.0, 0.0], [0.0; 2]);
+    let bounds = Bounds::from_vertices(&[
+        vertex([-1.0, 0.0, 0.0]),
+        vertex([3.0, 2.0, 0.0]),
+        vertex([1.0, 1.0, 4.0]),
+    ]);
+    assert_eq!(bounds.aabb.min, Point3::new(-1.0, 0.0, 0.0));
+    assert_eq!(bounds.aabb.max, Point3::new(3.0, 2.0, 4.0));
+    assert_eq!(bounds.sphere.center, Point3::new(1.0, 1.0, 2.0));
+    assert_eq!(bounds.sphere.radius, 3.0);
+
+    assert_eq!(Bounds::from_vertices::<BasicVertex>(&[]), Bounds::default());
+}
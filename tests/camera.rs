@@ -0,0 +1,234 @@
+//! Tests for the FPS and orbit camera controllers.
+
+use std::collections::HashSet;
+use std::time::Duration;
+
+use magneto::camera::{Camera, FPSCamera, OrbitCamera};
+use magneto::graphics::{Aabb, BoundingSphere, Bounds};
+use magneto::input::{ActionMap, Actions};
+use nalgebra::{Point3, Vector3};
+use winit::event::{MouseButton, MouseScrollDelta, VirtualKeyCode as Keycode};
+
+fn assert_near(actual: Point3<f32>, expected: Point3<f32>) {
+    assert!(
+        (actual - expected).norm() < 1e-5,
+        "expected {}, got {}",
+        expected,
+        actual
+    );
+}
+
+/// Actions from the default map with `keys` held.
+fn actions(keys: &[Keycode]) -> Actions {
+    let held: HashSet<_> = keys.iter().copied().collect();
+    ActionMap::default().evaluate(&held)
+}
+
+fn buttons(buttons: &[MouseButton]) -> HashSet<MouseButton> {
+    buttons.iter().copied().collect()
+}
+
+#[test]
+fn orbit_position_follows_yaw_and_pitch() {
+    let mut camera = OrbitCamera::new(Point3::new(1.0, 2.0, 3.0), 5.0, 0.01);
+    assert_near(camera.position(), Point3::new(1.0, 2.0, -2.0));
+
+    // The target sits straight ahead in view space
+    let target = camera.view_matrix().transform_point(&camera.target);
+    assert_near(target, Point3::new(0.0, 0.0, 5.0));
+
+    camera.yaw = std::f32::consts::FRAC_PI_2;
+    assert_near(camera.position(), Point3::new(-4.0, 2.0, 3.0));
+
+    camera.yaw = 0.0;
+    camera.pitch = std::f32::consts::FRAC_PI_6;
+    let expected = Point3::new(1.0, 2.0 + 2.5, 3.0 - 5.0 * 0.75f32.sqrt());
+    assert_near(camera.position(), expected);
+}
+
+#[test]
+fn orbit_mouse_drag_orbits_with_left_button() {
+    let mut camera = OrbitCamera::new(Point3::origin(), 5.0, 0.01);
+
+    camera.mouse_moved((10.0, -20.0), &buttons(&[MouseButton::Left]));
+    assert!((camera.yaw - -0.1).abs() < 1e-6);
+    assert!((camera.pitch - -0.2).abs() < 1e-6);
+    assert_eq!(camera.target, Point3::origin());
+    assert!(((camera.position() - camera.target).norm() - 5.0).abs() < 1e-5);
+
+    // Motion without a button does nothing
+    camera.mouse_moved((10.0, 10.0), &buttons(&[]));
+    assert!((camera.yaw - -0.1).abs() < 1e-6);
+    assert!((camera.pitch - -0.2).abs() < 1e-6);
+}
+
+#[test]
+fn orbit_mouse_drag_pans_with_middle_button() {
+    let mut camera = OrbitCamera::new(Point3::origin(), 5.0, 0.01);
+
+    // Dragging right moves the target left and dragging down moves it up, scaled by distance
+    camera.mouse_moved((10.0, 20.0), &buttons(&[MouseButton::Middle]));
+    assert_eq!((camera.yaw, camera.pitch), (0.0, 0.0));
+    assert_near(camera.target, Point3::new(-0.5, 1.0, 0.0));
+    assert_near(camera.position(), Point3::new(-0.5, 1.0, -5.0));
+}
+
+#[test]
+fn orbit_scroll_zooms() {
+    let mut camera = OrbitCamera::new(Point3::origin(), 10.0, 0.01);
+
+    camera.scrolled(MouseScrollDelta::LineDelta(0.0, 1.0));
+    assert!((camera.distance - 9.0).abs() < 1e-5);
+    assert_near(camera.position(), Point3::new(0.0, 0.0, -9.0));
+
+    camera.scrolled(MouseScrollDelta::LineDelta(0.0, -1.0));
+    assert!((camera.distance - 10.0).abs() < 1e-5);
+}
+
+#[test]
+fn orbit_update_reads_actions() {
+    let mut camera = OrbitCamera::new(Point3::origin(), 10.0, 0.01);
+    let dt = Duration::from_millis(100);
+
+    // Up orbits over the target
+    camera.update(&actions(&[Keycode::Up, Keycode::Right]), dt);
+    assert!((camera.pitch - 0.15).abs() < 1e-6);
+    assert!((camera.yaw - -0.15).abs() < 1e-6);
+    assert!(camera.position().y > 0.0);
+
+    camera.update(&actions(&[Keycode::PageUp]), dt);
+    assert!((camera.distance - 8.5).abs() < 1e-5);
+}
+
+#[test]
+fn orbit_clamps_distance_and_pitch() {
+    let mut camera = OrbitCamera::new(Point3::origin(), 10.0, 0.01);
+    camera.min_distance = 2.0;
+    camera.max_distance = 20.0;
+
+    camera.scrolled(MouseScrollDelta::LineDelta(0.0, 100.0));
+    assert_eq!(camera.distance, 2.0);
+    camera.scrolled(MouseScrollDelta::LineDelta(0.0, -100.0));
+    assert_eq!(camera.distance, 20.0);
+
+    camera.mouse_moved((0.0, 1000.0), &buttons(&[MouseButton::Left]));
+    assert_eq!(camera.pitch, camera.max_pitch);
+    assert!(camera.max_pitch < std::f32::consts::FRAC_PI_2);
+    camera.mouse_moved((0.0, -1000.0), &buttons(&[MouseButton::Left]));
+    assert_eq!(camera.pitch, -camera.max_pitch);
+}
+
+#[test]
+fn orbit_frames_bounds() {
+    let mut camera = OrbitCamera::new(Point3::origin(), 1.0, 0.01);
+    camera.max_distance = 2.0;
+
+    let bounds = Bounds {
+        aabb: Aabb::new(Point3::new(1.0, 1.0, 1.0), Point3::new(3.0, 3.0, 3.0)),
+        sphere: BoundingSphere::new(Point3::new(2.0, 2.0, 2.0), 3.0),
+    };
+    let fovy = std::f32::consts::FRAC_PI_3;
+    camera.frame(&bounds, fovy);
+
+    assert_eq!(camera.target, bounds.sphere.center);
+    assert!((camera.distance - 6.0).abs() < 1e-5);
+    assert_eq!(camera.max_distance, camera.distance);
+    assert_near(
+        camera.position(),
+        bounds.sphere.center - Vector3::new(0.0, 0.0, camera.distance),
+    );
+}
+
+/// Moves a camera at the origin with `keys` held for one second.
+fn fps_after(keys: &[Keycode], turn_speed: f32) -> FPSCamera {
+    let mut camera = FPSCamera::new(Point3::origin(), 0.01, 2.0);
+    camera.turn_speed = turn_speed;
+    camera.update(&actions(keys), Duration::from_secs(1));
+    camera
+}
+
+/// Where `camera` sees the world space `point`.
+fn view_space(camera: &impl Camera, point: Point3<f32>) -> Point3<f32> {
+    camera.view_matrix().transform_point(&point)
+}
+
+#[test]
+fn fps_moves_relative_to_view() {
+    let camera = fps_after(&[Keycode::W], 0.0);
+    assert_near(
+        view_space(&camera, Point3::new(0.0, 0.0, 2.0)),
+        Point3::origin(),
+    );
+
+    let camera = fps_after(&[Keycode::D, Keycode::Space], 0.0);
+    assert_near(
+        view_space(&camera, Point3::new(2.0, 2.0, 0.0)),
+        Point3::origin(),
+    );
+
+    let camera = fps_after(&[Keycode::S, Keycode::LShift], 0.0);
+    assert_near(
+        view_space(&camera, Point3::new(0.0, 0.0, -5.0)),
+        Point3::origin(),
+    );
+}
+
+#[test]
+fn fps_looks_with_actions() {
+    let quarter = std::f32::consts::FRAC_PI_2;
+
+    // Right turns towards +X
+    let camera = fps_after(&[Keycode::Right], quarter);
+    assert_near(
+        view_space(&camera, Point3::new(1.0, 0.0, 0.0)),
+        Point3::new(0.0, 0.0, 1.0),
+    );
+
+    // Up looks towards +Y
+    let camera = fps_after(&[Keycode::Up], quarter / 2.0);
+    let ahead = Point3::new(0.0, 1.0, 1.0) / 2.0f32.sqrt();
+    assert_near(view_space(&camera, ahead), Point3::new(0.0, 0.0, 1.0));
+
+    // E rolls clockwise, so the top of the view turns towards +X
+    let camera = fps_after(&[Keycode::E], quarter);
+    assert_near(
+        view_space(&camera, Point3::new(1.0, 0.0, 0.0)),
+        Point3::new(0.0, 1.0, 0.0),
+    );
+}
+
+#[test]
+fn fps_looks_with_mouse() {
+    let mut camera = FPSCamera::new(Point3::origin(), 0.01, 2.0);
+
+    // Moving the mouse right turns right, moving it up looks up
+    camera.mouse_moved((100.0 * std::f64::consts::FRAC_PI_2, 0.0));
+    camera.update(&actions(&[]), Duration::ZERO);
+    assert_near(
+        view_space(&camera, Point3::new(1.0, 0.0, 0.0)),
+        Point3::new(0.0, 0.0, 1.0),
+    );
+
+    let mut camera = FPSCamera::new(Point3::origin(), 0.01, 2.0);
+    camera.mouse_moved((0.0, -100.0 * std::f64::consts::FRAC_PI_4));
+    camera.update(&actions(&[]), Duration::ZERO);
+    let ahead = Point3::new(0.0, 1.0, 1.0) / 2.0f32.sqrt();
+    assert_near(view_space(&camera, ahead), Point3::new(0.0, 0.0, 1.0));
+}
+
+#[test]
+fn fps_pitch_stops_short_of_the_poles() {
+    let mut camera = FPSCamera::new(Point3::origin(), 0.01, 2.0);
+    camera.mouse_moved((0.0, -1000.0));
+    camera.update(&actions(&[Keycode::W]), Duration::from_secs(1));
+
+    // Moved forward almost straight up, with a valid view
+    let view = camera.view_matrix();
+    assert!(view.iter().all(|v| v.is_finite()));
+    assert!(
+        view_space(&camera, Point3::new(0.0, 2.0, 0.0))
+            .coords
+            .norm()
+            < 0.05
+    );
+}
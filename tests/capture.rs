@@ -0,0 +1,71 @@
+//! Tests for the texture readback helpers and the frame blit.
+
+use magneto::graphics::{
+    bgra_to_rgba, padded_bytes_per_row, unpad_rows, FrameBlit, Image, ShaderReflection, Texture,
+};
+
+mod common;
+
+#[test]
+fn pads_rows_to_copy_alignment() {
+    let align = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+    assert_eq!(padded_bytes_per_row(1), align);
+    assert_eq!(padded_bytes_per_row(align / 4), align);
+    assert_eq!(padded_bytes_per_row(align / 4 + 1), align * 2);
+    assert_eq!(padded_bytes_per_row(100), 512);
+}
+
+#[test]
+fn strips_row_padding() {
+    // Two rows of two texels, each padded out to 12 bytes
+    let data: Vec<u8> = (0..24).collect();
+    let pixels = unpad_rows(&data, 2, 12);
+    assert_eq!(
+        pixels,
+        vec![0, 1, 2, 3, 4, 5, 6, 7, 12, 13, 14, 15, 16, 17, 18, 19]
+    );
+
+    let width = 3;
+    let padded_row = padded_bytes_per_row(width);
+    let data = vec![7; (padded_row * 4) as usize];
+    assert_eq!(unpad_rows(&data, width, padded_row).len(), 3 * 4 * 4);
+}
+
+#[test]
+fn swizzles_bgra_to_rgba() {
+    let mut pixels = vec![1, 2, 3, 4, 10, 20, 30, 40];
+    bgra_to_rgba(&mut pixels);
+    assert_eq!(pixels, vec![3, 2, 1, 4, 30, 20, 10, 40]);
+}
+
+#[test]
+fn blit_shader_is_valid() {
+    let reflection =
+        ShaderReflection::from_wgsl(include_str!("../src/graphics/blit.wgsl")).unwrap();
+    assert_eq!(reflection.bindings.len(), 1);
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn blit_copies_every_pixel() {
+    let dpy = common::headless_display();
+    let format = wgpu::TextureFormat::Rgba8Unorm;
+    let data: Vec<u8> = (0..4 * 3 * 4).map(|i| (i * 5) as u8).collect();
+    let source = Texture::new_from_image(
+        &dpy,
+        &Image::from_rgba8(4, 3, data.clone()),
+        format,
+        Some("source"),
+    );
+    let target = Texture::new_render_target(&dpy.device, 4, 3, format, Some("target"));
+
+    let blit = FrameBlit::new(&dpy.device, &source, format);
+    let mut encoder = dpy
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    blit.blit(&mut encoder, &target.view);
+    dpy.queue.submit(std::iter::once(encoder.finish()));
+
+    let image = pollster::block_on(target.to_image(&dpy)).unwrap();
+    assert_eq!(image.into_raw(), data);
+}
@@ -0,0 +1,21 @@
+//! Helpers shared by the integration tests.
+//!
+//! Tests that render or dispatch need an adapter and are ignored by default, run them with
+//! `cargo test -- --ignored`.
+
+use magneto::graphics::{Display, DisplayBuilder};
+
+/// Creates a 1x1 headless display, panicking if this machine has no usable adapter.
+pub fn headless_display() -> Display {
+    headless_display_with(1, 1, 1)
+}
+
+/// Creates a headless display of the given size and sample count, using any adapter, including
+/// software fallbacks.
+pub fn headless_display_with(width: u32, height: u32, sample_count: u32) -> Display {
+    let mut builder = DisplayBuilder::new();
+    builder
+        .with_backends(wgpu::BackendBit::all())
+        .with_sample_count(sample_count);
+    pollster::block_on(builder.build_headless(width, height)).expect("GPU tests need an adapter")
+}
@@ -0,0 +1,91 @@
+//! Tests for compute pipelines and dispatch helpers.
+
+use std::path::Path;
+
+use magneto::graphics::{
+    workgroup_count, BglBuilder, BindGroupBuilder, ComputePassExt, ComputePipelineBuilder,
+    DeviceUtilExt,
+};
+
+mod common;
+
+#[test]
+fn workgroup_count_rounds_up() {
+    assert_eq!(workgroup_count([64, 1, 1], [64, 1, 1]), [1, 1, 1]);
+    assert_eq!(workgroup_count([65, 1, 1], [64, 1, 1]), [2, 1, 1]);
+    assert_eq!(workgroup_count([100, 30, 2], [8, 8, 1]), [13, 4, 2]);
+    assert_eq!(workgroup_count([0, 1, 1], [64, 1, 1]), [0, 1, 1]);
+}
+
+#[test]
+#[should_panic(expected = "zero dimension")]
+fn workgroup_count_rejects_zero_workgroup_size() {
+    workgroup_count([64, 1, 1], [64, 0, 1]);
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn dispatch_doubles_buffer() {
+    let dpy = common::headless_display();
+
+    let input: Vec<u32> = (0..100).collect();
+    let values = dpy.device.init_storage_buffer(bytemuck::cast_slice(&input));
+    let params = dpy
+        .device
+        .init_uniform_buffer(bytemuck::bytes_of(&(input.len() as u32)));
+
+    let shader = dpy
+        .device
+        .shader_from_file(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/compute/double.wgsl"),
+        )
+        .unwrap();
+    let bgl = BglBuilder::new()
+        .with_storage_buffer(wgpu::ShaderStage::COMPUTE, false)
+        .with_compute_uniforms()
+        .build(&dpy);
+    let bind_group = BindGroupBuilder::new(&bgl)
+        .with_storage_buffer(&values)
+        .with_uniform_buffer(&params)
+        .build(&dpy);
+    let layout = dpy
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+    let pipeline = ComputePipelineBuilder::new()
+        .with_module(&shader)
+        .with_layout(&layout)
+        .build(&dpy.device);
+
+    let size = (input.len() * std::mem::size_of::<u32>()) as u64;
+    let readback = dpy.device.create_buffer(&wgpu::BufferDescriptor {
+        label: None,
+        size,
+        usage: wgpu::BufferUsage::MAP_READ | wgpu::BufferUsage::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    let mut encoder = dpy
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    {
+        let mut pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+        pass.set_pipeline(&pipeline);
+        pass.set_bind_group(0, &bind_group, &[]);
+        pass.dispatch_size([input.len() as u32, 1, 1], [64, 1, 1]);
+    }
+    encoder.copy_buffer_to_buffer(&values, 0, &readback, 0, size);
+    dpy.queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = readback.slice(..);
+    let mapping = slice.map_async(wgpu::MapMode::Read);
+    dpy.device.poll(wgpu::Maintain::Wait);
+    pollster::block_on(mapping).unwrap();
+
+    let output: Vec<u32> = bytemuck::cast_slice(&slice.get_mapped_range()).to_vec();
+    let expected: Vec<u32> = input.iter().map(|v| v * 2).collect();
+    assert_eq!(output, expected);
+}
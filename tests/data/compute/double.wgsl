@@ -0,0 +1,23 @@
+[[block]]
+struct Values {
+    data: [[stride(4)]] array<u32>;
+};
+
+[[block]]
+struct Params {
+    count: u32;
+};
+
+[[group(0), binding(0)]]
+var<storage> values: [[access(read_write)]] Values;
+
+[[group(0), binding(1)]]
+var<uniform> params: Params;
+
+[[stage(compute), workgroup_size(64)]]
+fn main([[builtin(global_invocation_id)]] id: vec3<u32>) {
+    if (id.x >= params.count) {
+        return;
+    }
+    values.data[id.x] = values.data[id.x] * 2u;
+}
@@ -0,0 +1,34 @@
+[[block]]
+struct Uniforms {
+    transform: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> uniforms: Uniforms;
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] normal: vec3<f32>;
+    [[location(1)]] texture_coord: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(
+    [[location(0)]] position: vec3<f32>,
+    [[location(1)]] normal: vec3<f32>,
+    [[location(2)]] texture_coord: vec2<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = uniforms.transform * vec4<f32>(position, 1.0);
+    out.normal = (uniforms.transform * vec4<f32>(normal, 0.0)).xyz;
+    out.texture_coord = texture_coord;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let light: vec3<f32> = normalize(vec3<f32>(0.4, 0.7, -0.6));
+    let diffuse: f32 = max(dot(normalize(in.normal), light), 0.0);
+    let shade: f32 = 0.2 + 0.8 * diffuse;
+    return vec4<f32>(in.texture_coord.x * shade, in.texture_coord.y * shade, shade, 1.0);
+}
@@ -0,0 +1,6 @@
+#include "common/typo.wgsl"
+
+[[stage(vertex)]]
+fn main() -> [[builtin(position)]] vec4<f32> {
+    return vec4<f32>(helper());
+}
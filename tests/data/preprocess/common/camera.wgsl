@@ -0,0 +1,7 @@
+[[block]]
+struct Camera {
+    view_projection: mat4x4<f32>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: Camera;
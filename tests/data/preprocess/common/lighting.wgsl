@@ -0,0 +1,9 @@
+#include "camera.wgsl"
+
+#ifndef LIGHT_INTENSITY
+#define LIGHT_INTENSITY 1.0
+#endif
+
+fn shade() -> f32 {
+    return LIGHT_INTENSITY;
+}
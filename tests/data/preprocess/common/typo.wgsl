@@ -0,0 +1,4 @@
+fn helper() -> f32 {
+    let x: f32 = 1.0;
+    return x +;
+}
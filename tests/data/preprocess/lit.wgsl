@@ -0,0 +1,18 @@
+#include "common/camera.wgsl"
+#include "common/lighting.wgsl"
+
+#define SHADE_SCALE 0.5
+
+[[stage(vertex)]]
+fn vs_main([[location(0)]] position: vec3<f32>) -> [[builtin(position)]] vec4<f32> {
+    return camera.view_projection * vec4<f32>(position, 1.0);
+}
+
+[[stage(fragment)]]
+fn fs_main() -> [[location(0)]] vec4<f32> {
+#ifdef UNLIT
+    return vec4<f32>(1.0, 1.0, 1.0, 1.0);
+#else
+    return vec4<f32>(vec3<f32>(shade() * SHADE_SCALE), 1.0);
+#endif
+}
@@ -0,0 +1,5 @@
+[[stage(vertex)]]
+fn main() -> [[builtin(position)]] vec4<f32> {
+#ifdef FLIP
+    return vec4<f32>(-1.0);
+}
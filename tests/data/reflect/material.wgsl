@@ -0,0 +1,43 @@
+[[block]]
+struct Camera {
+    view_projection: mat4x4<f32>;
+};
+
+[[block]]
+struct Lights {
+    positions: [[stride(16)]] array<vec4<f32>>;
+};
+
+[[group(0), binding(0)]]
+var<uniform> camera: Camera;
+
+[[group(1), binding(0)]]
+var albedo: texture_2d<f32>;
+[[group(1), binding(1)]]
+var albedo_sampler: sampler;
+[[group(1), binding(3)]]
+var<storage> lights: [[access(read)]] Lights;
+
+struct VertexInput {
+    [[location(0)]] position: vec3<f32>;
+    [[location(2)]] texture_coord: vec2<f32>;
+};
+
+struct VertexOutput {
+    [[builtin(position)]] position: vec4<f32>;
+    [[location(0)]] texture_coord: vec2<f32>;
+};
+
+[[stage(vertex)]]
+fn vs_main(in: VertexInput, [[location(5)]] layer: u32) -> VertexOutput {
+    var out: VertexOutput;
+    out.position = camera.view_projection * vec4<f32>(in.position, f32(layer));
+    out.texture_coord = in.texture_coord;
+    return out;
+}
+
+[[stage(fragment)]]
+fn fs_main(in: VertexOutput) -> [[location(0)]] vec4<f32> {
+    let light: vec4<f32> = lights.positions[0];
+    return textureSample(albedo, albedo_sampler, in.texture_coord) * light.w;
+}
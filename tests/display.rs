@@ -0,0 +1,26 @@
+//! Tests for display settings.
+
+use magneto::graphics::DisplayBuilder;
+
+#[test]
+fn validates_sample_count() {
+    for &sample_count in &[1, 4] {
+        DisplayBuilder::new()
+            .with_sample_count(sample_count)
+            .validate()
+            .unwrap();
+    }
+
+    for &sample_count in &[0, 2, 8] {
+        let err = DisplayBuilder::new()
+            .with_sample_count(sample_count)
+            .validate()
+            .unwrap_err()
+            .to_string();
+        assert!(
+            err.contains(&format!("Invalid sample count {}", sample_count)),
+            "{}",
+            err
+        );
+    }
+}
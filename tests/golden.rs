@@ -0,0 +1,307 @@
+//! Golden-image regression tests for the renderer.
+//!
+//! Each test renders a small scene into a headless `Display`, reads it back and compares it
+//! against a reference PNG in `tests/data/golden`. With `MAGNETO_BLESS` set, the rendered image
+//! is written as the new reference instead.
+//!
+//! On mismatch, the rendered image and a diff image are written to the cargo target tmp dir.
+//! Rendering tests need an adapter and are ignored by default, run them with
+//! `cargo test --test golden -- --ignored`.
+
+use std::path::{Path, PathBuf};
+
+use image::{Rgba, RgbaImage};
+use magneto::graphics::{
+    BasicVertex, BglBuilder, BindGroupBuilder, DeviceUtilExt, Display, Mesh, MultisampledTarget,
+    RenderPipelineBuilder,
+};
+use nalgebra::{Matrix4, Vector3};
+
+mod common;
+
+const WIDTH: u32 = 128;
+const HEIGHT: u32 = 128;
+
+/// How far a rendered image may deviate from its reference.
+#[derive(Copy, Clone, Debug)]
+struct Tolerance {
+    /// Maximum absolute difference allowed in any channel before a pixel counts as different.
+    per_channel: u8,
+    /// Maximum number of differing pixels before the comparison fails.
+    max_pixels: usize,
+}
+
+impl Default for Tolerance {
+    fn default() -> Self {
+        Tolerance {
+            per_channel: 2,
+            max_pixels: 0,
+        }
+    }
+}
+
+/// Result of comparing two images.
+struct Comparison {
+    differing_pixels: usize,
+    diff: RgbaImage,
+}
+
+/// Compares `actual` against `expected` pixel by pixel.
+///
+/// The diff image marks differing pixels in red over a faded copy of the reference.
+fn compare_images(actual: &RgbaImage, expected: &RgbaImage, tolerance: Tolerance) -> Comparison {
+    assert_eq!(
+        actual.dimensions(),
+        expected.dimensions(),
+        "Rendered image and reference differ in size."
+    );
+
+    let mut differing_pixels = 0;
+    let mut diff = RgbaImage::new(actual.width(), actual.height());
+    for (x, y, a) in actual.enumerate_pixels() {
+        let e = expected.get_pixel(x, y);
+        let max_delta =
+            a.0.iter()
+                .zip(e.0.iter())
+                .map(|(a, e)| (*a as i16 - *e as i16).unsigned_abs() as u8)
+                .max()
+                .unwrap_or(0);
+
+        if max_delta > tolerance.per_channel {
+            differing_pixels += 1;
+            diff.put_pixel(x, y, Rgba([255, 0, 0, 255]));
+        } else {
+            diff.put_pixel(x, y, Rgba([e[0] / 4, e[1] / 4, e[2] / 4, 255]));
+        }
+    }
+
+    Comparison {
+        differing_pixels,
+        diff,
+    }
+}
+
+/// Compares `actual` against the reference image `name`, blessing or writing a diff as needed.
+fn check_golden(name: &str, actual: &RgbaImage, tolerance: Tolerance) {
+    let reference_path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data/golden")
+        .join(format!("{}.png", name));
+
+    if std::env::var_os("MAGNETO_BLESS").is_some() {
+        actual.save(&reference_path).unwrap();
+        eprintln!("Wrote reference image {:?}", reference_path);
+        return;
+    }
+    assert!(
+        reference_path.exists(),
+        "{:?}: missing reference, rerun with MAGNETO_BLESS=1",
+        reference_path
+    );
+
+    let expected = image::open(&reference_path).unwrap().to_rgba8();
+    let comparison = compare_images(actual, &expected, tolerance);
+    if comparison.differing_pixels > tolerance.max_pixels {
+        let out_dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join("golden");
+        std::fs::create_dir_all(&out_dir).unwrap();
+        let actual_path = out_dir.join(format!("{}.actual.png", name));
+        let diff_path = out_dir.join(format!("{}.diff.png", name));
+        actual.save(&actual_path).unwrap();
+        comparison.diff.save(&diff_path).unwrap();
+        panic!(
+            "{} pixels differ from reference {:?} (tolerance {:?}). Rendered image: {:?}, diff: {:?}",
+            comparison.differing_pixels, reference_path, tolerance, actual_path, diff_path
+        );
+    }
+}
+
+fn scene_shader(dpy: &Display) -> wgpu::ShaderModule {
+    dpy.device
+        .shader_from_file(
+            Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/golden/scene.wgsl"),
+        )
+        .unwrap()
+}
+
+/// Renders `mesh` with `transform` using the scene shader, and reads the frame back.
+fn render_scene(dpy: &Display, mesh: &mut Mesh, transform: Matrix4<f32>) -> RgbaImage {
+    let shader = scene_shader(dpy);
+
+    let bgl = BglBuilder::new().with_vertex_uniforms().build(dpy);
+    let layout = dpy
+        .device
+        .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: None,
+            bind_group_layouts: &[&bgl],
+            push_constant_ranges: &[],
+        });
+
+    let uniforms = dpy
+        .device
+        .init_uniform_buffer(bytemuck::cast_slice(transform.as_slice()));
+    let bind_group = BindGroupBuilder::new(&bgl)
+        .with_uniform_buffer(&uniforms)
+        .build(dpy);
+
+    let pipeline = RenderPipelineBuilder::new()
+        .with_module(&shader)
+        .with_vertex_entry_point("vs_main")
+        .with_fragment_entry_point("fs_main")
+        .with_layout(&layout)
+        .push_vertex_buffer_layout::<BasicVertex>()
+        .with_depth_stencil(wgpu::CompareFunction::Less)
+        .build_for_display(dpy);
+
+    let mut encoder = dpy
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    {
+        let mut rp = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: None,
+            color_attachments: &[dpy.color_attachment(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(wgpu::Color {
+                    r: 0.1,
+                    g: 0.1,
+                    b: 0.1,
+                    a: 1.0,
+                }),
+                store: true,
+            })],
+            depth_stencil_attachment: Some(dpy.depth_attachment(wgpu::Operations {
+                load: wgpu::LoadOp::Clear(1.0),
+                store: true,
+            })),
+        });
+        rp.set_pipeline(&pipeline);
+        rp.set_bind_group(0, &bind_group, &[]);
+        mesh.draw(&mut rp, 0..1);
+    }
+    dpy.queue.submit(std::iter::once(encoder.finish()));
+
+    pollster::block_on(dpy.capture_frame()).unwrap()
+}
+
+/// Rotates the scene and squeezes it into wgpu's 0..1 depth range.
+fn scene_transform(yaw: f32, pitch: f32) -> Matrix4<f32> {
+    Matrix4::new_translation(&Vector3::new(0.0, 0.0, 0.5))
+        * Matrix4::new_nonuniform_scaling(&Vector3::new(0.5, 0.5, 0.25))
+        * Matrix4::from_euler_angles(pitch, yaw, 0.0)
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn golden_cube() {
+    let dpy = common::headless_display_with(WIDTH, HEIGHT, 1);
+    let mut mesh = Mesh::cube::<BasicVertex>(&dpy.device);
+    let image = render_scene(&dpy, &mut mesh, scene_transform(0.6, 0.4));
+    check_golden("cube", &image, Tolerance::default());
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn golden_plane() {
+    let dpy = common::headless_display_with(WIDTH, HEIGHT, 1);
+    let mut mesh = Mesh::plane::<BasicVertex>(&dpy.device);
+    let image = render_scene(&dpy, &mut mesh, scene_transform(0.3, -0.9));
+    check_golden("plane", &image, Tolerance::default());
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn msaa_resolves_into_frame() {
+    let dpy = common::headless_display_with(WIDTH, HEIGHT, 4);
+    assert!(dpy.msaa.is_some());
+    let ops = wgpu::Operations {
+        load: wgpu::LoadOp::Load,
+        store: true,
+    };
+    assert!(dpy.color_attachment(ops).resolve_target.is_some());
+
+    let shader = scene_shader(&dpy);
+    let mut builder = RenderPipelineBuilder::new();
+    builder
+        .with_module(&shader)
+        .with_vertex_entry_point("vs_main")
+        .with_fragment_entry_point("fs_main")
+        .push_vertex_buffer_layout::<BasicVertex>();
+    builder.build_for_display(&dpy);
+    assert_eq!(builder.multisample_state().count, 4);
+
+    // Only the edges, now anti-aliased, differ from the aliased reference
+    let mut mesh = Mesh::cube::<BasicVertex>(&dpy.device);
+    let image = render_scene(&dpy, &mut mesh, scene_transform(0.6, 0.4));
+    let reference = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/golden/cube.png");
+    let reference = image::open(reference).unwrap().to_rgba8();
+    let comparison = compare_images(&image, &reference, Tolerance::default());
+    assert!(comparison.differing_pixels > 0);
+    assert!(
+        comparison.differing_pixels < (WIDTH * HEIGHT / 10) as usize,
+        "{} pixels differ from the aliased cube",
+        comparison.differing_pixels
+    );
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn single_sampled_display_has_no_msaa_target() {
+    let dpy = common::headless_display_with(WIDTH, HEIGHT, 1);
+    assert!(dpy.msaa.is_none());
+    let ops = wgpu::Operations {
+        load: wgpu::LoadOp::Load,
+        store: true,
+    };
+    assert!(dpy.color_attachment(ops).resolve_target.is_none());
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn multisampled_target_resolves() {
+    let dpy = common::headless_display();
+    let format = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let target = MultisampledTarget::new(&dpy.device, 4, 4, format, 4, Some("target"));
+
+    let shader = scene_shader(&dpy);
+    let mut builder = RenderPipelineBuilder::new();
+    builder
+        .with_module(&shader)
+        .with_vertex_entry_point("vs_main")
+        .with_fragment_entry_point("fs_main")
+        .push_vertex_buffer_layout::<BasicVertex>();
+    builder.build_for_target(&dpy.device, &target);
+    assert_eq!(builder.multisample_state().count, 4);
+
+    let mut encoder = dpy
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+        label: None,
+        color_attachments: &[target.color_attachment(wgpu::Operations {
+            load: wgpu::LoadOp::Clear(wgpu::Color::RED),
+            store: true,
+        })],
+        depth_stencil_attachment: None,
+    });
+    dpy.queue.submit(std::iter::once(encoder.finish()));
+
+    let image = pollster::block_on(target.resolve.to_image(&dpy)).unwrap();
+    assert!(image.pixels().all(|p| *p == Rgba([255, 0, 0, 255])));
+}
+
+#[test]
+fn compare_identical_images() {
+    let image = RgbaImage::from_pixel(4, 4, Rgba([10, 20, 30, 255]));
+    let comparison = compare_images(&image, &image, Tolerance::default());
+    assert_eq!(comparison.differing_pixels, 0);
+}
+
+#[test]
+fn compare_respects_tolerance() {
+    let expected = RgbaImage::from_pixel(4, 4, Rgba([100, 100, 100, 255]));
+    let mut actual = expected.clone();
+    actual.put_pixel(0, 0, Rgba([102, 100, 100, 255]));
+    actual.put_pixel(1, 0, Rgba([100, 90, 100, 255]));
+
+    let comparison = compare_images(&actual, &expected, Tolerance::default());
+    assert_eq!(comparison.differing_pixels, 1);
+    assert_eq!(*comparison.diff.get_pixel(1, 0), Rgba([255, 0, 0, 255]));
+    assert_eq!(*comparison.diff.get_pixel(0, 0), Rgba([25, 25, 25, 255]));
+}
@@ -0,0 +1,188 @@
+//! Tests for `ActionMap` configs and evaluation, and `Input` state.
+
+use std::collections::HashSet;
+
+use magneto::input::{
+    ActionMap, AxisBinding, AxisSource, Binding, GamepadAxis, GamepadButton, Input, MouseAxis,
+};
+use winit::event::{
+    DeviceId, ElementState, Event, KeyboardInput, MouseButton, StartCause,
+    VirtualKeyCode as Keycode, WindowEvent,
+};
+use winit::window::WindowId;
+
+fn window_event(event: WindowEvent<'static>) -> Event<'static, ()> {
+    Event::WindowEvent {
+        window_id: unsafe { WindowId::dummy() },
+        event,
+    }
+}
+
+#[allow(deprecated)]
+fn key(key: Keycode, state: ElementState) -> Event<'static, ()> {
+    window_event(WindowEvent::KeyboardInput {
+        device_id: unsafe { DeviceId::dummy() },
+        input: KeyboardInput {
+            scancode: 0,
+            state,
+            virtual_keycode: Some(key),
+            modifiers: Default::default(),
+        },
+        is_synthetic: false,
+    })
+}
+
+#[allow(deprecated)]
+fn mouse(button: MouseButton, state: ElementState) -> Event<'static, ()> {
+    window_event(WindowEvent::MouseInput {
+        device_id: unsafe { DeviceId::dummy() },
+        state,
+        button,
+        modifiers: Default::default(),
+    })
+}
+
+/// Starts a new frame the way the event loop does.
+fn new_frame(input: &mut Input) {
+    input.handle_event(&Event::<()>::NewEvents(StartCause::Poll));
+}
+
+#[test]
+fn action_map_round_trips_through_toml() {
+    let mut map = ActionMap::default();
+    map.bind_axis(
+        "zoom",
+        AxisBinding::new(AxisSource::MouseWheel(MouseAxis::Y)).with_scale(0.5),
+    );
+    map.gamepad_dead_zone = Some(0.25);
+
+    let path = std::env::temp_dir().join(format!("magneto-input-{}.toml", std::process::id()));
+    map.save(&path).unwrap();
+    let loaded = ActionMap::load(&path);
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(loaded.unwrap(), map);
+}
+
+#[test]
+fn action_map_parses_documented_example() {
+    let map = ActionMap::from_toml(
+        r#"
+        [actions]
+        sprint = [{ key = "LShift" }, { gamepad_button = "left_stick" }]
+
+        [axes]
+        move_forward = [
+            { buttons = { negative = { key = "S" }, positive = { key = "W" } } },
+            { gamepad_axis = "left_stick_y" },
+        ]
+        zoom = [{ mouse_wheel = "y", scale = 0.5 }]
+        "#,
+    )
+    .unwrap();
+
+    assert_eq!(
+        map.action_bindings("sprint"),
+        [
+            Binding::Key(Keycode::LShift),
+            Binding::GamepadButton(GamepadButton::LeftStick),
+        ]
+    );
+    assert_eq!(
+        map.axis_bindings("move_forward"),
+        [
+            AxisBinding::keys(Keycode::S, Keycode::W),
+            AxisBinding::gamepad(GamepadAxis::LeftStickY),
+        ]
+    );
+    assert_eq!(
+        map.axis_bindings("zoom"),
+        [AxisBinding::new(AxisSource::MouseWheel(MouseAxis::Y)).with_scale(0.5)]
+    );
+    assert_eq!(map.gamepad_dead_zone, None);
+
+    let held: HashSet<_> = vec![Keycode::W, Keycode::LShift].into_iter().collect();
+    let actions = map.evaluate(&held);
+    assert!(actions.pressed("sprint"));
+    assert_eq!(actions.axis("move_forward"), 1.0);
+}
+
+#[test]
+fn merge_keeps_dead_zone_unless_set() {
+    let mut map = ActionMap::default();
+    map.gamepad_dead_zone = Some(0.3);
+
+    let overlay = ActionMap::from_toml(
+        r#"
+        [axes]
+        zoom = [{ mouse_wheel = "y" }]
+        "#,
+    )
+    .unwrap();
+    map.merge(overlay);
+    assert_eq!(map.gamepad_dead_zone, Some(0.3));
+    assert_eq!(
+        map.axis_bindings("zoom"),
+        [AxisBinding::new(AxisSource::MouseWheel(MouseAxis::Y))]
+    );
+    assert_eq!(
+        map.axis_bindings("move_forward"),
+        ActionMap::default().axis_bindings("move_forward")
+    );
+
+    map.merge(ActionMap::from_toml("gamepad_dead_zone = 0.05").unwrap());
+    assert_eq!(map.gamepad_dead_zone, Some(0.05));
+}
+
+#[test]
+fn key_press_hold_release() {
+    let mut input = Input::new();
+
+    input.handle_event(&key(Keycode::W, ElementState::Pressed));
+    assert!(input.key_pressed(Keycode::W));
+    assert!(input.key_down(Keycode::W));
+    assert!(!input.key_released(Keycode::W));
+
+    // Held across the frame boundary, and key repeat is not a new press
+    new_frame(&mut input);
+    input.handle_event(&key(Keycode::W, ElementState::Pressed));
+    assert!(!input.key_pressed(Keycode::W));
+    assert!(input.key_down(Keycode::W));
+
+    new_frame(&mut input);
+    input.handle_event(&key(Keycode::W, ElementState::Released));
+    assert!(input.key_released(Keycode::W));
+    assert!(!input.key_down(Keycode::W));
+
+    new_frame(&mut input);
+    assert!(!input.key_released(Keycode::W));
+    assert!(input.keys_down().is_empty());
+}
+
+#[test]
+fn mouse_press_and_release_in_one_frame() {
+    let mut input = Input::new();
+
+    input.handle_event(&mouse(MouseButton::Left, ElementState::Pressed));
+    input.handle_event(&mouse(MouseButton::Left, ElementState::Released));
+    assert!(input.mouse_pressed(MouseButton::Left));
+    assert!(input.mouse_released(MouseButton::Left));
+    assert!(!input.mouse_down(MouseButton::Left));
+
+    input.end_frame();
+    assert!(!input.mouse_pressed(MouseButton::Left));
+    assert!(!input.mouse_released(MouseButton::Left));
+}
+
+#[test]
+fn losing_focus_releases_everything() {
+    let mut input = Input::new();
+    input.handle_event(&key(Keycode::LShift, ElementState::Pressed));
+    input.handle_event(&mouse(MouseButton::Right, ElementState::Pressed));
+    new_frame(&mut input);
+
+    input.handle_event(&window_event(WindowEvent::Focused(false)));
+    assert!(input.key_released(Keycode::LShift));
+    assert!(input.mouse_released(MouseButton::Right));
+    assert!(input.keys_down().is_empty());
+    assert!(input.mouse_buttons_down().is_empty());
+}
@@ -0,0 +1,69 @@
+//! Tests for `#[derive(HasLayout)]`.
+
+use bytemuck::{Pod, Zeroable};
+use magneto::graphics::{BasicVertex, HasLayout, TangentVertex};
+use nalgebra::{Matrix4, Vector3};
+use wgpu::{VertexAttribute, VertexFormat};
+
+fn attr(format: VertexFormat, offset: u64, shader_location: u32) -> VertexAttribute {
+    VertexAttribute {
+        format,
+        offset,
+        shader_location,
+    }
+}
+
+#[test]
+fn basic_vertex_matches_attr_array() {
+    let expected = wgpu::vertex_attr_array![3 => Float32x3, 4 => Float32x3, 5 => Float32x2];
+    assert_eq!(BasicVertex::layout(3), expected.to_vec());
+
+    let expected =
+        wgpu::vertex_attr_array![0 => Float32x3, 1 => Float32x3, 2 => Float32x2, 3 => Float32x4];
+    assert_eq!(TangentVertex::layout(0), expected.to_vec());
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable, HasLayout)]
+struct Instance {
+    model: Matrix4<f32>,
+    #[layout(skip)]
+    id: u32,
+    #[layout(format = Unorm8x4)]
+    colour: [u8; 4],
+    tint: Vector3<f32>,
+    layer: u32,
+}
+
+#[test]
+fn matrices_span_locations() {
+    assert_eq!(
+        Instance::layout(5),
+        vec![
+            attr(VertexFormat::Float32x4, 0, 5),
+            attr(VertexFormat::Float32x4, 16, 6),
+            attr(VertexFormat::Float32x4, 32, 7),
+            attr(VertexFormat::Float32x4, 48, 8),
+            attr(VertexFormat::Unorm8x4, 68, 9),
+            attr(VertexFormat::Float32x3, 72, 10),
+            attr(VertexFormat::Uint32, 84, 11),
+        ]
+    );
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable, HasLayout)]
+struct Packed([f32; 2], [[f32; 2]; 2], i32);
+
+#[test]
+fn tuple_structs() {
+    assert_eq!(
+        Packed::layout(0),
+        vec![
+            attr(VertexFormat::Float32x2, 0, 0),
+            attr(VertexFormat::Float32x2, 8, 1),
+            attr(VertexFormat::Float32x2, 16, 2),
+            attr(VertexFormat::Sint32, 24, 3),
+        ]
+    );
+}
@@ -0,0 +1,256 @@
+//! Tests for the `MeshData` shape generators and transforms.
+
+use bytemuck::{Pod, Zeroable};
+use magneto::graphics::{BasicVertex, HasLayout, MeshData, TangentVertex, Vertex};
+use nalgebra::{Matrix4, Vector3};
+
+/// Checks that every index is in range and that every triangle is wound counter-clockwise
+/// around its vertex normals.
+fn assert_valid(name: &str, mesh: &MeshData<BasicVertex>) {
+    assert_eq!(mesh.indices.len() % 3, 0, "{}", name);
+    assert!(
+        mesh.indices
+            .iter()
+            .all(|&i| (i as usize) < mesh.vertices.len()),
+        "{} has an index out of range",
+        name
+    );
+
+    for v in &mesh.vertices {
+        let length = Vector3::from(v.normal()).norm();
+        assert!(
+            (length - 1.0).abs() < 1e-4,
+            "{} has normal {:?}",
+            name,
+            v.normal()
+        );
+    }
+
+    for tri in mesh.indices.chunks(3) {
+        let [a, b, c] = [0, 1, 2].map(|i| &mesh.vertices[tri[i] as usize]);
+        let [pa, pb, pc] = [a, b, c].map(|v| Vector3::from(v.position()));
+        let face = (pb - pa).cross(&(pc - pa));
+        if face.norm() < 1e-6 {
+            continue;
+        }
+        let normals =
+            Vector3::from(a.normal()) + Vector3::from(b.normal()) + Vector3::from(c.normal());
+        assert!(
+            face.dot(&normals) > 0.0,
+            "{} has triangle {:?} wound against its normals",
+            name,
+            tri
+        );
+    }
+}
+
+/// Checks that normals point away from the origin, for convex shapes centred on it.
+fn assert_outward(name: &str, mesh: &MeshData<BasicVertex>) {
+    for v in &mesh.vertices {
+        let outward = Vector3::from(v.normal()).dot(&Vector3::from(v.position()));
+        assert!(
+            outward > 0.0,
+            "{} has normal {:?} at {:?} pointing inwards",
+            name,
+            v.normal(),
+            v.position()
+        );
+    }
+}
+
+#[test]
+fn uv_sphere() {
+    let mesh = MeshData::<BasicVertex>::uv_sphere(2.0, 8, 4);
+    assert_eq!(mesh.vertices.len(), 5 * 9);
+    assert_eq!(mesh.indices.len(), 3 * 8 * (2 * 4 - 2));
+    assert_valid("uv_sphere", &mesh);
+    assert_outward("uv_sphere", &mesh);
+    for v in &mesh.vertices {
+        assert!((Vector3::from(v.position()).norm() - 2.0).abs() < 1e-5);
+    }
+}
+
+#[test]
+fn icosphere() {
+    for subdivisions in 0..3 {
+        let mesh = MeshData::<BasicVertex>::icosphere(0.5, subdivisions);
+        assert_eq!(mesh.indices.len(), 3 * 20 * 4usize.pow(subdivisions));
+        assert_valid("icosphere", &mesh);
+        assert_outward("icosphere", &mesh);
+        for v in &mesh.vertices {
+            assert!((Vector3::from(v.position()).norm() - 0.5).abs() < 1e-5);
+        }
+    }
+}
+
+#[test]
+fn cylinder() {
+    let mesh = MeshData::<BasicVertex>::cylinder(1.0, 2.0, 6);
+    assert_eq!(mesh.vertices.len(), 4 * 6 + 6);
+    assert_eq!(mesh.indices.len(), 12 * 6);
+    assert_valid("cylinder", &mesh);
+    assert_outward("cylinder", &mesh);
+}
+
+#[test]
+fn cone() {
+    let mesh = MeshData::<BasicVertex>::cone(1.0, 2.0, 6);
+    assert_eq!(mesh.vertices.len(), 3 * 6 + 4);
+    assert_eq!(mesh.indices.len(), 6 * 6);
+    assert_valid("cone", &mesh);
+    assert_outward("cone", &mesh);
+}
+
+#[test]
+fn torus() {
+    let mesh = MeshData::<BasicVertex>::torus(2.0, 0.5, 8, 6);
+    assert_eq!(mesh.vertices.len(), 7 * 9);
+    assert_eq!(mesh.indices.len(), 3 * 2 * 8 * 6);
+    assert_valid("torus", &mesh);
+
+    // Normals point away from the middle of the tube
+    for v in &mesh.vertices {
+        let p = Vector3::from(v.position());
+        let centre = Vector3::new(p.x, 0.0, p.z).normalize() * 2.0;
+        assert!(Vector3::from(v.normal()).dot(&(p - centre)) > 0.0);
+    }
+}
+
+#[test]
+fn capsule() {
+    let mesh = MeshData::<BasicVertex>::capsule(0.5, 1.0, 8, 3);
+    assert_eq!(mesh.vertices.len(), (2 * 3 + 2) * 9);
+    assert_eq!(mesh.indices.len(), 3 * 8 * 4 * 3);
+    assert_valid("capsule", &mesh);
+    assert_outward("capsule", &mesh);
+
+    let top = mesh
+        .vertices
+        .iter()
+        .map(|v| v.position()[1])
+        .fold(f32::MIN, f32::max);
+    assert!((top - 1.0).abs() < 1e-5);
+}
+
+#[test]
+fn generators_fill_tangents() {
+    let mesh = MeshData::<TangentVertex>::uv_sphere(1.0, 8, 4);
+    for &i in &mesh.indices {
+        let v = &mesh.vertices[i as usize];
+        assert_eq!(v.tangent()[3].abs(), 1.0);
+        let tangent = Vector3::new(v.tangent()[0], v.tangent()[1], v.tangent()[2]);
+        assert!(tangent.dot(&Vector3::from(v.normal())).abs() < 1e-4);
+    }
+}
+
+#[test]
+fn merge_offsets_indices() {
+    let mut mesh = MeshData::<BasicVertex>::cone(1.0, 1.0, 4);
+    let other = MeshData::<BasicVertex>::cylinder(1.0, 1.0, 4);
+    let (vertex_count, index_count) = (mesh.vertices.len(), mesh.indices.len());
+
+    mesh.merge(&other);
+    assert_eq!(mesh.vertices.len(), vertex_count + other.vertices.len());
+    let offset: Vec<u32> = other
+        .indices
+        .iter()
+        .map(|i| i + vertex_count as u32)
+        .collect();
+    assert_eq!(mesh.indices[index_count..], offset[..]);
+    assert_valid("merged", &mesh);
+}
+
+#[test]
+fn transform_moves_and_rewinds() {
+    let mesh = MeshData::<BasicVertex>::uv_sphere(1.0, 8, 4);
+
+    let moved = mesh
+        .clone()
+        .transformed(&Matrix4::new_translation(&Vector3::new(1.0, 2.0, 3.0)));
+    assert_eq!(moved.indices, mesh.indices);
+    assert_eq!(
+        moved.bounds().sphere.center,
+        mesh.bounds().sphere.center + Vector3::new(1.0, 2.0, 3.0)
+    );
+
+    // Mirroring swaps the winding so triangles still follow their normals
+    let mirrored = mesh
+        .clone()
+        .transformed(&Matrix4::new_nonuniform_scaling(&Vector3::new(
+            1.0, 1.0, -1.0,
+        )));
+    assert_eq!(
+        mirrored.indices[..3],
+        [mesh.indices[0], mesh.indices[2], mesh.indices[1]]
+    );
+    for (v, original) in mirrored.vertices.iter().zip(&mesh.vertices) {
+        let [x, y, z] = original.normal();
+        let expected = Vector3::new(x, y, -z);
+        assert!((Vector3::from(v.normal()) - expected).norm() < 1e-5);
+    }
+    assert_valid("mirrored", &mirrored);
+    assert_outward("mirrored", &mirrored);
+
+    // Normals use the inverse transpose, so non-uniform scales keep them perpendicular
+    let squashed = mesh
+        .clone()
+        .transformed(&Matrix4::new_nonuniform_scaling(&Vector3::new(
+            1.0, 0.25, 1.0,
+        )));
+    assert_valid("squashed", &squashed);
+    assert_outward("squashed", &squashed);
+}
+
+/// A vertex with a colour, which its setters keep.
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable, HasLayout)]
+struct ColourVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    colour: [f32; 4],
+}
+
+impl Vertex for ColourVertex {
+    fn with_features(position: [f32; 3], normal: [f32; 3], _texture_coord: [f32; 2]) -> Self {
+        ColourVertex {
+            position,
+            normal,
+            colour: [1.0; 4],
+        }
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    fn normal(&self) -> [f32; 3] {
+        self.normal
+    }
+
+    fn set_position(&mut self, position: [f32; 3]) {
+        self.position = position;
+    }
+
+    fn set_normal(&mut self, normal: [f32; 3]) {
+        self.normal = normal;
+    }
+
+    fn set_tangent(&mut self, _tangent: [f32; 4]) {}
+}
+
+#[test]
+fn transform_keeps_custom_attributes() {
+    let mut mesh = MeshData::<ColourVertex>::uv_sphere(1.0, 8, 4);
+    for (i, v) in mesh.vertices.iter_mut().enumerate() {
+        v.colour = [i as f32, 0.5, 0.25, 1.0];
+    }
+
+    let moved = mesh
+        .clone()
+        .transformed(&Matrix4::new_translation(&Vector3::new(1.0, 2.0, 3.0)));
+    for (i, (v, original)) in moved.vertices.iter().zip(&mesh.vertices).enumerate() {
+        assert_eq!(v.colour, [i as f32, 0.5, 0.25, 1.0]);
+        let expected = Vector3::from(original.position) + Vector3::new(1.0, 2.0, 3.0);
+        assert!((Vector3::from(v.position) - expected).norm() < 1e-5);
+    }
+}
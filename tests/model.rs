@@ -0,0 +1,312 @@
+//! Tests for the OBJ and glTF model loaders.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use magneto::graphics::{BasicVertex, Vertex};
+use magneto::model::gltf::{load_gltf, sampler_descriptor, GltfModel, GltfNode, GltfProjection};
+use magneto::model::{load_model, load_obj_meshes, NormalGeneration, TextureMap};
+use nalgebra::{Matrix4, Point3, Vector3};
+
+mod common;
+
+fn data_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name)
+}
+
+fn assert_near(actual: &Matrix4<f32>, expected: &Matrix4<f32>) {
+    assert!(
+        (actual - expected).norm() < 1e-5,
+        "{} != {}",
+        actual,
+        expected
+    );
+}
+
+fn assert_unit(v: [f32; 3]) {
+    assert!((Vector3::from(v).norm() - 1.0).abs() < 1e-5, "{:?}", v);
+}
+
+#[test]
+fn obj_smooth_normals_are_area_weighted() {
+    let meshes = load_obj_meshes::<_, BasicVertex>(
+        data_path("obj/no_normals.obj"),
+        NormalGeneration::Smooth,
+    )
+    .unwrap();
+    assert_eq!(meshes.len(), 1);
+    let data = &meshes[0].data;
+    assert_eq!(meshes[0].name, "corner");
+    assert_eq!(data.vertices.len(), 5);
+    assert_eq!(data.indices.len(), 6);
+
+    for v in &data.vertices {
+        assert_unit(v.normal());
+        assert_eq!(v.texture_coord(), [0.0, 0.0]);
+
+        // The shared vertex leans towards the larger face's normal
+        let expected = match v.position() {
+            [0.0, 0.0, 0.0] => Vector3::new(0.0, 1.0, 4.0).normalize(),
+            [2.0, 0.0, 0.0] | [0.0, 2.0, 0.0] => Vector3::z(),
+            _ => Vector3::y(),
+        };
+        assert!(
+            (Vector3::from(v.normal()) - expected).norm() < 1e-5,
+            "{:?} at {:?}",
+            v.normal(),
+            v.position()
+        );
+    }
+}
+
+#[test]
+fn obj_flat_normals_use_face_normals() {
+    let meshes =
+        load_obj_meshes::<_, BasicVertex>(data_path("obj/no_normals.obj"), NormalGeneration::Flat)
+            .unwrap();
+    let data = &meshes[0].data;
+    assert_eq!(data.vertices.len(), 6);
+    assert_eq!(data.indices, [0, 1, 2, 3, 4, 5]);
+
+    let normals: Vec<_> = data.vertices.iter().map(|v| v.normal()).collect();
+    assert_eq!(normals[..3], [[0.0, 0.0, 1.0]; 3]);
+    assert_eq!(normals[3..], [[0.0, 1.0, 0.0]; 3]);
+    assert!(data
+        .vertices
+        .iter()
+        .all(|v| v.texture_coord() == [0.0, 0.0]));
+}
+
+#[test]
+fn obj_out_of_range_index_is_named() {
+    let err = load_obj_meshes::<_, BasicVertex>(
+        data_path("obj/out_of_range.obj"),
+        NormalGeneration::Smooth,
+    )
+    .err()
+    .unwrap();
+    assert!(
+        err.to_string()
+            .contains("Face on line 6 references vertex 9 but only 3 are defined before it."),
+        "{}",
+        err
+    );
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn obj_loads_full_materials() {
+    let dpy = common::headless_display();
+    let model = load_model::<_, BasicVertex>(data_path("obj/materials.obj"), &dpy).unwrap();
+
+    let ids: Vec<_> = model.meshes.iter().map(|m| m.material_id).collect();
+    assert_eq!(ids, [Some(0), Some(1)]);
+
+    let painted = &model.materials[0];
+    assert_eq!(painted.name, "painted");
+    assert_eq!(painted.ambient, [0.1, 0.2, 0.3]);
+    assert_eq!(painted.diffuse, [0.8, 0.4, 0.2]);
+    assert_eq!(painted.specular, [0.5, 0.5, 0.5]);
+    assert_eq!(painted.emissive, [1.0, 0.5, 0.0]);
+    assert_eq!(painted.shininess, 32.0);
+    assert_eq!(painted.dissolve, 0.75);
+    assert_eq!(painted.metallic, 0.25);
+    assert_eq!(painted.roughness, 0.6);
+
+    // Texture paths resolve relative to the OBJ file, colour maps are sRGB and data maps linear
+    let texture = |map: &Option<TextureMap>, name: &str, format: wgpu::TextureFormat| {
+        let map = map.as_ref().unwrap();
+        assert_eq!(map.path, Some(data_path("obj/textures").join(name)));
+        assert_eq!(map.texture.format, format);
+        assert_eq!((map.texture.size.width, map.texture.size.height), (2, 2));
+    };
+    let srgb = wgpu::TextureFormat::Rgba8UnormSrgb;
+    let linear = wgpu::TextureFormat::Rgba8Unorm;
+    texture(&painted.diffuse_texture, "diffuse.png", srgb);
+    texture(&painted.normal_texture, "normal.png", linear);
+    texture(&painted.specular_texture, "specular.png", srgb);
+    texture(&painted.dissolve_texture, "opacity.png", linear);
+
+    let bare = &model.materials[1];
+    assert_eq!(bare.name, "bare");
+    assert_eq!(bare.emissive, [0.0; 3]);
+    assert_eq!(bare.metallic, 0.0);
+    assert_eq!(bare.roughness, 1.0);
+    assert!(bare.diffuse_texture.is_none());
+    assert!(bare.normal_texture.is_none());
+    assert!(bare.specular_texture.is_none());
+    assert!(bare.dissolve_texture.is_none());
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn obj_materials_share_textures() {
+    let dpy = common::headless_display();
+    let model = load_model::<_, BasicVertex>(data_path("obj/shared.obj"), &dpy).unwrap();
+    let texture = |map: &Option<TextureMap>| map.as_ref().unwrap().texture.clone();
+
+    let first = texture(&model.materials[0].diffuse_texture);
+    let second = texture(&model.materials[1].diffuse_texture);
+    assert!(Arc::ptr_eq(&first, &second));
+
+    // The same file loaded as linear data is a separate texture
+    let dissolve = texture(&model.materials[1].dissolve_texture);
+    assert!(!Arc::ptr_eq(&first, &dissolve));
+    assert_eq!(dissolve.format, wgpu::TextureFormat::Rgba8Unorm);
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn gltf_loads_left_handed_scene() {
+    let dpy = common::headless_display();
+    let model = load_gltf::<_, BasicVertex>(data_path("gltf/scene.gltf"), &dpy).unwrap();
+
+    let names: Vec<_> = model.nodes.iter().map(|n| n.name.as_deref()).collect();
+    assert_eq!(
+        names,
+        [
+            Some("root"),
+            Some("triangle"),
+            Some("camera"),
+            Some("unused")
+        ]
+    );
+    assert_eq!(model.roots, [0]);
+    assert_eq!(model.nodes[0].children, [1, 2]);
+    assert_eq!(model.nodes[1].meshes, [0, 1]);
+    assert_eq!(model.nodes[2].camera, Some(0));
+
+    // Translations and rotations are mirrored along Z
+    assert_near(
+        &model.nodes[1].transform,
+        &Matrix4::new_translation(&Vector3::new(1.0, 0.0, 3.0)),
+    );
+    assert_near(
+        &model.nodes[2].transform,
+        &(Matrix4::new_translation(&Vector3::new(0.0, 1.0, -5.0))
+            * Matrix4::from_euler_angles(0.0, -std::f32::consts::FRAC_PI_2, 0.0)),
+    );
+    let world = model.world_transforms().unwrap();
+    assert_near(
+        &world[1].unwrap(),
+        &Matrix4::new_translation(&Vector3::new(1.0, 0.0, -1.0)),
+    );
+    assert!(world[3].is_none());
+
+    assert_eq!(model.meshes.len(), 2);
+    for (mesh, material) in model.meshes.iter().zip(&[Some(0), Some(1)]) {
+        assert_eq!(mesh.name, "triangle");
+        assert_eq!(mesh.mesh.count, 3);
+        assert_eq!(mesh.material_id, *material);
+        assert_eq!(mesh.mesh.bounds.aabb.min, Point3::new(0.0, 0.0, -1.0));
+        assert_eq!(mesh.mesh.bounds.aabb.max, Point3::new(1.0, 1.0, -1.0));
+    }
+    // The unused node is not part of the scene and does not grow its bounds
+    let bounds = model.bounds().unwrap();
+    assert_eq!(bounds.aabb.min, Point3::new(1.0, 0.0, -2.0));
+    assert_eq!(bounds.aabb.max, Point3::new(2.0, 1.0, -2.0));
+
+    let red = &model.materials[0];
+    assert_eq!(red.name, "red");
+    assert_eq!(red.diffuse, [1.0, 0.0, 0.0]);
+    assert_eq!(red.dissolve, 0.5);
+    assert_eq!(red.metallic, 0.25);
+    assert_eq!(red.roughness, 0.75);
+    assert_eq!(red.emissive, [0.0, 0.5, 0.0]);
+    assert!(red.diffuse_texture.is_none());
+    let plain = &model.materials[1];
+    assert_eq!(plain.name, "plain");
+    assert_eq!(plain.diffuse, [1.0; 3]);
+    assert_eq!(plain.metallic, 1.0);
+
+    assert_eq!(model.cameras[0].name.as_deref(), Some("main"));
+    match model.cameras[0].projection {
+        GltfProjection::Perspective { yfov, zfar, .. } => {
+            assert_eq!(yfov, 0.8);
+            assert_eq!(zfar, Some(100.0));
+        }
+        p => panic!("Expected a perspective camera, got {:?}", p),
+    }
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn gltf_materials_share_textures() {
+    let dpy = common::headless_display();
+    let model = load_gltf::<_, BasicVertex>(data_path("gltf/shared_textures.gltf"), &dpy).unwrap();
+    let texture = |map: &Option<TextureMap>| map.as_ref().unwrap().texture.clone();
+    let (first, second) = (&model.materials[0], &model.materials[1]);
+
+    // Different glTF textures with the same image and sampler share one upload
+    let base = texture(&first.diffuse_texture);
+    assert!(Arc::ptr_eq(&base, &texture(&second.diffuse_texture)));
+    assert_eq!(
+        first.diffuse_texture.as_ref().unwrap().path,
+        Some(data_path("gltf/../obj/textures/diffuse.png"))
+    );
+
+    // Linear data and a different sampler need their own textures
+    let normal = texture(&second.normal_texture);
+    assert!(!Arc::ptr_eq(&base, &normal));
+    assert_eq!(normal.format, wgpu::TextureFormat::Rgba8Unorm);
+    let emissive = texture(&second.emissive_texture);
+    assert!(!Arc::ptr_eq(&base, &emissive));
+    assert_eq!(emissive.format, wgpu::TextureFormat::Rgba8UnormSrgb);
+}
+
+fn node(children: Vec<usize>) -> GltfNode {
+    GltfNode {
+        name: None,
+        transform: Matrix4::identity(),
+        children,
+        meshes: Vec::new(),
+        camera: None,
+    }
+}
+
+#[test]
+fn gltf_rejects_cyclic_nodes() {
+    let mut model = GltfModel {
+        meshes: Vec::new(),
+        materials: Vec::new(),
+        nodes: vec![node(vec![1]), node(vec![2]), node(vec![])],
+        roots: vec![0],
+        cameras: Vec::new(),
+    };
+    assert_eq!(model.world_transforms().unwrap().len(), 3);
+
+    model.nodes[2].children.push(0);
+    let err = model.world_transforms().unwrap_err();
+    assert!(err.to_string().contains("node 0"), "{}", err);
+    assert!(model.bounds().is_err());
+}
+
+#[test]
+fn gltf_samplers_map_to_wgpu() {
+    let gltf = ::gltf::Gltf::from_slice(
+        br#"{
+            "asset": { "version": "2.0" },
+            "samplers": [
+                {},
+                { "magFilter": 9728, "minFilter": 9986, "wrapS": 33071, "wrapT": 33648 }
+            ]
+        }"#,
+    )
+    .unwrap();
+    let samplers: Vec<_> = gltf.samplers().map(|s| sampler_descriptor(&s)).collect();
+
+    // glTF defaults to repeating
+    assert_eq!(samplers[0].address_mode_u, wgpu::AddressMode::Repeat);
+    assert_eq!(samplers[0].address_mode_v, wgpu::AddressMode::Repeat);
+    assert_eq!(samplers[0].mag_filter, wgpu::FilterMode::Linear);
+    assert_eq!(samplers[0].min_filter, wgpu::FilterMode::Linear);
+
+    // NEAREST, NEAREST_MIPMAP_LINEAR, CLAMP_TO_EDGE and MIRRORED_REPEAT
+    assert_eq!(samplers[1].address_mode_u, wgpu::AddressMode::ClampToEdge);
+    assert_eq!(samplers[1].address_mode_v, wgpu::AddressMode::MirrorRepeat);
+    assert_eq!(samplers[1].mag_filter, wgpu::FilterMode::Nearest);
+    assert_eq!(samplers[1].min_filter, wgpu::FilterMode::Nearest);
+    assert_eq!(samplers[1].mipmap_filter, wgpu::FilterMode::Linear);
+}
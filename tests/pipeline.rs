@@ -0,0 +1,195 @@
+//! Tests for the state set by `RenderPipelineBuilder`.
+
+use magneto::graphics::{BlendMode, RenderPipelineBuilder, DEPTH_FORMAT};
+use wgpu::{BlendComponent, BlendFactor, BlendOperation, BlendState};
+
+fn component(src_factor: BlendFactor, dst_factor: BlendFactor) -> BlendComponent {
+    BlendComponent {
+        src_factor,
+        dst_factor,
+        operation: BlendOperation::Add,
+    }
+}
+
+#[test]
+fn blend_modes() {
+    let over = component(BlendFactor::One, BlendFactor::OneMinusSrcAlpha);
+    let cases = [
+        (
+            BlendMode::Replace,
+            BlendState {
+                color: component(BlendFactor::One, BlendFactor::Zero),
+                alpha: component(BlendFactor::One, BlendFactor::Zero),
+            },
+        ),
+        (
+            BlendMode::Alpha,
+            BlendState {
+                color: component(BlendFactor::SrcAlpha, BlendFactor::OneMinusSrcAlpha),
+                alpha: over,
+            },
+        ),
+        (
+            BlendMode::Premultiplied,
+            BlendState {
+                color: over,
+                alpha: over,
+            },
+        ),
+        (
+            BlendMode::Additive,
+            BlendState {
+                color: component(BlendFactor::One, BlendFactor::One),
+                alpha: component(BlendFactor::One, BlendFactor::One),
+            },
+        ),
+    ];
+
+    for (mode, expected) in cases.iter() {
+        assert_eq!(mode.state(), *expected, "{:?}", mode);
+    }
+}
+
+#[test]
+fn color_targets() {
+    let format = wgpu::TextureFormat::Bgra8UnormSrgb;
+    let mut builder = RenderPipelineBuilder::new();
+    assert_eq!(
+        builder.color_targets(format),
+        vec![wgpu::ColorTargetState {
+            format,
+            blend: Some(BlendState::REPLACE),
+            write_mask: wgpu::ColorWrite::ALL,
+        }]
+    );
+
+    builder
+        .with_blend(BlendMode::Alpha)
+        .with_write_mask(wgpu::ColorWrite::COLOR)
+        .push_color_target(wgpu::TextureFormat::Rgba16Float, BlendMode::Additive)
+        .push_color_target_state(wgpu::ColorTargetState {
+            format: wgpu::TextureFormat::R32Uint,
+            blend: None,
+            write_mask: wgpu::ColorWrite::RED,
+        });
+    assert_eq!(
+        builder.color_targets(format),
+        vec![
+            wgpu::ColorTargetState {
+                format,
+                blend: Some(BlendMode::Alpha.state()),
+                write_mask: wgpu::ColorWrite::COLOR,
+            },
+            wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::Rgba16Float,
+                blend: Some(BlendMode::Additive.state()),
+                write_mask: wgpu::ColorWrite::ALL,
+            },
+            wgpu::ColorTargetState {
+                format: wgpu::TextureFormat::R32Uint,
+                blend: None,
+                write_mask: wgpu::ColorWrite::RED,
+            },
+        ]
+    );
+}
+
+#[test]
+fn strip_topologies_restart_at_u32_max() {
+    let mut builder = RenderPipelineBuilder::new();
+    assert_eq!(builder.primitive_state().strip_index_format, None);
+
+    for topology in [
+        wgpu::PrimitiveTopology::LineStrip,
+        wgpu::PrimitiveTopology::TriangleStrip,
+    ]
+    .iter()
+    {
+        let primitive = builder.with_topology(*topology).primitive_state();
+        assert_eq!(primitive.topology, *topology);
+        assert_eq!(
+            primitive.strip_index_format,
+            Some(wgpu::IndexFormat::Uint32)
+        );
+    }
+
+    for topology in [
+        wgpu::PrimitiveTopology::PointList,
+        wgpu::PrimitiveTopology::LineList,
+        wgpu::PrimitiveTopology::TriangleList,
+    ]
+    .iter()
+    {
+        let primitive = builder.with_topology(*topology).primitive_state();
+        assert_eq!(primitive.topology, *topology);
+        assert_eq!(primitive.strip_index_format, None);
+    }
+
+    // 16 bit strips are set after the topology
+    let primitive = builder
+        .with_topology(wgpu::PrimitiveTopology::TriangleStrip)
+        .with_strip_index_format(Some(wgpu::IndexFormat::Uint16))
+        .primitive_state();
+    assert_eq!(
+        primitive.strip_index_format,
+        Some(wgpu::IndexFormat::Uint16)
+    );
+}
+
+#[test]
+fn depth_settings_merge() {
+    let stencil = wgpu::StencilState {
+        front: wgpu::StencilFaceState {
+            compare: wgpu::CompareFunction::Equal,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op: wgpu::StencilOperation::Replace,
+        },
+        back: wgpu::StencilFaceState::IGNORE,
+        read_mask: 0xff,
+        write_mask: 0x0f,
+    };
+
+    let mut builder = RenderPipelineBuilder::new();
+    assert!(builder.depth_stencil_state().is_none());
+
+    // Any depth setting enables an always passing test without writes
+    builder
+        .with_depth_bias(2, 1.5, 0.25)
+        .with_stencil(stencil.clone());
+    let state = builder.depth_stencil_state().unwrap();
+    assert_eq!(state.format, DEPTH_FORMAT);
+    assert_eq!(state.depth_compare, wgpu::CompareFunction::Always);
+    assert!(!state.depth_write_enabled);
+
+    // Changing the format and enabling the test keep the bias and stencil
+    builder
+        .with_depth_format(wgpu::TextureFormat::Depth24PlusStencil8)
+        .with_depth_stencil(wgpu::CompareFunction::Less);
+    let state = builder.depth_stencil_state().unwrap();
+    assert_eq!(state.format, wgpu::TextureFormat::Depth24PlusStencil8);
+    assert_eq!(state.depth_compare, wgpu::CompareFunction::Less);
+    assert!(state.depth_write_enabled);
+    assert_eq!(state.stencil, stencil);
+    assert_eq!(state.bias.constant, 2);
+    assert_eq!(state.bias.slope_scale, 1.5);
+    assert_eq!(state.bias.clamp, 0.25);
+
+    // Enabling the test keeps an earlier format, and writes can be turned off after
+    let mut builder = RenderPipelineBuilder::new();
+    builder
+        .with_depth_format(wgpu::TextureFormat::Depth32Float)
+        .with_depth_stencil(wgpu::CompareFunction::GreaterEqual)
+        .with_depth_write(false);
+    let state = builder.depth_stencil_state().unwrap();
+    assert_eq!(state.format, wgpu::TextureFormat::Depth32Float);
+    assert_eq!(state.depth_compare, wgpu::CompareFunction::GreaterEqual);
+    assert!(!state.depth_write_enabled);
+}
+
+#[test]
+fn sample_count() {
+    let mut builder = RenderPipelineBuilder::new();
+    assert_eq!(builder.multisample_state().count, 1);
+    assert_eq!(builder.with_sample_count(4).multisample_state().count, 4);
+}
@@ -0,0 +1,200 @@
+//! Tests for the WGSL preprocessor and shader cache.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use magneto::graphics::{PreprocessedShader, ShaderCache, ShaderDefines, ShaderReflection};
+
+mod common;
+
+fn data_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data/preprocess")
+        .join(name)
+}
+
+#[test]
+fn includes_each_file_once() {
+    let shader =
+        PreprocessedShader::from_file(data_path("lit.wgsl"), &ShaderDefines::new()).unwrap();
+
+    assert_eq!(shader.source.matches("struct Camera").count(), 1);
+    let files: Vec<_> = shader
+        .files
+        .iter()
+        .map(|f| f.file_name().unwrap().to_str().unwrap())
+        .collect();
+    assert_eq!(files, vec!["lit.wgsl", "camera.wgsl", "lighting.wgsl"]);
+    for file in &shader.files {
+        assert_eq!(*file, file.canonicalize().unwrap());
+    }
+    assert_eq!(shader.source.lines().count(), shader.source_map.len());
+
+    ShaderReflection::from_preprocessed(&shader).unwrap();
+}
+
+#[test]
+fn substitutes_defines() {
+    let shader =
+        PreprocessedShader::from_file(data_path("lit.wgsl"), &ShaderDefines::new()).unwrap();
+    assert!(shader.source.contains("return 1.0;"), "{}", shader.source);
+    assert!(shader.source.contains("shade() * 0.5"), "{}", shader.source);
+
+    let defines = ShaderDefines::new().with_value("LIGHT_INTENSITY", 2.5);
+    let shader = PreprocessedShader::from_file(data_path("lit.wgsl"), &defines).unwrap();
+    assert!(shader.source.contains("return 2.5;"), "{}", shader.source);
+
+    let defines = ShaderDefines::new().with_value("x", "y");
+    let shader =
+        PreprocessedShader::from_source("let a = 1e5 + x + xx;", "a.wgsl", &defines).unwrap();
+    assert_eq!(shader.source, "let a = 1e5 + y + xx;\n");
+}
+
+#[test]
+fn selects_permutations() {
+    let lit = PreprocessedShader::from_file(data_path("lit.wgsl"), &ShaderDefines::new()).unwrap();
+    assert!(lit.source.contains("shade()"));
+    assert!(!lit.source.contains("vec4<f32>(1.0, 1.0, 1.0, 1.0)"));
+
+    let unlit =
+        PreprocessedShader::from_file(data_path("lit.wgsl"), &ShaderDefines::new().with("UNLIT"))
+            .unwrap();
+    assert!(unlit.source.contains("vec4<f32>(1.0, 1.0, 1.0, 1.0)"));
+    assert!(!unlit.source.contains("shade() *"));
+    ShaderReflection::from_preprocessed(&unlit).unwrap();
+
+    let nested = "#ifdef A\n#ifdef B\nab\n#else\na\n#endif\n#else\nnone\n#endif\n";
+    let run = |defines: ShaderDefines| {
+        PreprocessedShader::from_source(nested, "n.wgsl", &defines)
+            .unwrap()
+            .source
+    };
+    assert_eq!(run(ShaderDefines::new()), "none\n");
+    assert_eq!(run(ShaderDefines::new().with("A")), "a\n");
+    assert_eq!(run(ShaderDefines::new().with("A").with("B")), "ab\n");
+    assert_eq!(run(ShaderDefines::new().with("B")), "none\n");
+}
+
+#[test]
+fn maps_lines_to_original_files() {
+    let shader =
+        PreprocessedShader::from_file(data_path("lit.wgsl"), &ShaderDefines::new()).unwrap();
+    let line = shader
+        .source
+        .lines()
+        .position(|l| l.contains("return 1.0;"))
+        .unwrap();
+    let location = shader.location(line + 1).unwrap();
+    assert!(location.file.ends_with("common/lighting.wgsl"));
+    assert_eq!(location.line, 8);
+}
+
+#[test]
+fn reports_errors_at_original_location() {
+    let shader =
+        PreprocessedShader::from_file(data_path("broken.wgsl"), &ShaderDefines::new()).unwrap();
+    let err = ShaderReflection::from_preprocessed(&shader)
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("typo.wgsl:3:"), "{}", err);
+    assert!(err.contains("return x +;"), "{}", err);
+
+    let err = PreprocessedShader::from_file(data_path("unterminated.wgsl"), &ShaderDefines::new())
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("unterminated.wgsl:3: Unterminated #ifdef"),
+        "{}",
+        err
+    );
+
+    let err = PreprocessedShader::from_source(
+        "#include \"missing.wgsl\"",
+        "m.wgsl",
+        &ShaderDefines::new(),
+    )
+    .unwrap_err()
+    .to_string();
+    assert!(err.contains("m.wgsl:1: Unable to include"), "{}", err);
+
+    // Validation errors have no line, but name the root file
+    let shader = PreprocessedShader::from_source(
+        "fn f() -> f32 { return 1u; }",
+        "v.wgsl",
+        &ShaderDefines::new(),
+    )
+    .unwrap();
+    let err = format!(
+        "{:#}",
+        ShaderReflection::from_preprocessed(&shader).unwrap_err()
+    );
+    assert!(err.starts_with("In shader v.wgsl: "), "{}", err);
+
+    let err = PreprocessedShader::from_source("#pragma once", "p.wgsl", &ShaderDefines::new())
+        .unwrap_err()
+        .to_string();
+    assert!(
+        err.contains("p.wgsl:1: Unknown directive #pragma"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn caches_permutations() {
+    let dpy = common::headless_display();
+
+    let mut cache = ShaderCache::new();
+    let path = data_path("lit.wgsl");
+    let unlit = ShaderDefines::new().with("UNLIT");
+
+    let a = cache
+        .get(&dpy.device, &path, &ShaderDefines::new())
+        .unwrap();
+    let b = cache
+        .get(&dpy.device, &path, &ShaderDefines::new())
+        .unwrap();
+    let c = cache.get(&dpy.device, &path, &unlit).unwrap();
+    assert!(Arc::ptr_eq(&a, &b));
+    assert!(!Arc::ptr_eq(&a, &c));
+
+    // Other paths to the same file share the cached shader
+    let other_path = data_path("common/../lit.wgsl");
+    let e = cache
+        .get(&dpy.device, &other_path, &ShaderDefines::new())
+        .unwrap();
+    assert!(Arc::ptr_eq(&a, &e));
+
+    assert_eq!(cache.invalidate(data_path("common/camera.wgsl")), 2);
+    let d = cache
+        .get(&dpy.device, &path, &ShaderDefines::new())
+        .unwrap();
+    assert!(!Arc::ptr_eq(&a, &d));
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn invalidates_deleted_includes() {
+    let dpy = common::headless_display();
+
+    // Work on a copy, as an include is deleted
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join("shader_cache_deleted");
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("common")).unwrap();
+    for file in &["lit.wgsl", "common/camera.wgsl", "common/lighting.wgsl"] {
+        std::fs::copy(data_path(file), dir.join(file)).unwrap();
+    }
+
+    let mut cache = ShaderCache::new();
+    cache
+        .get(&dpy.device, dir.join("lit.wgsl"), &ShaderDefines::new())
+        .unwrap();
+
+    std::fs::remove_file(dir.join("common/lighting.wgsl")).unwrap();
+    assert_eq!(
+        cache.invalidate(dir.join("common/../common/lighting.wgsl")),
+        1
+    );
+    assert_eq!(cache.files().count(), 0);
+}
@@ -0,0 +1,77 @@
+//! Tests for the camera projections.
+
+use magneto::camera::{InfinitePerspective, Orthographic, Perspective, Projection};
+use nalgebra::{Matrix4, Vector4};
+use winit::dpi::PhysicalSize;
+
+mod common;
+
+/// Clip space depth of a view space point `z` units along the view axis.
+fn depth(projection: &Matrix4<f32>, z: f32) -> f32 {
+    let clip = projection * Vector4::new(0.0, 0.0, z, 1.0);
+    clip.z / clip.w
+}
+
+fn assert_near(actual: f32, expected: f32) {
+    assert!(
+        (actual - expected).abs() < 1e-5,
+        "expected {}, got {}",
+        expected,
+        actual
+    );
+}
+
+#[test]
+fn perspective_depth_range() {
+    let projection = Perspective::new(1.0, 1.5, 0.1, 100.0).projection_matrix();
+    assert_near(depth(&projection, 0.1), 0.0);
+    assert_near(depth(&projection, 100.0), 1.0);
+    assert!(depth(&projection, 10.0) > depth(&projection, 1.0));
+}
+
+#[test]
+fn infinite_perspective_depth_is_reversed() {
+    let projection = InfinitePerspective::new(1.0, 1.5, 0.1).projection_matrix();
+    assert_near(depth(&projection, 0.1), 1.0);
+    assert!(depth(&projection, 1.0e6) < 1.0e-5);
+    assert!(depth(&projection, 1.0) > depth(&projection, 10.0));
+}
+
+#[test]
+fn orthographic_depth_range() {
+    let projection = Orthographic::new(10.0, 2.0, 1.0, 50.0).projection_matrix();
+    assert_near(depth(&projection, 1.0), 0.0);
+    assert_near(depth(&projection, 50.0), 1.0);
+
+    // The visible height is fixed and the width follows the aspect ratio
+    let corner = projection * Vector4::new(10.0, 5.0, 1.0, 1.0);
+    assert_near(corner.x, 1.0);
+    assert_near(corner.y, 1.0);
+}
+
+#[test]
+fn resize_updates_aspect() {
+    let mut projection = Perspective::new(1.0, 1.0, 0.1, 100.0);
+    projection.resize(PhysicalSize::new(1280, 720));
+    assert_near(projection.aspect, 1280.0 / 720.0);
+
+    projection.resize(PhysicalSize::new(0, 720));
+    assert_near(projection.aspect, 1280.0 / 720.0);
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn projection_tracks_display_reload() {
+    let mut dpy = common::headless_display();
+    let mut projection = Perspective::for_display(&dpy, 1.0, 0.1, 100.0);
+    assert_near(projection.aspect, 1.0);
+
+    projection.resize(dpy.reload_swapchain(PhysicalSize::new(200, 100)));
+    assert_near(projection.aspect, 2.0);
+    assert_near(Perspective::for_display(&dpy, 1.0, 0.1, 100.0).aspect, 2.0);
+
+    // Minimising keeps the last aspect ratio
+    projection.resize(dpy.reload_swapchain(PhysicalSize::new(0, 0)));
+    assert!(dpy.is_minimised());
+    assert_near(projection.aspect, 2.0);
+}
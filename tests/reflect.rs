@@ -0,0 +1,258 @@
+//! Tests for WGSL shader reflection.
+
+use std::path::{Path, PathBuf};
+
+use bytemuck::{Pod, Zeroable};
+use magneto::graphics::{
+    BasicVertex, DeviceUtilExt, HasLayout, RenderPipelineBuilder, ShaderReflection,
+};
+
+mod common;
+
+fn data_path(name: &str) -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/data")
+        .join(name)
+}
+
+fn material() -> ShaderReflection {
+    let src = std::fs::read_to_string(data_path("reflect/material.wgsl")).unwrap();
+    ShaderReflection::from_wgsl(&src).unwrap()
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable, HasLayout)]
+struct Layer {
+    layer: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable, HasLayout)]
+struct FloatLayer {
+    layer: f32,
+}
+
+#[test]
+fn reflects_bindings() {
+    let reflection = material();
+    let bindings: Vec<_> = reflection
+        .bindings
+        .iter()
+        .map(|b| {
+            (
+                b.name.as_deref().unwrap(),
+                b.group,
+                b.binding,
+                b.visibility,
+                b.ty,
+            )
+        })
+        .collect();
+
+    assert_eq!(
+        bindings,
+        vec![
+            (
+                "camera",
+                0,
+                0,
+                wgpu::ShaderStage::VERTEX,
+                wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+            ),
+            (
+                "albedo",
+                1,
+                0,
+                wgpu::ShaderStage::FRAGMENT,
+                wgpu::BindingType::Texture {
+                    sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    view_dimension: wgpu::TextureViewDimension::D2,
+                    multisampled: false,
+                },
+            ),
+            (
+                "albedo_sampler",
+                1,
+                1,
+                wgpu::ShaderStage::FRAGMENT,
+                wgpu::BindingType::Sampler {
+                    filtering: true,
+                    comparison: false,
+                },
+            ),
+            (
+                "lights",
+                1,
+                3,
+                wgpu::ShaderStage::FRAGMENT,
+                wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage { read_only: true },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+            ),
+        ]
+    );
+    assert_eq!(reflection.group_count(), 2);
+    assert_eq!(reflection.bind_group_layout_entries(1).len(), 3);
+}
+
+#[test]
+fn reflects_vertex_inputs() {
+    let reflection = material();
+    let ep = reflection
+        .entry_point("vs_main", wgpu::ShaderStage::VERTEX)
+        .unwrap();
+    let inputs: Vec<_> = ep
+        .vertex_inputs
+        .iter()
+        .map(|i| (i.name.as_deref().unwrap(), i.location, i.format))
+        .collect();
+
+    assert_eq!(
+        inputs,
+        vec![
+            ("position", 0, wgpu::VertexFormat::Float32x3),
+            ("texture_coord", 2, wgpu::VertexFormat::Float32x2),
+            ("layer", 5, wgpu::VertexFormat::Uint32),
+        ]
+    );
+    assert!(reflection
+        .entry_point("vs_main", wgpu::ShaderStage::FRAGMENT)
+        .is_none());
+}
+
+#[test]
+fn checks_vertex_layouts() {
+    let reflection = material();
+    let vertex = BasicVertex::layout(0);
+
+    reflection
+        .check_vertex_layout("vs_main", &[&vertex, &Layer::layout(5)])
+        .unwrap();
+
+    let missing = reflection
+        .check_vertex_layout("vs_main", &[&vertex])
+        .unwrap_err()
+        .to_string();
+    assert!(missing.contains("`layer` at location 5"), "{}", missing);
+
+    let mismatched = reflection
+        .check_vertex_layout("vs_main", &[&vertex, &FloatLayer::layout(5)])
+        .unwrap_err()
+        .to_string();
+    assert!(mismatched.contains("Uint32"), "{}", mismatched);
+    assert!(mismatched.contains("Float32"), "{}", mismatched);
+
+    assert!(reflection.check_vertex_layout("main", &[&vertex]).is_err());
+}
+
+#[test]
+fn parse_errors_point_at_source() {
+    let err = ShaderReflection::from_wgsl("fn main() -> f32 { return 1.0 }")
+        .unwrap_err()
+        .to_string();
+    assert!(err.contains("wgsl:1:"), "{}", err);
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn pipeline_checks_shader_inputs() {
+    let dpy = common::headless_display();
+
+    let shader = dpy
+        .device
+        .reflect_shader_from_file(data_path("golden/scene.wgsl"))
+        .unwrap();
+    assert_eq!(shader.bind_group_layouts.len(), 1);
+
+    RenderPipelineBuilder::new()
+        .with_shader(&shader)
+        .with_vertex_entry_point("vs_main")
+        .with_fragment_entry_point("fs_main")
+        .push_vertex_buffer_layout::<BasicVertex>()
+        .try_build_for_display(&dpy)
+        .unwrap();
+
+    let err = RenderPipelineBuilder::new()
+        .with_shader(&shader)
+        .with_vertex_entry_point("vs_main")
+        .with_fragment_entry_point("fs_main")
+        .push_vertex_buffer_layout::<Layer>()
+        .try_build_for_display(&dpy)
+        .unwrap_err();
+    assert!(
+        err.to_string().contains("`normal` at location 1"),
+        "{}",
+        err
+    );
+}
+
+#[test]
+fn derives_filtering_from_usage() {
+    let src = r#"
+[[group(0), binding(0)]]
+var shadow_map: texture_depth_2d;
+[[group(0), binding(1)]]
+var shadow_sampler: sampler;
+[[group(0), binding(2)]]
+var ids: texture_2d<u32>;
+[[group(0), binding(3)]]
+var samples: texture_multisampled_2d<f32>;
+[[group(0), binding(4)]]
+var heights: texture_2d<f32>;
+[[group(0), binding(5)]]
+var colours: texture_2d<f32>;
+[[group(0), binding(6)]]
+var colour_sampler: sampler;
+[[group(0), binding(7)]]
+var masks: texture_2d<f32>;
+
+[[stage(fragment)]]
+fn fs_main() -> [[location(0)]] vec4<f32> {
+    let uv = vec2<f32>(0.5, 0.5);
+    let depth = textureSample(shadow_map, shadow_sampler, uv);
+    let mask = textureSample(masks, shadow_sampler, uv);
+    let id = textureLoad(ids, vec2<i32>(0, 0), 0);
+    let sample = textureLoad(samples, vec2<i32>(0, 0), 0);
+    let height = textureLoad(heights, vec2<i32>(0, 0), 0);
+    let colour = textureSample(colours, colour_sampler, uv);
+    return colour * sample * mask * height.x * depth * f32(id.x);
+}
+"#;
+    let reflection = ShaderReflection::from_wgsl(src).unwrap();
+    let types: Vec<_> = reflection.bindings.iter().map(|b| b.ty).collect();
+    let texture = |sample_type, multisampled| wgpu::BindingType::Texture {
+        sample_type,
+        view_dimension: wgpu::TextureViewDimension::D2,
+        multisampled,
+    };
+    let unfilterable = wgpu::TextureSampleType::Float { filterable: false };
+
+    assert_eq!(
+        types,
+        vec![
+            texture(wgpu::TextureSampleType::Depth, false),
+            // Used with a depth texture
+            wgpu::BindingType::Sampler {
+                filtering: false,
+                comparison: false,
+            },
+            texture(wgpu::TextureSampleType::Uint, false),
+            texture(unfilterable, true),
+            // Only read with textureLoad
+            texture(unfilterable, false),
+            texture(wgpu::TextureSampleType::Float { filterable: true }, false),
+            wgpu::BindingType::Sampler {
+                filtering: true,
+                comparison: false,
+            },
+            // Only sampled through a non-filtering sampler
+            texture(unfilterable, false),
+        ]
+    );
+}
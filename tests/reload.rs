@@ -0,0 +1,124 @@
+//! Tests for shader hot reloading.
+
+use std::cell::Cell;
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::time::{Duration, SystemTime};
+
+use magneto::graphics::{BasicVertex, FileWatcher, RenderPipelineBuilder, ShaderReloader};
+
+mod common;
+
+/// A fresh directory for one test's files.
+fn temp_dir(name: &str) -> PathBuf {
+    let dir = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Writes `contents` to `path` and moves its modification time past the previous one, so the
+/// change is seen even on filesystems with coarse timestamps.
+fn write(path: &Path, contents: &str) {
+    let previous = std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    std::fs::write(path, contents).unwrap();
+
+    let now = SystemTime::now();
+    let later = previous.map_or(now, |p| p.max(now)) + Duration::from_secs(1);
+    File::options()
+        .write(true)
+        .open(path)
+        .unwrap()
+        .set_modified(later)
+        .unwrap();
+}
+
+#[test]
+fn file_watcher_detects_changes() {
+    let dir = temp_dir("file_watcher");
+    let file = dir.join("a.wgsl");
+    write(&file, "a");
+
+    let mut watcher = FileWatcher::new();
+    watcher.watch([&file]);
+    assert!(!watcher.poll());
+
+    write(&file, "b");
+    assert!(watcher.poll());
+    assert!(!watcher.poll());
+
+    std::fs::remove_file(&file).unwrap();
+    assert!(watcher.poll());
+    assert!(!watcher.poll());
+
+    write(&file, "c");
+    assert!(watcher.poll());
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn reload_keeps_old_pipeline_on_error() {
+    let dpy = common::headless_display();
+
+    let scene = std::fs::read_to_string(
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data/golden/scene.wgsl"),
+    )
+    .unwrap();
+    let dir = temp_dir("shader_reload");
+    let path = dir.join("scene.wgsl");
+    write(&path, &scene);
+
+    let mut reloader = ShaderReloader::new();
+    reloader.poll_interval = Duration::ZERO;
+    let shader = reloader.shader_from_file(&dpy.device, &path).unwrap();
+
+    let builds = Rc::new(Cell::new(0));
+    let counter = builds.clone();
+    reloader
+        .add_pipeline(&dpy, shader, move |dpy, shader| {
+            counter.set(counter.get() + 1);
+            RenderPipelineBuilder::new()
+                .with_shader(shader)
+                .with_vertex_entry_point("vs_main")
+                .with_fragment_entry_point("fs_main")
+                .push_vertex_buffer_layout::<BasicVertex>()
+                .try_build_for_display(dpy)
+        })
+        .unwrap();
+    assert_eq!(builds.get(), 1);
+    assert!(reloader.update(&dpy).is_empty());
+
+    // Invalid WGSL keeps the old shader without rebuilding
+    write(&path, &scene.replace("return out;", "return out"));
+    assert!(reloader.update(&dpy).is_empty());
+    assert_eq!(builds.get(), 1);
+
+    // Valid WGSL whose inputs the pipeline's vertex layout does not provide
+    write(
+        &path,
+        &scene.replace(
+            "[[location(2)]] texture_coord: vec2<f32>,",
+            "[[location(2)]] texture_coord: vec2<f32>,\n    [[location(3)]] tangent: vec4<f32>,",
+        ),
+    );
+    assert!(reloader.update(&dpy).is_empty());
+    assert_eq!(builds.get(), 2);
+
+    // Valid WGSL that only wgpu rejects, as the fragment stage reads a location the vertex
+    // stage does not write
+    write(
+        &path,
+        &scene.replace(
+            "fn fs_main(in: VertexOutput)",
+            "fn fs_main(in: VertexOutput, [[location(2)]] extra: vec4<f32>)",
+        ),
+    );
+    assert!(reloader.update(&dpy).is_empty());
+    assert_eq!(builds.get(), 3);
+
+    write(&path, &scene.replace("0.2 + 0.8", "0.3 + 0.7"));
+    assert_eq!(reloader.update(&dpy), vec![shader]);
+    assert_eq!(builds.get(), 4);
+    assert!(reloader.update(&dpy).is_empty());
+}
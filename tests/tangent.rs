@@ -0,0 +1,141 @@
+//! Tests for MikkTSpace tangent generation.
+
+use bytemuck::{Pod, Zeroable};
+use magneto::graphics::{generate_tangents, BasicVertex, HasLayout, TangentVertex, Vertex};
+
+/// A unit quad in the XZ plane facing +Y, with `u` along +X and `v` along +Z.
+fn quad<V: Vertex>() -> (Vec<V>, Vec<u32>) {
+    let normal = [0.0, 1.0, 0.0];
+    let vertices = vec![
+        V::with_features([0.0, 0.0, 0.0], normal, [0.0, 0.0]),
+        V::with_features([1.0, 0.0, 0.0], normal, [1.0, 0.0]),
+        V::with_features([1.0, 0.0, 1.0], normal, [1.0, 1.0]),
+        V::with_features([0.0, 0.0, 1.0], normal, [0.0, 1.0]),
+    ];
+    (vertices, vec![0, 3, 1, 1, 3, 2])
+}
+
+#[test]
+fn quad_tangent_follows_u() {
+    let (mut vertices, mut indices) = quad::<TangentVertex>();
+    assert!(generate_tangents(&mut vertices, &mut indices));
+
+    // Every corner agrees, so no vertex is split
+    assert_eq!(vertices.len(), 4);
+    assert_eq!(indices, [0, 3, 1, 1, 3, 2]);
+    for v in &vertices {
+        let [x, y, z, w] = v.tangent();
+        assert!((x - 1.0).abs() < 1e-5 && y.abs() < 1e-5 && z.abs() < 1e-5);
+        assert_eq!(w.abs(), 1.0);
+    }
+}
+
+#[test]
+fn mirrored_uvs_flip_the_bitangent_sign() {
+    let (mut vertices, mut indices) = quad::<TangentVertex>();
+    assert!(generate_tangents(&mut vertices, &mut indices));
+    let sign = vertices[0].tangent()[3];
+
+    let (mut mirrored, mut indices) = quad::<TangentVertex>();
+    for v in &mut mirrored {
+        v.texture_coord[1] = 1.0 - v.texture_coord[1];
+    }
+    assert!(generate_tangents(&mut mirrored, &mut indices));
+    for v in &mirrored {
+        assert_eq!(v.tangent()[3], -sign);
+    }
+}
+
+#[test]
+fn vertices_without_tangents_are_untouched() {
+    let (original, original_indices) = quad::<BasicVertex>();
+    let (mut vertices, mut indices) = (original.clone(), original_indices.clone());
+    assert!(generate_tangents(&mut vertices, &mut indices));
+
+    assert_eq!(indices, original_indices);
+    assert_eq!(
+        bytemuck::cast_slice::<_, u8>(&vertices),
+        bytemuck::cast_slice::<_, u8>(&original)
+    );
+}
+
+#[test]
+fn out_of_range_indices_are_rejected() {
+    let (mut vertices, _) = quad::<TangentVertex>();
+    let mut indices = vec![0, 3, 1, 1, 3, 4];
+    assert!(!generate_tangents(&mut vertices, &mut indices));
+
+    assert_eq!(vertices.len(), 4);
+    assert_eq!(indices, [0, 3, 1, 1, 3, 4]);
+    assert!(vertices.iter().all(|v| v.tangent() == [0.0; 4]));
+}
+
+/// A vertex with an attribute `with_tangent` cannot set, which it keeps in `set_tangent`.
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable, HasLayout)]
+struct ColourVertex {
+    position: [f32; 3],
+    normal: [f32; 3],
+    texture_coord: [f32; 2],
+    tangent: [f32; 4],
+    colour: [f32; 4],
+}
+
+impl Vertex for ColourVertex {
+    const HAS_TANGENT: bool = true;
+
+    fn with_features(position: [f32; 3], normal: [f32; 3], texture_coord: [f32; 2]) -> Self {
+        ColourVertex {
+            position,
+            normal,
+            texture_coord,
+            tangent: [0.0; 4],
+            colour: [1.0; 4],
+        }
+    }
+
+    fn position(&self) -> [f32; 3] {
+        self.position
+    }
+
+    fn normal(&self) -> [f32; 3] {
+        self.normal
+    }
+
+    fn texture_coord(&self) -> [f32; 2] {
+        self.texture_coord
+    }
+
+    fn tangent(&self) -> [f32; 4] {
+        self.tangent
+    }
+
+    fn set_tangent(&mut self, tangent: [f32; 4]) {
+        self.tangent = tangent;
+    }
+}
+
+#[test]
+fn set_tangent_keeps_other_attributes() {
+    let (mut vertices, _) = quad::<ColourVertex>();
+    for (i, v) in vertices.iter_mut().enumerate() {
+        v.colour = [i as f32, 0.5, 0.25, 1.0];
+    }
+    // Mirror the second triangle's UVs so that shared vertices are split
+    vertices.push(ColourVertex {
+        texture_coord: [1.0, -1.0],
+        colour: [4.0, 0.5, 0.25, 1.0],
+        ..vertices[2]
+    });
+    let mut indices = vec![0, 3, 1, 1, 3, 4];
+    assert!(generate_tangents(&mut vertices, &mut indices));
+    assert!(vertices.len() > 5, "no vertex was split");
+
+    for &i in &indices {
+        let v = &vertices[i as usize];
+        assert_ne!(v.tangent(), [0.0; 4]);
+        assert_eq!(v.colour[1..], [0.5, 0.25, 1.0]);
+    }
+    assert_eq!(vertices[0].colour[0], 0.0);
+    assert_eq!(vertices[4].colour[0], 4.0);
+}
@@ -0,0 +1,92 @@
+//! Tests for `#[derive(UniformLayout)]` and `UniformBuffer`.
+
+use bytemuck::{Pod, Zeroable};
+use magneto::graphics::{UniformBuffer, UniformLayout};
+use nalgebra::{Matrix4, Vector3};
+
+mod common;
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable, UniformLayout)]
+struct Light {
+    position: Vector3<f32>,
+    intensity: f32,
+    colour: [f32; 3],
+    #[uniform(skip)]
+    _padding: u32,
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable, UniformLayout)]
+struct Scene {
+    view_projection: Matrix4<f32>,
+    time: f32,
+    #[uniform(skip)]
+    _padding: [u32; 3],
+    lights: [Light; 2],
+    normal_matrix: [[f32; 4]; 3],
+    size: [f32; 2],
+    exposure: f32,
+    #[uniform(skip)]
+    _padding_end: u32,
+}
+
+#[test]
+fn derived_layouts_match_wgsl() {
+    assert_eq!(Light::ALIGN, 16);
+    assert_eq!(Light::SIZE, 32);
+    assert_eq!(Scene::SIZE, 64 + 16 + 64 + 48 + 16);
+}
+
+#[repr(C)]
+#[derive(Copy, Clone, Pod, Zeroable, UniformLayout)]
+struct Tuple(Vector3<f32>, f32);
+
+#[test]
+fn tuple_structs_derive() {
+    assert_eq!(Tuple::SIZE, 16);
+}
+
+#[test]
+#[ignore = "needs an adapter"]
+fn uniform_buffer_writes_through_queue() {
+    let dpy = common::headless_display();
+
+    let layout =
+        UniformBuffer::<Light>::create_bind_group_layout(&dpy, wgpu::ShaderStage::FRAGMENT);
+    let mut light = UniformBuffer::new(&dpy, &layout, Light::zeroed());
+    light.set(
+        &dpy,
+        Light {
+            position: Vector3::new(1.0, 2.0, 3.0),
+            intensity: 4.0,
+            colour: [5.0, 6.0, 7.0],
+            _padding: 0,
+        },
+    );
+    light.update(&dpy, |l| l.intensity *= 2.0);
+    assert_eq!(light.get().intensity, 8.0);
+
+    // Copy the buffer back to check the queued writes reached the GPU
+    let size = std::mem::size_of::<Light>() as wgpu::BufferAddress;
+    let readback = dpy.device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("readback"),
+        size,
+        usage: wgpu::BufferUsage::COPY_DST | wgpu::BufferUsage::MAP_READ,
+        mapped_at_creation: false,
+    });
+    let mut encoder = dpy
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_buffer_to_buffer(light.buffer(), 0, &readback, 0, size);
+    dpy.queue.submit(std::iter::once(encoder.finish()));
+
+    let slice = readback.slice(..);
+    let mapping = slice.map_async(wgpu::MapMode::Read);
+    dpy.device.poll(wgpu::Maintain::Wait);
+    pollster::block_on(mapping).unwrap();
+    assert_eq!(
+        &slice.get_mapped_range()[..],
+        bytemuck::bytes_of(light.get())
+    );
+}